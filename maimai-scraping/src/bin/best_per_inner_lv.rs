@@ -5,9 +5,9 @@ use clap::Parser;
 use hashbrown::HashMap;
 use itertools::Itertools;
 use maimai_scraping::maimai::{
-    associated_user_data,
+    associated_user_data, deluxscore,
     rating::{InternalScoreLevel, ScoreConstant},
-    schema::latest::AchievementValue,
+    schema::latest::{AchievementValue, ValueWithMax},
     song_list::{database::SongDatabase, Song},
     version::MaimaiVersion,
     MaimaiUserData,
@@ -32,10 +32,16 @@ fn main() -> anyhow::Result<()> {
 
     let zero = AchievementValue::try_from(0).unwrap();
     let mut best = HashMap::<_, AchievementValue>::new();
+    let mut best_dx = HashMap::<_, ValueWithMax<u32>>::new();
     for score in data.ordinary_data_associated()?.ordinary_records() {
         let a = score.record().achievement_result().value();
         let x = best.entry(score.score().score()).or_insert(zero);
         *x = (*x).max(a);
+        let d = score.record().deluxscore_result().score();
+        let x = best_dx.entry(score.score().score()).or_insert(d);
+        if x.value() < d.value() {
+            *x = d;
+        }
     }
 
     let lv: ScoreConstant = opts
@@ -50,7 +56,19 @@ fn main() -> anyhow::Result<()> {
         .map(|score| (best.get(&score).copied().unwrap_or(zero), score))
         .sorted()
     {
-        println!("{a:8} {s}");
+        let dx = best_dx.get(&s).map_or_else(String::new, |&d| {
+            let next = match deluxscore::to_next_star(d) {
+                Some((next, required)) => format!("+{required} to ☆{next}"),
+                None => "max".to_owned(),
+            };
+            format!(
+                "☆{} {}/{} ({next})",
+                deluxscore::stars(d).get(),
+                d.value(),
+                d.max()
+            )
+        });
+        println!("{a:8} {dx:28} {s}");
     }
 
     Ok(())
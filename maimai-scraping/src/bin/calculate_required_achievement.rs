@@ -5,6 +5,7 @@ use clap::Parser;
 use inquire::{CustomType, InquireError};
 use lazy_format::lazy_format;
 use maimai_scraping::maimai::{
+    deluxscore,
     rating::{rank_coef, single_song_rating, ScoreConstant},
     schema::latest::AchievementValue,
 };
@@ -13,6 +14,9 @@ use maimai_scraping::maimai::{
 struct Opts {
     #[arg(long)]
     all: bool,
+    /// Show the DX score borders for each star instead
+    #[arg(long)]
+    deluxscore_max: Option<u32>,
 }
 
 macro_rules! check {
@@ -26,6 +30,13 @@ macro_rules! check {
 }
 fn main() -> anyhow::Result<()> {
     let opts = Opts::parse();
+    if let Some(max) = opts.deluxscore_max {
+        for stars in 1..=5 {
+            let border = deluxscore::border(max, stars).unwrap();
+            println!("☆{stars} {border} (-{})", max - border);
+        }
+        return Ok(());
+    }
     let rating: u16 = check!(CustomType::new("Single song rating").prompt())?;
     if opts.all {
         for level in ScoreConstant::candidates() {
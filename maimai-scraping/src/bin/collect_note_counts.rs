@@ -0,0 +1,88 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use clap::Parser;
+use log::warn;
+use maimai_scraping::maimai::{
+    achievement::ScoreNotes,
+    associated_user_data,
+    record_verifier::infer_note_profile,
+    song_list::{database::SongDatabase, Song},
+    MaimaiUserData,
+};
+use maimai_scraping_utils::fs_json_util::{read_json, write_json};
+
+/// Collects note counts of scores from play records into `note_counts.json`.
+#[derive(Parser)]
+struct Opts {
+    database: PathBuf,
+    user_data: PathBuf,
+    /// Existing entries are kept, and new ones are appended.
+    note_counts: PathBuf,
+}
+
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+    let opts = Opts::parse();
+    let songs: Vec<Song> = read_json(&opts.database)?;
+    let database = SongDatabase::new(&songs)?;
+    let user_data: MaimaiUserData = read_json(&opts.user_data)?;
+    let data = associated_user_data::UserData::annotate(&database, &user_data)?;
+
+    let mut map = BTreeMap::new();
+    if opts.note_counts.is_file() {
+        let entries: Vec<ScoreNotes> = read_json(&opts.note_counts)?;
+        for entry in entries {
+            map.insert(
+                (entry.icon, entry.generation, entry.difficulty),
+                entry.notes,
+            );
+        }
+    }
+    let before = map.len();
+
+    let mut records = BTreeMap::<_, Vec<_>>::new();
+    for record in data.records().values() {
+        let Some(Ok(record)) = record.as_ordinary().map(|r| r.into_associated()) else {
+            continue;
+        };
+        records
+            .entry(record.score().score())
+            .or_default()
+            .push(record.record());
+    }
+    for (score, records) in records {
+        let Some(icon) = score.scores().song().song().icon.clone() else {
+            continue;
+        };
+        let candidates = infer_note_profile(records);
+        let Some(&(notes, _)) = candidates.first() else {
+            continue;
+        };
+        if candidates.len() > 1 {
+            warn!("{score}: records disagree on note counts: {candidates:?}");
+        }
+        let key = (icon, score.scores().generation(), score.difficulty());
+        match map.get(&key) {
+            Some(&existing) if existing != notes => {
+                warn!("{score}: stored {existing:?}, but inferred {notes:?} from records");
+            }
+            Some(_) => {}
+            None => {
+                map.insert(key, notes);
+            }
+        }
+    }
+
+    println!("Added {} entries", map.len() - before);
+    let entries: Vec<_> = map
+        .into_iter()
+        .map(|((icon, generation, difficulty), notes)| ScoreNotes {
+            icon,
+            generation,
+            difficulty,
+            notes,
+        })
+        .collect();
+    write_json(&opts.note_counts, &entries)?;
+    Ok(())
+}
@@ -0,0 +1,80 @@
+//! Downloads the official song list, and saves it as a snapshot if it has changed.
+
+use std::path::PathBuf;
+
+use clap::{Parser, ValueEnum};
+use fs_err::read_to_string;
+use itertools::Itertools;
+use log::info;
+use maimai_scraping::{
+    chrono_util::jst_now,
+    maimai::song_list::official_snapshot::{self, OfficialListDiff},
+};
+
+#[derive(Parser)]
+struct Opts {
+    snapshot_dir: PathBuf,
+    /// Can point at a local server for testing
+    #[arg(long, default_value = official_snapshot::DEFAULT_URL)]
+    url: String,
+    /// Save a snapshot even if nothing has changed since the previous one
+    #[arg(long)]
+    save_unchanged: bool,
+    #[arg(long, value_enum, default_value = "text")]
+    format: Format,
+    /// Instead of the diff, print the paths of all snapshots in order,
+    /// to be passed to `make_song_database_new`
+    #[arg(long)]
+    print_paths: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Text,
+    Json,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    env_logger::builder().format_timestamp_nanos().init();
+    let opts = Opts::parse();
+
+    let timestamp = jst_now();
+    let text = official_snapshot::fetch(&opts.url).await?;
+    let songs = official_snapshot::parse(&text)?;
+
+    let snapshots = official_snapshot::list(&opts.snapshot_dir)?;
+    let previous = match snapshots.last() {
+        Some((_, path)) => {
+            info!("Comparing with {path:?}");
+            official_snapshot::parse(&read_to_string(path)?)?
+        }
+        None => vec![],
+    };
+    let diff = OfficialListDiff::new(&previous, &songs);
+
+    if diff.is_empty() && !snapshots.is_empty() && !opts.save_unchanged {
+        info!("No changes since the previous snapshot");
+    } else {
+        let path = opts
+            .snapshot_dir
+            .join(official_snapshot::file_name(timestamp));
+        fs_err::write(&path, &text)?;
+        info!("Saved a snapshot to {path:?}");
+    }
+
+    if opts.print_paths {
+        let snapshots = official_snapshot::list(&opts.snapshot_dir)?;
+        println!(
+            "{}",
+            snapshots.iter().map(|(_, path)| path.display()).join(" ")
+        );
+    } else {
+        match opts.format {
+            Format::Text => print!("{diff}"),
+            Format::Json => println!("{}", serde_json::to_string_pretty(&diff)?),
+        }
+    }
+
+    Ok(())
+}
@@ -1,5 +1,6 @@
 use std::{borrow::Cow, path::PathBuf};
 
+use anyhow::bail;
 use clap::{Parser, ValueEnum};
 use hashbrown::HashSet;
 use joinery::JoinableIterator;
@@ -7,13 +8,13 @@ use lazy_format::lazy_format;
 use maimai_scraping::maimai::{
     internal_lv_estimator::{
         self,
-        multi_user::{update_all, RecordLabel},
+        multi_user::{self, update_all, RecordLabel},
         Estimator, Reason,
     },
     song_list::{self, database::SongDatabase},
     version::MaimaiVersion,
 };
-use maimai_scraping_utils::fs_json_util::{read_json, read_toml};
+use maimai_scraping_utils::fs_json_util::{read_json, read_toml, write_json};
 
 #[derive(Parser)]
 struct Opts {
@@ -26,6 +27,22 @@ struct Opts {
 
     #[arg(long)]
     version: Option<MaimaiVersion>,
+
+    /// Start from the estimator state saved in this snapshot
+    #[arg(long)]
+    load_snapshot: Option<PathBuf>,
+    /// Save the estimator state to this path after estimation
+    #[arg(long)]
+    save_snapshot: Option<PathBuf>,
+
+    /// Revisit only the lists affected by new events, and report the number of
+    /// determined scores compared to evaluating every list once
+    #[arg(long)]
+    propagate: bool,
+
+    /// On contradiction, print a minimal set of conflicting sources for each score
+    #[arg(long)]
+    diagnose: bool,
 }
 
 #[derive(Clone, Copy, ValueEnum)]
@@ -47,13 +64,49 @@ fn main() -> anyhow::Result<()> {
 
     let version = opts.version.unwrap_or(MaimaiVersion::latest());
 
-    let mut estimator = if opts.distrust {
+    let mut estimator = if let Some(path) = &opts.load_snapshot {
+        let snapshot = read_json(path)?;
+        let estimator = multi_user::from_snapshot(&database, &config, snapshot)?;
+        if estimator.version() != version {
+            bail!(
+                "The snapshot is for {:?}, not {version:?}",
+                estimator.version()
+            );
+        }
+        estimator
+    } else if opts.distrust {
         Estimator::new_distrust_all(&database, version)?
     } else {
         Estimator::new(&database, version)?
     };
     let before_len = estimator.event_len();
-    update_all(&database, &datas, &mut estimator)?;
+    let res = if opts.propagate {
+        multi_user::associate_all(&database, &datas).and_then(|datas| {
+            let report = multi_user::propagate_all(&datas, &mut estimator)?;
+            eprintln!(
+                "Determined scores: {} initially, {} in one pass, {} after propagation",
+                report.initial, report.one_pass, report.propagated,
+            );
+            eprintln!(
+                "Evaluated rating target lists {} times and song score list {} times",
+                report.stats.list_evaluations, report.stats.song_score_list_evaluations,
+            );
+            Ok(())
+        })
+    } else {
+        update_all(&database, &datas, &mut estimator)
+    };
+    if let Err(e) = res {
+        if opts.diagnose {
+            for contradiction in estimator.contradictions() {
+                println!("{contradiction}");
+            }
+        }
+        return Err(e);
+    }
+    if let Some(path) = &opts.save_snapshot {
+        write_json(path, &multi_user::to_snapshot(&estimator)?)?;
+    }
 
     let scores = {
         let mut scores = vec![];
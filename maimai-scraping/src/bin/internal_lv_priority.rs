@@ -132,15 +132,7 @@ fn main() -> anyhow::Result<()> {
             }
             Ok(v) => {
                 for (i, res) in v.iter().enumerate().take(candidate_len) {
-                    let locked = match res
-                        .score
-                        .scores()
-                        .song()
-                        .song()
-                        .locked_history
-                        .values()
-                        .last()
-                    {
+                    let locked = match res.score.scores().song().song().latest_locked() {
                         Some(true) => '!',
                         Some(false) => ' ',
                         None => '?',
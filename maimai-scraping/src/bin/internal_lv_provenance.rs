@@ -0,0 +1,70 @@
+use std::{io::BufWriter, path::PathBuf};
+
+use clap::{Parser, ValueEnum};
+use maimai_scraping::maimai::{
+    internal_lv_estimator::{
+        multi_user::{self, update_all},
+        Estimator,
+    },
+    song_list::{self, database::SongDatabase},
+    version::MaimaiVersion,
+};
+use maimai_scraping_utils::fs_json_util::{read_json, read_toml};
+
+#[derive(Parser)]
+struct Opts {
+    database: PathBuf,
+    estimator_config: PathBuf,
+    #[arg(long)]
+    distrust: bool,
+    #[arg(long)]
+    version: Option<MaimaiVersion>,
+
+    #[arg(long, value_enum, default_value = "dot")]
+    format: Format,
+    /// Also include the events from the database (one for every score)
+    #[arg(long)]
+    include_database: bool,
+    /// Only show the sources whose label contains this string,
+    /// e.g. the timestamp of a rating target list
+    #[arg(long)]
+    source: Option<String>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Dot,
+    Json,
+}
+
+fn main() -> anyhow::Result<()> {
+    env_logger::builder().format_timestamp_nanos().init();
+    let opts = Opts::parse();
+
+    let songs: Vec<song_list::Song> = read_json(opts.database)?;
+    let database = SongDatabase::new(&songs)?;
+
+    let config: multi_user::Config = read_toml(opts.estimator_config)?;
+    let datas = config.read_all()?;
+
+    let version = opts.version.unwrap_or(MaimaiVersion::latest());
+    let mut estimator = if opts.distrust {
+        Estimator::new_distrust_all(&database, version)?
+    } else {
+        Estimator::new(&database, version)?
+    };
+    update_all(&database, &datas, &mut estimator)?;
+
+    let mut graph = estimator.provenance(opts.include_database);
+    if let Some(source) = &opts.source {
+        graph.retain_sources(|x| x.label.contains(source));
+    }
+
+    let stdout = BufWriter::new(std::io::stdout().lock());
+    match opts.format {
+        Format::Dot => graph.write_dot(stdout)?,
+        Format::Json => serde_json::to_writer_pretty(stdout, &graph)?,
+    }
+
+    Ok(())
+}
@@ -0,0 +1,43 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use maimai_scraping::maimai::{
+    internal_lv_estimator::multi_user::MultiUserSnapshot, rating::InternalScoreLevel,
+};
+use maimai_scraping_utils::fs_json_util::read_json;
+
+/// Lists the scores whose internal level candidates differ between two snapshots.
+#[derive(Parser)]
+struct Opts {
+    old: PathBuf,
+    new: PathBuf,
+    #[arg(long)]
+    json: bool,
+}
+
+fn main() -> anyhow::Result<()> {
+    let opts = Opts::parse();
+    let old: MultiUserSnapshot = read_json(&opts.old)?;
+    let new: MultiUserSnapshot = read_json(&opts.new)?;
+    if old.version != new.version {
+        println!(
+            "Note: comparing snapshots of different versions ({:?} => {:?})",
+            old.version, new.version
+        );
+    }
+    let diff = old.diff(&new);
+    if opts.json {
+        println!("{}", serde_json::to_string_pretty(&diff)?);
+        return Ok(());
+    }
+    for entry in diff {
+        let show = |x: Option<InternalScoreLevel>| x.map_or("(none)".to_owned(), |x| x.to_string());
+        println!(
+            "{}: {} => {}",
+            entry.title,
+            show(entry.before),
+            show(entry.after)
+        );
+    }
+    Ok(())
+}
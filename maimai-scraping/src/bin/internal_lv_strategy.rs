@@ -34,8 +34,12 @@ struct Opts {
     credentials_path: PathBuf,
     cookie_store_path: PathBuf,
     database_path: PathBuf,
-    locked_toml: PathBuf,
     config_toml: PathBuf,
+    /// Locked scores, with whether each of them is playable by the main card.
+    /// A score is also considered locked if it is locked according to `locked_history`
+    /// of the database, so this is needed only for the scores missing there.
+    #[clap(long)]
+    locked_toml: Option<PathBuf>,
 
     // Constraints
     #[clap(long)]
@@ -207,8 +211,10 @@ async fn main() -> anyhow::Result<()> {
     let database = SongDatabase::new(&songs)?;
     let mut estimator = Estimator::new(&database, MaimaiVersion::latest())?;
 
-    let locked_scores: locked_toml::Root = read_toml(&opts.locked_toml)?;
-    let locked_scores = locked_scores.read(&database)?;
+    let locked_scores = match &opts.locked_toml {
+        Some(path) => read_toml::<_, locked_toml::Root>(path)?.read(&database)?,
+        None => locked_toml::LockedScores::default(),
+    };
 
     let config: internal_lv_estimator::multi_user::Config = read_toml(&opts.config_toml)?;
     let datas = config.read_all()?;
@@ -555,10 +561,13 @@ fn if_then(a: bool, b: bool) -> bool {
 mod locked_toml {
     use anyhow::bail;
     use hashbrown::HashMap;
-    use maimai_scraping::maimai::{
-        schema::latest::{ScoreGeneration, SongIcon, SongName},
-        song_list::database::{OrdinaryScoresRef, SongDatabase},
-        version::MaimaiVersion,
+    use maimai_scraping::{
+        chrono_util::jst_now,
+        maimai::{
+            schema::latest::{ScoreGeneration, SongIcon, SongName},
+            song_list::database::{OrdinaryScoresRef, SongDatabase},
+            version::MaimaiVersion,
+        },
     };
     use serde::Deserialize;
 
@@ -576,6 +585,7 @@ mod locked_toml {
         pub playable: bool,
     }
 
+    #[derive(Default)]
     pub struct LockedScores<'s>(HashMap<OrdinaryScoresRef<'s>, bool>);
 
     impl Root {
@@ -600,16 +610,11 @@ mod locked_toml {
 
     impl LockedScores<'_> {
         // Whether the score is locked (not playable by new card).
+        // Scores listed in the TOML are locked; others are also considered locked
+        // if the database says the song is locked now.
         pub fn is_locked(&self, score: OrdinaryScoresRef) -> bool {
             self.0.contains_key(&score)
-                || score
-                    .song()
-                    .song()
-                    .locked_history
-                    .values()
-                    .copied()
-                    .last()
-                    .unwrap_or(false)
+                || (score.song().song().locked_at(jst_now())).unwrap_or(false)
         }
 
         // Whether the score is playable (by main card).
@@ -0,0 +1,110 @@
+use std::path::PathBuf;
+
+use clap::{Parser, ValueEnum};
+use itertools::Itertools;
+use maimai_scraping::maimai::{
+    judge_analysis::{average_by, LossBreakdown, NoteType},
+    MaimaiUserData,
+};
+use maimai_scraping_utils::fs_json_util::read_json;
+use serde::Serialize;
+
+#[derive(Parser)]
+struct Opts {
+    user_data: PathBuf,
+    #[clap(long, value_enum, default_value = "score")]
+    group_by: GroupBy,
+    /// Only show groups with at least this many records
+    #[clap(long, default_value = "1")]
+    min_plays: usize,
+    #[clap(long)]
+    json: bool,
+}
+
+#[derive(Serialize)]
+struct Row {
+    key: String,
+    plays: usize,
+    #[serde(flatten)]
+    loss: LossBreakdown,
+    costliest: Option<NoteType>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum GroupBy {
+    Score,
+    Month,
+    All,
+}
+
+fn main() -> anyhow::Result<()> {
+    let opts = Opts::parse();
+    let data: MaimaiUserData = read_json(&opts.user_data)?;
+    let records = data
+        .records
+        .values()
+        .filter(|r| !r.utage_metadata().as_ref().is_some_and(|u| u.buddy()));
+    let groups = match opts.group_by {
+        GroupBy::Score => average_by(records, |r| {
+            let metadata = r.score_metadata();
+            format!(
+                "{} ({} {})",
+                r.song_metadata().name(),
+                metadata.generation().abbrev(),
+                metadata.difficulty().abbrev(),
+            )
+        }),
+        GroupBy::Month => average_by(records, |r| {
+            r.played_at()
+                .time()
+                .to_natural_date()
+                .format("%Y-%m")
+                .to_string()
+        }),
+        GroupBy::All => average_by(records, |_| "all".to_owned()),
+    };
+    let groups = groups
+        .into_iter()
+        .filter(|(_, (plays, _))| *plays >= opts.min_plays);
+
+    if opts.json {
+        let rows = groups
+            .map(|(key, (plays, loss))| {
+                let costliest = loss.costliest_note_type().map(|x| x.0);
+                Row {
+                    key,
+                    plays,
+                    loss,
+                    costliest,
+                }
+            })
+            .collect_vec();
+        println!("{}", serde_json::to_string_pretty(&rows)?);
+        return Ok(());
+    }
+
+    println!(
+        "plays\ttotal\t{}\tother\tcostliest\tkey",
+        NoteType::all().iter().map(|t| format!("{t:?}")).join("\t")
+    );
+    for (key, (plays, loss)) in groups {
+        println!(
+            "{plays}\t{:.4}\t{}\t{:.4}\t{}\t{key}",
+            loss.total(),
+            loss.by_note_type()
+                .values()
+                .map(|x| format!("{x:.4}"))
+                .join("\t"),
+            loss.unexplained,
+            costliest(&loss),
+        );
+    }
+    Ok(())
+}
+
+fn costliest(loss: &LossBreakdown) -> String {
+    match loss.costliest_note_type() {
+        Some((note_type, loss)) => format!("{note_type:?} (-{loss:.4}%)"),
+        None => "-".to_owned(),
+    }
+}
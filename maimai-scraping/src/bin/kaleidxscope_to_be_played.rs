@@ -8,11 +8,12 @@ use clap::Parser;
 use fs_err::File;
 use hashbrown::HashMap;
 use itertools::Itertools;
+use log::warn;
 use maimai_scraping::{
     maimai::{
         associated_user_data,
         schema::latest::SongName,
-        song_list::{database::SongDatabase, Song},
+        song_list::{database::SongDatabase, search::SearchIndex, Song},
         version::MaimaiVersion,
         MaimaiUserData,
     },
@@ -28,6 +29,7 @@ struct Opts {
 }
 
 fn main() -> anyhow::Result<()> {
+    env_logger::builder().format_timestamp_nanos().init();
     let opts = Opts::parse();
 
     let songs: Vec<Song> = read_json(opts.database_path)?;
@@ -36,11 +38,24 @@ fn main() -> anyhow::Result<()> {
     let user_data: MaimaiUserData = read_json(opts.user_data_path)?;
     let data = associated_user_data::UserData::annotate(&database, &user_data)?;
 
+    let search_index = SearchIndex::new(&database);
     let mut songs = HashMap::new();
     for line in BufReader::new(File::open(opts.list_path)?).lines() {
         let song_name = SongName::from(line?.to_owned());
         let song = match database.song_from_name(&song_name).collect_vec()[..] {
             [song] => song,
+            // Not typed exactly; try fuzzy search
+            [] => {
+                let hit = search_index.find_unique(song_name.as_ref())?;
+                warn!(
+                    "Guessed {song_name:?} as {} ({} {:?} matched with score {:.3})",
+                    hit.song.latest_song_name(),
+                    hit.field,
+                    hit.matched,
+                    hit.score,
+                );
+                hit.song
+            }
             ref songs => bail!("Song not unique for {song_name:?}: {songs:?}"),
         };
         songs.insert(song, None);
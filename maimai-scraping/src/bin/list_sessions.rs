@@ -0,0 +1,66 @@
+use std::path::PathBuf;
+
+use chrono::{Duration, NaiveDate};
+use clap::Parser;
+use joinery::JoinableIterator;
+use maimai_scraping::maimai::{
+    sessions::{sessions, sessions_on, SessionConfig},
+    MaimaiUserData,
+};
+use maimai_scraping_utils::fs_json_util::read_json;
+
+#[derive(Parser)]
+struct Opts {
+    input_file: PathBuf,
+    /// Minutes of break that separates sessions
+    #[clap(long, default_value = "60")]
+    session_gap: i64,
+    #[clap(long)]
+    show_credits: bool,
+    /// Show only the sessions on this date (where the day starts at 5:00)
+    #[clap(long)]
+    date: Option<NaiveDate>,
+}
+
+fn main() -> anyhow::Result<()> {
+    let opts = Opts::parse();
+    let data: MaimaiUserData = read_json(opts.input_file)?;
+    let config = SessionConfig::new(
+        SessionConfig::default().max_gap_in_credit(),
+        Duration::minutes(opts.session_gap),
+    );
+    let sessions = sessions(data.records.values(), config);
+    let sessions: Vec<_> = match opts.date {
+        Some(date) => sessions_on(&sessions, date).collect(),
+        None => sessions.iter().collect(),
+    };
+    for session in sessions {
+        println!(
+            "{}  {} - {}  {} credits, {} tracks  @{}  rating {} => {} ({:+})  with [{}]",
+            session.natural_date(),
+            session.start().get().format("%H:%M"),
+            session.end().get().format("%H:%M"),
+            session.credits().len(),
+            session.track_count(),
+            session
+                .place()
+                .map_or("?".to_owned(), |place| place.to_string()),
+            session.rating_before(),
+            session.rating_after(),
+            session.rating_delta(),
+            session.companions().iter().join_with(", "),
+        );
+        if opts.show_credits {
+            for credit in session.credits() {
+                println!(
+                    "    {} - {}  {} tracks  {:+}",
+                    credit.start().get().format("%H:%M"),
+                    credit.end().get().format("%H:%M"),
+                    credit.tracks().len(),
+                    credit.rating_delta(),
+                );
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,83 @@
+use std::{io::BufWriter, path::PathBuf};
+
+use anyhow::Context;
+use clap::{Parser, ValueEnum};
+use enum_iterator::Sequence;
+use maimai_scraping::maimai::{
+    internal_lv_estimator::{multi_user, Estimator},
+    lv_changes::LvChangeReport,
+    rating::ScoreLevel,
+    song_list::{database::SongDatabase, Song},
+    version::MaimaiVersion,
+};
+use maimai_scraping_utils::fs_json_util::{read_json, read_toml};
+
+#[derive(Parser)]
+struct Opts {
+    database_path: PathBuf,
+    /// If given, the levels of `--to` are estimated with this config
+    #[arg(long)]
+    estimator_config: Option<PathBuf>,
+
+    /// Defaults to the version previous to `--to`
+    #[arg(long)]
+    from: Option<MaimaiVersion>,
+    /// Defaults to the latest version
+    #[arg(long)]
+    to: Option<MaimaiVersion>,
+    /// Only show the scores of this level in either version
+    #[arg(long)]
+    level: Option<ScoreLevel>,
+
+    #[arg(long, value_enum, default_value = "markdown")]
+    format: Format,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Csv,
+    Json,
+    Markdown,
+}
+
+fn main() -> anyhow::Result<()> {
+    env_logger::builder().format_timestamp_nanos().init();
+    let opts = Opts::parse();
+
+    let songs: Vec<Song> = read_json(opts.database_path)?;
+    let database = SongDatabase::new(&songs)?;
+
+    let to = opts.to.unwrap_or(MaimaiVersion::latest());
+    let from = match opts.from {
+        Some(from) => from,
+        None => to
+            .previous()
+            .context("Given version has no previous version")?,
+    };
+
+    let config: Option<multi_user::Config> =
+        opts.estimator_config.as_ref().map(read_toml).transpose()?;
+    let estimator = match &config {
+        Some(config) => {
+            let mut estimator = Estimator::new(&database, to)?;
+            let datas = config.read_all()?;
+            multi_user::update_all(&database, &datas, &mut estimator)?;
+            Some(estimator)
+        }
+        None => None,
+    };
+
+    let mut report = LvChangeReport::new(&database, estimator.as_ref(), from, to);
+    if let Some(level) = opts.level {
+        report.retain_level(level);
+    }
+
+    let stdout = BufWriter::new(std::io::stdout().lock());
+    match opts.format {
+        Format::Csv => report.write_csv(stdout)?,
+        Format::Json => serde_json::to_writer_pretty(stdout, &report.rows())?,
+        Format::Markdown => print!("{}", report.to_markdown()),
+    }
+
+    Ok(())
+}
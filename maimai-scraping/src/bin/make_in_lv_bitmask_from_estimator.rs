@@ -0,0 +1,87 @@
+//! Writes the candidates estimated by the internal level estimator
+//! as an `in_lv_bitmask` file, which `make_song_database_new` reads.
+
+use std::path::PathBuf;
+
+use anyhow::{bail, Context};
+use clap::Parser;
+use maimai_scraping::maimai::{
+    internal_lv_estimator::{
+        multi_user::{self, update_all},
+        Estimator,
+    },
+    song_list::{
+        database::SongDatabase,
+        in_lv::{self, SongRaw},
+        Song,
+    },
+    version::MaimaiVersion,
+};
+use maimai_scraping_utils::fs_json_util::{read_json, read_toml, write_json};
+
+#[derive(Parser)]
+struct Opts {
+    database: PathBuf,
+    estimator_config: PathBuf,
+    in_lv_bitmask_output: PathBuf,
+    #[arg(long)]
+    distrust: bool,
+    #[arg(long)]
+    version: Option<MaimaiVersion>,
+    /// Start from the estimator state saved in this snapshot
+    #[arg(long)]
+    load_snapshot: Option<PathBuf>,
+}
+
+fn main() -> anyhow::Result<()> {
+    env_logger::builder().format_timestamp_nanos().init();
+    let opts = Opts::parse();
+
+    let songs: Vec<Song> = read_json(opts.database)?;
+    let database = SongDatabase::new(&songs)?;
+    let config: multi_user::Config = read_toml(opts.estimator_config)?;
+    let datas = config.read_all()?;
+    let version = opts.version.unwrap_or(MaimaiVersion::latest());
+
+    let mut estimator = if let Some(path) = &opts.load_snapshot {
+        let estimator = multi_user::from_snapshot(&database, &config, read_json(path)?)?;
+        if estimator.version() != version {
+            bail!(
+                "The snapshot is for {:?}, not {version:?}",
+                estimator.version()
+            );
+        }
+        estimator
+    } else if opts.distrust {
+        Estimator::new_distrust_all(&database, version)?
+    } else {
+        Estimator::new(&database, version)?
+    };
+    update_all(&database, &datas, &mut estimator)?;
+
+    let mut res = vec![];
+    for song in database.songs() {
+        if !song.song().remove_state.exist_for_version(version) {
+            continue;
+        }
+        for scores in song.scoreses() {
+            let mut lv = vec![];
+            for score in scores.all_scores() {
+                if score.for_version(version).is_none() {
+                    continue;
+                }
+                let candidates = estimator
+                    .get(score)
+                    .with_context(|| format!("Score not in the estimator: {score}"))?
+                    .candidates();
+                lv.push(candidates.in_lv_mask(version).get() as f64);
+            }
+            res.push(SongRaw::<in_lv::kind::Bitmask>::from_scores(
+                scores, version, lv,
+            )?);
+        }
+    }
+    write_json(opts.in_lv_bitmask_output, &res)?;
+
+    Ok(())
+}
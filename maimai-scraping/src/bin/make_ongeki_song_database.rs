@@ -0,0 +1,49 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use maimai_scraping::ongeki::{
+    associated_user_data,
+    song_list::{database::SongDatabase, official, Song},
+    OngekiUserData,
+};
+use maimai_scraping_utils::fs_json_util::{read_json, write_json};
+
+#[derive(Parser)]
+struct Opts {
+    official_json: PathBuf,
+    output_json: PathBuf,
+
+    /// Existing database to take known constants from
+    #[clap(long)]
+    previous: Option<PathBuf>,
+    /// Verify that all the records in this user data can be associated
+    #[clap(long)]
+    user_data: Option<PathBuf>,
+}
+
+fn main() -> anyhow::Result<()> {
+    let opts = Opts::parse();
+    let mut songs = official::load(&opts.official_json)?;
+    if let Some(path) = &opts.previous {
+        let previous: Vec<Song> = read_json(path)?;
+        official::carry_over_constants(&mut songs, &previous);
+    }
+    let database = SongDatabase::new(&songs)?;
+
+    if let Some(path) = &opts.user_data {
+        let user_data: OngekiUserData = read_json(path)?;
+        let data = associated_user_data::UserData::annotate(&database, &user_data);
+        for record in data.records().values() {
+            if let Err(e) = record.score() {
+                println!(
+                    "{:?} {}: {e:#}",
+                    record.record().played_at().time(),
+                    record.record().song_metadata().name(),
+                );
+            }
+        }
+    }
+
+    write_json(&opts.output_json, &songs)?;
+    Ok(())
+}
@@ -0,0 +1,35 @@
+use std::path::PathBuf;
+
+use clap::{Parser, ValueEnum};
+use maimai_scraping::maimai::song_list::{diff::SongDbDiff, Song};
+use maimai_scraping_utils::fs_json_util::read_json;
+
+#[derive(Parser)]
+struct Opts {
+    old_database_path: PathBuf,
+    new_database_path: PathBuf,
+
+    #[arg(long, value_enum, default_value = "text")]
+    format: Format,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Text,
+    Json,
+}
+
+fn main() -> anyhow::Result<()> {
+    let opts = Opts::parse();
+
+    let old: Vec<Song> = read_json(opts.old_database_path)?;
+    let new: Vec<Song> = read_json(opts.new_database_path)?;
+    let diff = SongDbDiff::new(&old, &new)?;
+
+    match opts.format {
+        Format::Text => print!("{diff}"),
+        Format::Json => println!("{}", serde_json::to_string_pretty(&diff)?),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,113 @@
+use std::path::PathBuf;
+
+use clap::{Parser, ValueEnum};
+use itertools::Itertools;
+use maimai_scraping::maimai::{
+    internal_lv_estimator::multi_user::MultiUserSnapshot,
+    song_list::{database::SongDatabase, Song},
+    song_query::{show_value, Column, Condition, SongQuery},
+    version::MaimaiVersion,
+};
+use maimai_scraping_utils::fs_json_util::read_json;
+use serde_json::{Map, Value};
+
+/// Lists the scores of a version matching the conditions.
+/// See `maimai::song_query` for the syntax of conditions.
+#[derive(Parser)]
+struct Opts {
+    database_path: PathBuf,
+    /// Conditions, all of which must be satisfied, e.g. `lv=13+ diff=mas !determined`
+    conditions: Vec<Condition>,
+
+    /// Defaults to the version of `--snapshot` if given, and otherwise the latest version
+    #[arg(long)]
+    version: Option<MaimaiVersion>,
+    /// Estimator snapshot whose candidates override the levels in the database
+    #[arg(long)]
+    snapshot: Option<PathBuf>,
+
+    /// Comma-separated columns to print
+    #[arg(long, value_delimiter = ',')]
+    columns: Vec<Column>,
+    #[arg(long, value_enum, default_value = "table")]
+    format: Format,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Table,
+    Tsv,
+    Json,
+}
+
+fn main() -> anyhow::Result<()> {
+    let opts = Opts::parse();
+
+    let songs: Vec<Song> = read_json(opts.database_path)?;
+    let database = SongDatabase::new(&songs)?;
+
+    let snapshot: Option<MultiUserSnapshot> = opts.snapshot.map(read_json).transpose()?;
+    let version = (opts.version)
+        .or(snapshot.as_ref().map(|x| x.version))
+        .unwrap_or(MaimaiVersion::latest());
+    let mut query = SongQuery::new(version);
+    if let Some(snapshot) = &snapshot {
+        query = query.with_snapshot(snapshot)?;
+    }
+
+    let columns = if opts.columns.is_empty() {
+        Column::defaults()
+    } else {
+        opts.columns
+    };
+    let scores = query.run(&database, &opts.conditions);
+    let rows = query.rows(&scores, &columns);
+
+    match opts.format {
+        Format::Table => {
+            let cells = rows
+                .iter()
+                .map(|row| row.iter().map(|x| show_value(x).to_string()).collect_vec())
+                .collect_vec();
+            let header = columns.iter().map(|x| x.to_string()).collect_vec();
+            let widths = (0..columns.len())
+                .map(|i| {
+                    (cells.iter().chain([&header]))
+                        .map(|row| width(&row[i]))
+                        .max()
+                        .unwrap_or(0)
+                })
+                .collect_vec();
+            for row in [&header].into_iter().chain(&cells) {
+                let line = (row.iter().zip(&widths))
+                    .map(|(cell, &w)| format!("{cell}{}", " ".repeat(w - width(cell))))
+                    .join(" | ");
+                println!("{}", line.trim_end());
+            }
+            eprintln!("{} scores", rows.len());
+        }
+        Format::Tsv => {
+            println!("{}", columns.iter().join("\t"));
+            for row in &rows {
+                println!("{}", row.iter().map(show_value).join("\t"));
+            }
+        }
+        Format::Json => {
+            let rows = rows
+                .into_iter()
+                .map(|row| {
+                    let map: Map<_, _> = columns.iter().map(|x| x.to_string()).zip(row).collect();
+                    Value::Object(map)
+                })
+                .collect_vec();
+            println!("{}", serde_json::to_string_pretty(&rows)?);
+        }
+    }
+
+    Ok(())
+}
+
+/// Approximate width in a terminal, counting CJK characters as two columns.
+fn width(s: &str) -> usize {
+    s.chars().map(|c| if c >= '\u{1100}' { 2 } else { 1 }).sum()
+}
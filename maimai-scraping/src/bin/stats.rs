@@ -0,0 +1,82 @@
+use std::{io::stdout, path::PathBuf};
+
+use clap::{Parser, ValueEnum};
+use fs_err::File;
+use maimai_scraping::maimai::{
+    associated_user_data,
+    song_list::{database::SongDatabase, Song},
+    stats::{Report, TableKind},
+    MaimaiUserData,
+};
+use maimai_scraping_utils::fs_json_util::{read_json, write_json};
+
+#[derive(Parser)]
+struct Opts {
+    user_data: PathBuf,
+    /// Required for the per-level table
+    #[clap(long)]
+    database: Option<PathBuf>,
+    #[clap(long, value_enum, default_value = "tsv")]
+    format: Format,
+    /// Only output this table (e.g. `by_day`, `by_level`); all tables if omitted
+    #[clap(long)]
+    table: Option<TableKind>,
+    /// Write each table to `<output_dir>/<table>.<ext>` instead of stdout
+    #[clap(long)]
+    output_dir: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Tsv,
+    Csv,
+    Json,
+}
+
+fn main() -> anyhow::Result<()> {
+    let opts = Opts::parse();
+    let data: MaimaiUserData = read_json(&opts.user_data)?;
+    let songs: Option<Vec<Song>> = opts.database.as_ref().map(read_json).transpose()?;
+    let database = songs.as_deref().map(SongDatabase::new).transpose()?;
+    let associated = database
+        .as_ref()
+        .map(|database| associated_user_data::UserData::annotate(database, &data))
+        .transpose()?;
+    let report = Report::new(&data, associated.as_ref());
+
+    let (delimiter, ext) = match opts.format {
+        Format::Tsv => (b'\t', "tsv"),
+        Format::Csv => (b',', "csv"),
+        Format::Json => {
+            match &opts.output_dir {
+                Some(dir) => {
+                    fs_err::create_dir_all(dir)?;
+                    write_json(dir.join("stats.json"), &report)?;
+                }
+                None => serde_json::to_writer_pretty(stdout().lock(), &report)?,
+            }
+            return Ok(());
+        }
+    };
+    let tables = match opts.table {
+        Some(table) => vec![table],
+        None => TableKind::all().to_vec(),
+    };
+    if let Some(dir) = &opts.output_dir {
+        fs_err::create_dir_all(dir)?;
+    }
+    for table in tables {
+        match &opts.output_dir {
+            Some(dir) => {
+                let file = File::create(dir.join(format!("{table}.{ext}")))?;
+                report.write_table(table, file, delimiter)?;
+            }
+            None => {
+                println!("# {table}");
+                report.write_table(table, stdout().lock(), delimiter)?;
+                println!();
+            }
+        }
+    }
+    Ok(())
+}
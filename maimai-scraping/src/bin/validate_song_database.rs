@@ -0,0 +1,56 @@
+use std::path::PathBuf;
+
+use anyhow::bail;
+use clap::{Parser, ValueEnum};
+use maimai_scraping::maimai::song_list::{
+    validation::{RuleConfig, RuleSet, Severity},
+    Song,
+};
+use maimai_scraping_utils::fs_json_util::{read_json, read_toml};
+
+#[derive(Parser)]
+struct Opts {
+    database_path: PathBuf,
+    /// TOML file to disable rules or override their severities.
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    #[arg(long, value_enum, default_value = "text")]
+    format: Format,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Text,
+    Json,
+}
+
+fn main() -> anyhow::Result<()> {
+    let opts = Opts::parse();
+
+    let songs: Vec<Song> = read_json(opts.database_path)?;
+    let config: RuleConfig = match &opts.config {
+        Some(path) => read_toml(path)?,
+        None => RuleConfig::default(),
+    };
+    let violations = RuleSet::with_config(&config)?.run(&songs);
+
+    match opts.format {
+        Format::Text => {
+            for violation in &violations {
+                println!("{violation}");
+            }
+        }
+        Format::Json => println!("{}", serde_json::to_string_pretty(&violations)?),
+    }
+
+    let errors = violations
+        .iter()
+        .filter(|x| x.severity == Severity::Error)
+        .count();
+    if errors > 0 {
+        bail!("{errors} error(s) found");
+    }
+
+    Ok(())
+}
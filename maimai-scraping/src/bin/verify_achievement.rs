@@ -1,8 +1,12 @@
 use std::path::PathBuf;
 
 use clap::Parser;
+use hashbrown::HashMap;
 use maimai_scraping::maimai::{
-    schema::latest::{AchievementValue, JudgeCount, JudgeCountWithoutCP, PlaceName},
+    associated_user_data,
+    record_verifier::{infer_note_profile, verify_record},
+    schema::latest::PlaceName,
+    song_list::{database::SongDatabase, Song},
     MaimaiUserData,
 };
 use maimai_scraping_utils::fs_json_util::read_json;
@@ -10,92 +14,58 @@ use maimai_scraping_utils::fs_json_util::read_json;
 #[derive(Parser)]
 struct Opts {
     data_path: PathBuf,
+    /// Compare note counts with the database, and infer them where unknown
+    #[clap(long)]
+    database: Option<PathBuf>,
 }
 
 fn main() -> anyhow::Result<()> {
     let opts = Opts::parse();
     let data: MaimaiUserData = read_json(opts.data_path)?;
-    for record in data.records.values() {
-        let judges = record.judge_result();
-        if record.utage_metadata().as_ref().is_some_and(|u| u.buddy()) {
-            continue;
-        }
-        if record.played_at().place() == &Some(PlaceName::from("不明".to_owned())) {
-            continue;
-        }
+    let records = data.records.values().filter(|record| {
+        !record.utage_metadata().as_ref().is_some_and(|u| u.buddy())
+            && record.played_at().place() != &Some(PlaceName::from("不明".to_owned()))
+    });
 
-        let counts_tuple = |j: JudgeCountWithoutCP| {
-            (
-                j.perfect() as u64,
-                j.great() as u64,
-                j.good() as u64,
-                j.miss() as u64,
-            )
-        };
-        let (note_score, note_sum) = {
-            let (mut score, mut sum) = (0, 0);
-            for (weight, j) in [
-                (1, judges.tap()),
-                (1, judges.touch()),
-                (2, judges.hold()),
-                (3, judges.slide()),
-            ] {
-                let (cp, others) = match j {
-                    JudgeCount::Nothing => continue,
-                    JudgeCount::JudgeCountWithCP(j) => (j.critical_perfect() as u64, j.others()),
-                    JudgeCount::JudgeCountWithoutCP(j) => (0, j),
-                };
-                let (p, g, o, m) = counts_tuple(others);
-                let p = p + cp;
-                score += (p * 10 + g * 8 + o * 5) * weight;
-                sum += (p + g + o + m) * 10 * weight;
-            }
-            (score, sum)
-        };
-        let (break_score, break_sum, bonus, bonus_sum, break_perfect, break_great) = {
-            let judges = judges.break_();
-            let cp = judges.critical_perfect() as u64;
-            let (p, g, o, m) = counts_tuple(judges.others());
-            let total = cp + p + g + o + m;
-            (
-                (cp + p) * 50 + g * 25 + o * 20, // + g' * 5 + g'' * 15
-                total * 50,
-                cp * 20 + p * 10 + g * 8 + o * 6, // + p' * 5
-                total * 20,
-                p,
-                g,
-            )
-        };
-        let mut combinations = 0;
-        for p in 0..=break_perfect {
-            for gg in 0..=break_great {
-                for g in 0..=break_great - gg {
-                    let score = note_score + break_score + g * 5 + gg * 15;
-                    let note_sum = note_sum + break_sum;
-                    let bonus = bonus + p * 5;
-                    // (score / note_sum * 100 + bonus / bonus_sum) * 10000
-                    let num = (score * 100 * bonus_sum + bonus * note_sum) * 10000;
-                    let den = note_sum * bonus_sum;
-                    let a: AchievementValue = u32::try_from(num / den).unwrap().try_into().unwrap();
-                    if a == record.achievement_result().value() {
-                        println!(
-                            "{a} => {score}/{note_sum} {:?} + {bonus}/{bonus_sum} {:?}",
-                            [gg, g, break_great - gg - g],
-                            [p, break_perfect - p]
-                        );
-                        combinations += 1;
-                    }
-                }
+    let Some(database) = &opts.database else {
+        for record in records {
+            for discrepancy in verify_record(record, None) {
+                println!("{discrepancy}: {record:?}");
             }
         }
-        match combinations {
-            0 => println!("Not found: {record:?}"),
-            1 => {}
-            _ => {
-                println!("{combinations} combinations were found")
-            }
+        return Ok(());
+    };
+
+    let songs: Vec<Song> = read_json(database)?;
+    let database = SongDatabase::new(&songs)?;
+    let associated = associated_user_data::UserData::annotate(&database, &data)?;
+    let mut unknown = HashMap::<_, Vec<_>>::new();
+    for record in records {
+        let score = associated
+            .records()
+            .get(&record.played_at().time())
+            .and_then(|r| r.as_ordinary()?.into_associated().ok())
+            .map(|r| r.score().score());
+        let notes = score.and_then(|score| score.notes());
+        if let (Some(score), None) = (score, notes) {
+            unknown.entry(score).or_default().push(record);
+        }
+        for discrepancy in verify_record(record, notes) {
+            println!("{discrepancy}: {record:?}");
         }
     }
 
+    println!("Note counts inferred from records:");
+    for (score, records) in unknown {
+        let candidates = infer_note_profile(records);
+        if let [(notes, count)] = &candidates[..] {
+            println!("  {score}: {notes:?} ({count} records)");
+        } else {
+            println!("  {score}: ambiguous");
+            for (notes, count) in candidates {
+                println!("    {notes:?} ({count} records)");
+            }
+        }
+    }
     Ok(())
 }
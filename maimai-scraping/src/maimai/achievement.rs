@@ -0,0 +1,385 @@
+use anyhow::{bail, Context};
+use enum_map::{Enum, EnumMap};
+use getset::CopyGetters;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use typed_builder::TypedBuilder;
+
+use super::{
+    judge_analysis::{judge_table, JudgeGrade, NoteType},
+    schema::latest::{AchievementValue, JudgeResult, ScoreDifficulty, ScoreGeneration, SongIcon},
+};
+
+/// Number of notes of each type in a score.
+#[derive(
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Debug,
+    Default,
+    TypedBuilder,
+    CopyGetters,
+    Serialize,
+    Deserialize,
+)]
+#[getset(get_copy = "pub")]
+pub struct NoteProfile {
+    tap: u32,
+    hold: u32,
+    slide: u32,
+    touch: u32,
+    break_: u32,
+}
+impl NoteProfile {
+    /// Counts the notes in a play record.
+    pub fn from_judge_result(judge: JudgeResult) -> Self {
+        let table = judge_table(judge);
+        let count = |t| table[t].values().sum();
+        Self {
+            tap: count(NoteType::Tap),
+            hold: count(NoteType::Hold),
+            slide: count(NoteType::Slide),
+            touch: count(NoteType::Touch),
+            break_: count(NoteType::Break),
+        }
+    }
+
+    pub fn count(self, note_type: NoteType) -> u32 {
+        match note_type {
+            NoteType::Tap => self.tap,
+            NoteType::Hold => self.hold,
+            NoteType::Slide => self.slide,
+            NoteType::Touch => self.touch,
+            NoteType::Break => self.break_,
+        }
+    }
+
+    pub fn total(self) -> u32 {
+        NoteType::all().into_iter().map(|t| self.count(t)).sum()
+    }
+
+    /// The weight of non-break notes, in units of a tap.
+    fn non_break_units(self) -> u64 {
+        NoteType::all()
+            .into_iter()
+            .filter(|&t| t != NoteType::Break)
+            .map(|t| self.count(t) as u64 * t.base_weight())
+            .sum()
+    }
+
+    fn sums(self) -> Sums {
+        let units = self.non_break_units() + self.break_ as u64 * NoteType::Break.base_weight();
+        Sums {
+            note: units * 10,
+            bonus: self.break_ as u64 * 20,
+        }
+    }
+
+    /// Computes the achievement when the player made `mistakes`.
+    /// Returns `None` if there are more mistakes than notes.
+    pub fn achievement(self, mistakes: &Mistakes) -> Option<AchievementValue> {
+        let non_break = mistakes
+            .iter()
+            .filter(|(m, _)| !m.is_break())
+            .map(|(_, &c)| c as u64)
+            .sum::<u64>();
+        let break_ = mistakes
+            .iter()
+            .filter(|(m, _)| m.is_break())
+            .map(|(_, &c)| c as u64)
+            .sum::<u64>();
+        if non_break > self.non_break_units() || break_ > self.break_ as u64 {
+            return None;
+        }
+        let sums = self.sums();
+        let mut score = sums;
+        for (mistake, &count) in mistakes {
+            let deduction = mistake.deduction();
+            score.note -= deduction.note * count as u64;
+            score.bonus -= deduction.bonus * count as u64;
+        }
+        calc(sums, score)
+    }
+
+    /// Enumerates all the combinations of `kinds` of mistakes
+    /// with which the achievement is at least `min`,
+    /// in descending order of the achievement.
+    pub fn mistakes_reaching(
+        self,
+        min: AchievementValue,
+        kinds: &[Mistake],
+    ) -> Vec<(AchievementValue, Mistakes)> {
+        let sums = self.sums();
+        let mut mistakes = Mistakes::default();
+        let mut results = vec![];
+        dfs(
+            kinds,
+            min,
+            sums,
+            sums,
+            (self.non_break_units(), self.break_ as u64),
+            &mut mistakes,
+            &mut results,
+        );
+        results.sort_by_key(|x| std::cmp::Reverse(x.0));
+        results
+    }
+}
+
+/// An entry of `note_counts.json`, which `make_song_database_new` reads.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct ScoreNotes {
+    pub icon: SongIcon,
+    pub generation: ScoreGeneration,
+    pub difficulty: ScoreDifficulty,
+    pub notes: NoteProfile,
+}
+
+impl std::str::FromStr for NoteProfile {
+    type Err = anyhow::Error;
+    /// Parses space-separated five integers in the order of
+    /// tap, touch, hold, slide and break, as `possible_achievement` has always taken.
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let Some((tap, touch, hold, slide, break_)) = s
+            .split_whitespace()
+            .map(|x| x.parse().with_context(|| format!("Invalid value: {x:?}")))
+            .collect_tuple()
+        else {
+            bail!("Should contain space-separated five integers, found {s:?}")
+        };
+        Ok(Self {
+            tap: tap?,
+            hold: hold?,
+            slide: slide?,
+            touch: touch?,
+            break_: break_?,
+        })
+    }
+}
+
+/// A judge that loses achievement.
+///
+/// Non-break mistakes are counted in units of a tap;
+/// e.g. a great on a hold is two `TapGreat`s.
+/// Break perfects and greats are distinguished by timing,
+/// which is not shown in the play record.
+#[derive(
+    Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Enum, Serialize, Deserialize,
+)]
+pub enum Mistake {
+    TapGreat,
+    TapGood,
+    TapMiss,
+    BreakPerfectClose,
+    BreakPerfectFar,
+    BreakGreatClose,
+    BreakGreatMid,
+    BreakGreatFar,
+    BreakGood,
+    BreakMiss,
+}
+impl Mistake {
+    pub fn all() -> [Self; 10] {
+        use Mistake::*;
+        [
+            TapGreat,
+            TapGood,
+            TapMiss,
+            BreakPerfectClose,
+            BreakPerfectFar,
+            BreakGreatClose,
+            BreakGreatMid,
+            BreakGreatFar,
+            BreakGood,
+            BreakMiss,
+        ]
+    }
+
+    pub fn is_break(self) -> bool {
+        !matches!(
+            self,
+            Mistake::TapGreat | Mistake::TapGood | Mistake::TapMiss
+        )
+    }
+
+    /// The mistake made by a judge of `grade` on a note of `note_type`, if any.
+    /// Break perfects and greats are assumed to be the closest ones.
+    pub fn of_judge(grade: JudgeGrade, note_type: NoteType) -> Option<Self> {
+        use JudgeGrade::*;
+        use Mistake::*;
+        let is_break = note_type == NoteType::Break;
+        Some(match grade {
+            CriticalPerfect => return None,
+            Perfect if is_break => BreakPerfectClose,
+            Perfect => return None,
+            Great if is_break => BreakGreatClose,
+            Great => TapGreat,
+            Good if is_break => BreakGood,
+            Good => TapGood,
+            Miss if is_break => BreakMiss,
+            Miss => TapMiss,
+        })
+    }
+
+    /// Deduction from the note score, where a tap is worth 10.
+    pub fn note_deduction(self) -> u64 {
+        self.deduction().note
+    }
+
+    /// Deduction from the break bonus, where a break is worth 20.
+    pub fn bonus_deduction(self) -> u64 {
+        self.deduction().bonus
+    }
+
+    /// Deductions from the note score (where a tap is worth 10)
+    /// and from the break bonus (where a break is worth 20).
+    fn deduction(self) -> Sums {
+        use Mistake::*;
+        let (note, bonus) = match self {
+            TapGreat => (2, 0),
+            TapGood => (5, 0),
+            TapMiss => (10, 0),
+            BreakPerfectClose => (0, 5),
+            BreakPerfectFar => (0, 10),
+            BreakGreatClose => (2 * 5, 12),
+            BreakGreatMid => (4 * 5, 12),
+            BreakGreatFar => (5 * 5, 12),
+            BreakGood => (6 * 5, 14),
+            BreakMiss => (10 * 5, 20),
+        };
+        Sums { note, bonus }
+    }
+}
+
+pub type Mistakes = EnumMap<Mistake, u32>;
+
+/// Converts the judges in a play record into mistakes.
+/// Break perfects and greats are assumed to be the closest ones,
+/// so the achievement computed from the result is an upper bound.
+pub fn mistakes_from_judge_result(judge: JudgeResult) -> Mistakes {
+    let table = judge_table(judge);
+    let mut ret = Mistakes::default();
+    for (note_type, counts) in &table {
+        for (grade, &count) in counts {
+            if let Some(mistake) = Mistake::of_judge(grade, note_type) {
+                // Non-break mistakes are counted in units of a tap
+                let weight = match mistake.is_break() {
+                    true => 1,
+                    false => note_type.base_weight() as u32,
+                };
+                ret[mistake] += count * weight;
+            }
+        }
+    }
+    ret
+}
+
+#[derive(Clone, Copy)]
+struct Sums {
+    note: u64,
+    bonus: u64,
+}
+
+fn calc(sum: Sums, score: Sums) -> Option<AchievementValue> {
+    // (note_score / note_sum * 100 + bonus_score / bonus_sum) * 10000
+    if sum.note == 0 {
+        return None;
+    }
+    let value = if sum.bonus == 0 {
+        // Without breaks, the bonus is given in full
+        score.note * 100 * 10000 / sum.note + 10000
+    } else {
+        (score.note * 100 * sum.bonus + score.bonus * sum.note) * 10000 / (sum.note * sum.bonus)
+    };
+    AchievementValue::try_from(value as u32).ok()
+}
+
+fn dfs(
+    kinds: &[Mistake],
+    min: AchievementValue,
+    sum: Sums,
+    remaining_score: Sums,
+    remaining_count: (u64, u64),
+    mistakes: &mut Mistakes,
+    results: &mut Vec<(AchievementValue, Mistakes)>,
+) {
+    let Some((&kind, kinds)) = kinds.split_first() else {
+        if let Some(achievement) = calc(sum, remaining_score) {
+            results.push((achievement, *mistakes));
+        }
+        return;
+    };
+    let deduction = kind.deduction();
+    let count_max = if kind.is_break() {
+        remaining_count.1
+    } else {
+        remaining_count.0
+    };
+    for i in 0..=count_max {
+        let (Some(note), Some(bonus)) = (
+            remaining_score.note.checked_sub(deduction.note * i),
+            remaining_score.bonus.checked_sub(deduction.bonus * i),
+        ) else {
+            break;
+        };
+        let score = Sums { note, bonus };
+        if calc(sum, score).is_none_or(|a| a < min) {
+            break;
+        }
+        let count = if kind.is_break() {
+            (remaining_count.0, remaining_count.1 - i)
+        } else {
+            (remaining_count.0 - i, remaining_count.1)
+        };
+        mistakes[kind] = i as u32;
+        dfs(kinds, min, sum, score, count, mistakes, results);
+    }
+    mistakes[kind] = 0;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::maimai::schema::latest::AchievementValue;
+
+    use super::{Mistake, Mistakes, NoteProfile};
+
+    #[test]
+    fn test_parse_note_profile() {
+        let profile: NoteProfile = "1 2 3 4 5".parse().unwrap();
+        let counts = (
+            profile.tap(),
+            profile.touch(),
+            profile.hold(),
+            profile.slide(),
+            profile.break_(),
+        );
+        assert_eq!(counts, (1, 2, 3, 4, 5));
+        assert!("1 2 3 4".parse::<NoteProfile>().is_err());
+    }
+
+    #[test]
+    fn test_achievement() {
+        let profile: NoteProfile = "100 0 0 0 10".parse().unwrap();
+        let a = |x: u32| AchievementValue::try_from(x).unwrap();
+        assert_eq!(profile.achievement(&Mistakes::default()), Some(a(101_0000)));
+
+        let mut mistakes = Mistakes::default();
+        mistakes[Mistake::TapGreat] = 3;
+        mistakes[Mistake::BreakPerfectClose] = 1;
+        assert_eq!(profile.achievement(&mistakes), Some(a(100_5750)));
+
+        mistakes[Mistake::BreakMiss] = 10;
+        assert_eq!(profile.achievement(&mistakes), None);
+
+        let results = profile.mistakes_reaching(a(100_9000), &Mistake::all());
+        assert!(results.windows(2).all(|w| w[0].0 >= w[1].0));
+        assert!(results
+            .iter()
+            .all(|(x, m)| *x >= a(100_9000) && profile.achievement(m) == Some(*x)));
+        assert_eq!(results[0], (a(101_0000), Mistakes::default()));
+    }
+}
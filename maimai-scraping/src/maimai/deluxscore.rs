@@ -0,0 +1,71 @@
+use super::{
+    schema::latest::{DeluxscoreRank, PlayRecord, ValueWithMax},
+    MaimaiUserData,
+};
+
+/// Percentages of the max DX score required for each number of stars.
+pub const STAR_BORDERS: [u32; 5] = [85, 90, 93, 95, 97];
+
+/// The least DX score with which `stars` stars are given.
+/// Returns `None` if `stars` is not in `1..=5`.
+pub fn border(max: u32, stars: u8) -> Option<u32> {
+    let percent = *STAR_BORDERS.get((stars as usize).checked_sub(1)?)?;
+    Some((max * percent).div_ceil(100))
+}
+
+/// Number of stars for the DX score.
+pub fn stars(score: ValueWithMax<u32>) -> DeluxscoreRank {
+    let stars = (1..=5)
+        .take_while(|&s| border(score.max(), s).is_some_and(|b| b <= score.value()))
+        .count();
+    DeluxscoreRank::try_from(stars as u8).expect("Stars are in 0..=5")
+}
+
+/// The next number of stars, and the DX score needed to reach it.
+/// Returns `None` if the score already has five stars.
+pub fn to_next_star(score: ValueWithMax<u32>) -> Option<(u8, u32)> {
+    let next = stars(score).get() + 1;
+    let border = border(score.max(), next)?;
+    Some((next, border - score.value()))
+}
+
+/// The best DX score of the same score as `record` among the records played before it.
+pub fn best_before(data: &MaimaiUserData, record: &PlayRecord) -> Option<ValueWithMax<u32>> {
+    let key = |r: &PlayRecord| {
+        let metadata = r.score_metadata();
+        (
+            r.song_metadata().cover_art().clone(),
+            metadata.generation(),
+            metadata.difficulty(),
+        )
+    };
+    let target = key(record);
+    data.records
+        .range(..record.played_at().time())
+        .map(|(_, r)| r)
+        .filter(|r| key(r) == target)
+        .map(|r| r.deluxscore_result().score())
+        .max_by_key(|s| s.value())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::maimai::schema::latest::ValueWithMax;
+
+    use super::{border, stars, to_next_star};
+
+    #[test]
+    fn test_stars() {
+        // 600 notes
+        let max = 1800;
+        assert_eq!(border(max, 1), Some(1530));
+        assert_eq!(border(max, 3), Some(1674));
+        assert_eq!(border(max, 6), None);
+        let score = |x| ValueWithMax::new(x, max).unwrap();
+        assert_eq!(stars(score(1529)).get(), 0);
+        assert_eq!(stars(score(1530)).get(), 1);
+        assert_eq!(stars(score(1746)).get(), 5);
+        assert_eq!(to_next_star(score(1700)), Some((4, 10)));
+        assert_eq!(to_next_star(score(1800)), None);
+    }
+}
@@ -0,0 +1,167 @@
+//! Explains why the candidates of a score became empty.
+
+use std::fmt::Display;
+
+use itertools::Itertools;
+
+use crate::maimai::{rating::InternalScoreLevel, song_list::database::OrdinaryScoreRef};
+
+use super::{Estimator, Event};
+
+/// A minimal set of events that contradict each other on a score.
+///
+/// Removing any one of the events makes the rest consistent,
+/// so at least one of their sources (or the configuration that produced them) is wrong.
+pub struct Contradiction<'s, 'e, LD, LL> {
+    score: OrdinaryScoreRef<'s>,
+    events: Vec<&'e Event<'s, LD, LL>>,
+}
+impl<'s, 'e, LD, LL> Contradiction<'s, 'e, LD, LL> {
+    pub fn score(&self) -> OrdinaryScoreRef<'s> {
+        self.score
+    }
+    pub fn events(&self) -> &[&'e Event<'s, LD, LL>] {
+        &self.events
+    }
+}
+impl<LD, LL> Display for Contradiction<'_, '_, LD, LL>
+where
+    LD: Display,
+    LL: Display,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "No candidates for {}, as the following conflict:",
+            self.score
+        )?;
+        for event in &self.events {
+            write!(f, "\n  - {} {}", event.allowed(), event.reason())?;
+        }
+        Ok(())
+    }
+}
+
+impl<'s, LD, LL> Estimator<'s, LD, LL> {
+    /// Lists the scores without candidates, each with a minimal set of conflicting events.
+    ///
+    /// `set` returns an error as soon as a score runs out of candidates,
+    /// so this is typically called on the estimator after such an error.
+    pub fn contradictions(&self) -> Vec<Contradiction<'s, '_, LD, LL>> {
+        self.map
+            .values()
+            .filter(|candidates| candidates.candidates.is_empty())
+            // Sort in the order the contradictions occurred
+            .sorted_by_key(|candidates| candidates.reasons.last())
+            .map(|candidates| {
+                let events = candidates
+                    .reasons
+                    .iter()
+                    .map(|&i| &self.events.0[i])
+                    .collect();
+                Contradiction {
+                    score: candidates.score,
+                    events: minimal_conflict(events),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Removes events one by one as long as the rest still have no common candidate.
+/// The events are examined from the oldest one,
+/// so that the later (and usually more specific) sources are kept.
+fn minimal_conflict<'e, 's, LD, LL>(
+    mut events: Vec<&'e Event<'s, LD, LL>>,
+) -> Vec<&'e Event<'s, LD, LL>> {
+    let conflicts = |events: &[&Event<'s, LD, LL>]| {
+        events
+            .iter()
+            .map(|e| e.allowed)
+            .reduce(InternalScoreLevel::intersection)
+            .is_some_and(|x| x.is_empty())
+    };
+    let mut i = 0;
+    while i < events.len() {
+        let removed = events.remove(i);
+        if !conflicts(&events) {
+            events.insert(i, removed);
+            i += 1;
+        }
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::maimai::song_list::database::SongDatabase;
+
+    use super::{
+        super::{
+            test_util::{master, songs, TestEstimator, TestLabel, VERSION},
+            Reason,
+        },
+        minimal_conflict,
+    };
+
+    #[test]
+    fn test_contradictions() {
+        let songs = songs(2);
+        let database = SongDatabase::new(&songs).unwrap();
+        let mut estimator = TestEstimator::new(&database, VERSION).unwrap();
+        let list = |name| Reason::List(TestLabel(name, 0));
+        let score = master(&database, 0);
+        estimator
+            .set(score, |x| u8::from(x) >= 132, Reason::Assumption)
+            .unwrap();
+        estimator
+            .set(score, |x| u8::from(x) <= 135, list("a"))
+            .unwrap();
+        estimator
+            .set(master(&database, 1), |x| u8::from(x) <= 131, list("a"))
+            .unwrap();
+        estimator
+            .set(score, |x| u8::from(x) <= 133, list("b"))
+            .unwrap();
+        assert!(estimator.contradictions().is_empty());
+        assert!(estimator
+            .set(score, |x| u8::from(x) >= 134, list("c"))
+            .is_err());
+
+        let contradictions = estimator.contradictions();
+        assert_eq!(contradictions.len(), 1);
+        assert_eq!(contradictions[0].score(), score);
+        let names = (contradictions[0].events().iter())
+            .map(|e| match e.reason() {
+                Reason::List(TestLabel(name, _)) => *name,
+                _ => "other",
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["b", "c"]);
+    }
+
+    #[test]
+    fn test_minimal_conflict() {
+        let songs = songs(1);
+        let database = SongDatabase::new(&songs).unwrap();
+        let mut estimator = TestEstimator::new(&database, VERSION).unwrap();
+        let score = master(&database, 0);
+        for (i, (min, max)) in [(131, 136), (130, 132), (133, 136), (130, 134)]
+            .into_iter()
+            .enumerate()
+        {
+            let reason = Reason::List(TestLabel("a", i));
+            let _ = estimator.set(score, |x| (min..=max).contains(&u8::from(x)), reason);
+        }
+        let events = estimator.events.0.iter().collect();
+        let iterations = minimal_conflict(events)
+            .into_iter()
+            .map(|e| match e.reason() {
+                Reason::List(TestLabel(_, i)) => *i,
+                _ => usize::MAX,
+            })
+            .collect::<Vec<_>>();
+        // The oldest events are dropped first as long as the rest still conflict
+        assert_eq!(iterations, [1, 2]);
+    }
+}
@@ -3,23 +3,31 @@
 //! - Type parameter `L` is the label for the source, i.e. play record / rating target list.
 //!   `L` is used for debugging and must implement cheap `Copy`.
 
+pub mod diagnostics;
 pub mod multi_user;
+pub mod propagation;
+pub mod provenance;
+pub mod snapshot;
 
 mod song_score;
+#[cfg(test)]
+mod test_util;
+
+pub use song_score::AssociatedSongScoreList;
 
 use std::{collections::BTreeSet, fmt::Debug, ops::Range};
 
 use anyhow::{bail, Context};
 use chrono::{NaiveDateTime, NaiveTime};
 use derive_more::Display;
-use enum_iterator::Sequence;
 use getset::{CopyGetters, Getters};
 use hashbrown::HashMap;
 use itertools::{repeat_n, Itertools};
 use joinery::JoinableIterator;
 use lazy_format::lazy_format;
 use log::trace;
-use song_score::{AssociatedSongScoreList, ScoreOrder};
+use serde::{Deserialize, Serialize};
+use song_score::ScoreOrder;
 
 use crate::algorithm::possibilties_from_sum_and_ordering;
 
@@ -54,7 +62,7 @@ struct Candidates<'s> {
 #[derive(Clone, Copy)]
 pub struct CandidatesRef<'s, 'e, LD, LL> {
     candidates: &'e Candidates<'s>,
-    parent: &'s Estimator<'s, LD, LL>,
+    parent: &'e Estimator<'s, LD, LL>,
 }
 impl<'s, 'e, LD, LL> CandidatesRef<'s, 'e, LD, LL> {
     pub fn score(self) -> OrdinaryScoreRef<'s> {
@@ -88,6 +96,9 @@ pub struct Event<'s, LD, LL> {
     score: OrdinaryScoreRef<'s>,
     #[getset(get = "pub")]
     candidates: CandidateList,
+    /// The candidates allowed by `reason` alone, within the level in the database.
+    #[getset(get = "pub")]
+    allowed: CandidateList,
     #[getset(get = "pub")]
     reason: Reason<LD, LL>,
 }
@@ -112,7 +123,7 @@ where
         Ok(())
     }
 }
-#[derive(Clone, Copy, Debug, Display)]
+#[derive(Clone, Copy, Debug, Display, Serialize, Deserialize)]
 #[display(bound(LD: Display, LL: Display))]
 pub enum Reason<LD, LL> {
     #[display("according to the database which stores {_0:?}")]
@@ -179,12 +190,19 @@ impl<'s, LD, LL> Estimator<'s, LD, LL> {
             .map
             .get_mut(&score)
             .with_context(|| format!("The following score was not in the map: {score:?}"))?;
+        let database_event = &self.events.0[candidates.reasons[0]];
+        let mut allowed = InternalScoreLevel::unknown(
+            self.version,
+            database_event.allowed.into_level(self.version),
+        );
+        allowed.retain(&predicate);
         let old_len = candidates.candidates.count_candidates();
         candidates.candidates.retain(predicate);
         if candidates.candidates.count_candidates() < old_len {
             let event = Event {
                 score,
                 candidates: candidates.candidates,
+                allowed,
                 reason,
             };
             trace!("{event}");
@@ -202,22 +220,22 @@ impl<'s, LD, LL> Estimator<'s, LD, LL> {
         }
         Ok(())
     }
-    pub fn get<'e: 's>(
-        &'e self,
-        score: OrdinaryScoreRef<'s>,
-    ) -> Option<CandidatesRef<'s, 'e, LD, LL>> {
+    pub fn get<'e>(&'e self, score: OrdinaryScoreRef<'s>) -> Option<CandidatesRef<'s, 'e, LD, LL>> {
         Some(CandidatesRef {
             candidates: self.map.get(&score)?,
             parent: self,
         })
     }
-    pub fn get_scores<'e: 's>(&'e self) -> impl Iterator<Item = CandidatesRef<'s, 'e, LD, LL>> {
+    pub fn get_scores<'e>(&'e self) -> impl Iterator<Item = CandidatesRef<'s, 'e, LD, LL>> {
         self.map.values().map(|candidates| CandidatesRef {
             candidates,
             parent: self,
         })
     }
 
+    pub fn version(&self) -> MaimaiVersion {
+        self.version
+    }
     pub fn events(&self) -> &[Event<'s, LD, LL>] {
         &self.events.0
     }
@@ -273,14 +291,7 @@ where
             NewOrOld::Old => 35,
         };
 
-        let new_song_threshold = if self.version >= MaimaiVersion::Circle {
-            // From Circle and later, the scores from the last two versions are considered new.
-            self.version
-                .previous()
-                .expect("If self.version is Circle or later, there is always a previous version")
-        } else {
-            self.version
-        };
+        let new_song_threshold = self.version.new_song_threshold();
         let score_applicable = |score_version: MaimaiVersion| {
             // XOR parity check: If new expected and score is new => applicable
             (new_or_old == NewOrOld::New) ^ (score_version >= new_song_threshold)
@@ -559,6 +570,58 @@ where
         Ok(())
     }
 
+    /// Applies newly fetched rating target lists,
+    /// and returns the range of `self.events()` added by this call.
+    ///
+    /// A newly determined level may make an older list informative,
+    /// so once the new lists add any event, the lists returned by `known(iteration)`
+    /// (for `iteration = 1, 2, ...`) and the song score list, if any,
+    /// are revisited until no more events are added, as `multi_user::estimate_all` does.
+    /// `known` may include the new lists as well.
+    pub fn guess_incrementally<R, I>(
+        &mut self,
+        new_rating_targets: impl IntoIterator<Item = R>,
+        known: impl Fn(usize) -> I,
+        song_score_list: Option<&AssociatedSongScoreList<'s>>,
+    ) -> anyhow::Result<Range<usize>>
+    where
+        R: RatingTargetListLike<'s, LL>,
+        R::Entry: Copy + Debug,
+        I: IntoIterator<Item = R>,
+    {
+        let start = self.event_len();
+        self.guess_from_rating_target_order(new_rating_targets)?;
+        if self.event_len() > start {
+            self.guess_until_fixpoint(|i| known(i + 1), song_score_list)?;
+        }
+        Ok(start..self.event_len())
+    }
+
+    /// Evaluates the lists returned by `lists(iteration)` (for `iteration = 0, 1, ...`)
+    /// followed by the song score list, if any, until no more events are added.
+    pub fn guess_until_fixpoint<R, I>(
+        &mut self,
+        lists: impl Fn(usize) -> I,
+        song_score_list: Option<&AssociatedSongScoreList<'s>>,
+    ) -> anyhow::Result<()>
+    where
+        R: RatingTargetListLike<'s, LL>,
+        R::Entry: Copy + Debug,
+        I: IntoIterator<Item = R>,
+    {
+        for i in 0.. {
+            let before_len = self.event_len();
+            self.guess_from_rating_target_order(lists(i))?;
+            if let Some(data) = song_score_list {
+                self.guess_by_sort_order(data)?;
+            }
+            if before_len == self.event_len() {
+                return Ok(());
+            }
+        }
+        bail!("Did not finish after 2^64-1 times (whoa, are humans still there?)");
+    }
+
     // We could do this, but not to for now, as it is less significant now.
     //
     // pub fn records_not_in_targets<'d>(
@@ -831,6 +894,7 @@ impl<'s> Candidates<'s> {
         reasons.push(events.push(Event {
             score: score.score(),
             candidates,
+            allowed: candidates,
             reason: Reason::Database(candidates),
         }));
         // let candidates = match score.level() {
@@ -900,3 +964,52 @@ pub fn visualize_rating_target<'est, 'd, 's: 'est + 'd, 'ent: 'd, 'e, LD: 'est,
     });
     lazy_format!("{level:<3} {candidates} {achievement:>9} {score}")
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::maimai::song_list::database::SongDatabase;
+
+    use super::test_util::{
+        all_candidates, constant, master, song_score_list, songs, TestEstimator, TestList, VERSION,
+    };
+
+    const TRUTH: [u8; 5] = [130, 133, 135, 131, 136];
+
+    #[test]
+    fn test_guess_incrementally() {
+        let songs = songs(TRUTH.len());
+        let database = SongDatabase::new(&songs).unwrap();
+        let lists = [
+            TestList::new("a", &database, &TRUTH, &[(0, 100_5000), (1, 99_5000)]),
+            TestList::new("b", &database, &TRUTH, &[(1, 100_0000), (2, 98_0000)]),
+            TestList::new(
+                "c",
+                &database,
+                &TRUTH,
+                &[(2, 100_5000), (3, 97_0000), (4, 99_0000)],
+            ),
+        ];
+        let song_score_list = song_score_list(&database, &[0, 3, 1, 2, 4]);
+        let lists = &lists;
+        let lists_until = |n: usize| move |i| lists[..n].iter().map(move |x| (x, i));
+
+        let mut full = TestEstimator::new(&database, VERSION).unwrap();
+        full.guess_by_sort_order(&song_score_list).unwrap();
+        full.guess_until_fixpoint(lists_until(3), Some(&song_score_list))
+            .unwrap();
+
+        let mut incremental = TestEstimator::new(&database, VERSION).unwrap();
+        incremental.guess_by_sort_order(&song_score_list).unwrap();
+        incremental
+            .guess_until_fixpoint(lists_until(2), Some(&song_score_list))
+            .unwrap();
+        incremental
+            .guess_incrementally([(&lists[2], 0)], lists_until(3), Some(&song_score_list))
+            .unwrap();
+        assert_eq!(all_candidates(&incremental), all_candidates(&full));
+
+        // Determined only by revisiting "c" after the song score list
+        let candidates = |i| full.get(master(&database, i)).unwrap().candidates();
+        assert_eq!(candidates(3).get_if_unique(), Some(constant(131)));
+    }
+}
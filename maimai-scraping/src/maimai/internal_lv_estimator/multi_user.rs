@@ -1,13 +1,16 @@
-use std::{ops::Range, path::PathBuf};
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+};
 
-use anyhow::bail;
+use anyhow::Context;
 use chrono::NaiveDateTime;
 use clap::Args;
 use derive_more::{Display, From};
 use getset::{CopyGetters, Getters};
 use log::warn;
 use maimai_scraping_utils::fs_json_util::read_json;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::maimai::{
     associated_user_data::{
@@ -24,6 +27,7 @@ use crate::maimai::{
 };
 
 use super::{
+    propagation::PropagationStats, provenance::SourceLabel, snapshot::Snapshot,
     song_score::AssociatedSongScoreList, Estimator, RatingTargetEntryLike, RatingTargetListLike,
     RecordLike,
 };
@@ -65,7 +69,7 @@ pub struct EstimatorConfig {
     pub ignore_time: bool,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug, From, Deserialize, Display)]
+#[derive(Clone, PartialEq, Eq, Debug, From, Serialize, Deserialize, Display)]
 pub struct UserName(String);
 
 pub type DataPair<'c> = (&'c UserConfig, MaimaiUserData);
@@ -95,6 +99,15 @@ impl Config {
             song_score_list,
         })
     }
+
+    /// Finds the user whose data is stored at `data_path`.
+    /// The paths are compared after canonicalization if possible,
+    /// so that e.g. a relative path matches the absolute one.
+    pub fn user_by_data_path(&self, data_path: &Path) -> Option<&UserConfig> {
+        let canonicalize = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_owned());
+        let data_path = canonicalize(data_path);
+        (self.users.iter()).find(|user| canonicalize(&user.data_path) == data_path)
+    }
 }
 
 #[derive(Clone, Copy, Debug, Display)]
@@ -118,6 +131,93 @@ pub struct RatingTargetLabel<'n> {
     iteration: usize,
 }
 
+impl SourceLabel for RecordLabel<'_> {}
+/// The same list is applied once per iteration of the propagation,
+/// so the iteration is kept on the event rather than the source.
+impl SourceLabel for RatingTargetLabel<'_> {
+    fn source_label(&self) -> String {
+        format!(
+            "rating target recorded at {} by {}",
+            self.timestamp, self.user
+        )
+    }
+    fn iteration(&self) -> Option<usize> {
+        Some(self.iteration)
+    }
+}
+
+/// `RecordLabel` without borrowing the user name, stored in snapshots.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum RecordLabelOwned {
+    FromData { play_time: PlayTime, user: UserName },
+    Additional,
+}
+/// `RatingTargetLabel` without borrowing the user name, stored in snapshots.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RatingTargetLabelOwned {
+    timestamp: PlayTime,
+    user: UserName,
+    iteration: usize,
+}
+
+pub type MultiUserSnapshot = Snapshot<RecordLabelOwned, RatingTargetLabelOwned>;
+
+pub fn to_snapshot(estimator: &MultiUserEstimator) -> anyhow::Result<MultiUserSnapshot> {
+    estimator.to_snapshot(
+        |label| match label {
+            RecordLabel::FromData(x) => RecordLabelOwned::FromData {
+                play_time: x.play_time,
+                user: x.user.clone(),
+            },
+            RecordLabel::Additional => RecordLabelOwned::Additional,
+        },
+        |label| RatingTargetLabelOwned {
+            timestamp: label.timestamp,
+            user: label.user.clone(),
+            iteration: label.iteration,
+        },
+    )
+}
+
+/// Restores an estimator from a snapshot.
+/// Every user appearing in the snapshot must be in `config`.
+pub fn from_snapshot<'s, 'n>(
+    database: &SongDatabase<'s>,
+    config: &'n Config,
+    snapshot: MultiUserSnapshot,
+) -> anyhow::Result<MultiUserEstimator<'s, 'n>> {
+    let user = |name: &UserName| {
+        config
+            .users
+            .iter()
+            .map(|user| &user.name)
+            .find(|x| *x == name)
+            .with_context(|| format!("User {name} is not in the config"))
+    };
+    Estimator::from_snapshot(
+        database,
+        snapshot,
+        |label| {
+            Ok(match label {
+                RecordLabelOwned::FromData { play_time, user: u } => {
+                    RecordLabel::FromData(RecordLabelFromData {
+                        play_time,
+                        user: user(&u)?,
+                    })
+                }
+                RecordLabelOwned::Additional => RecordLabel::Additional,
+            })
+        },
+        |label| {
+            Ok(RatingTargetLabel {
+                timestamp: label.timestamp,
+                user: user(&label.user)?,
+                iteration: label.iteration,
+            })
+        },
+    )
+}
+
 impl<'c, 'd, 's> RecordLike<'s, RecordLabel<'c>>
     for (&'c UserConfig, OrdinaryPlayRecordAssociated<'d, 's>)
 {
@@ -251,22 +351,117 @@ pub fn estimate_all<'s, 'c>(
         estimator.guess_by_sort_order(data)?;
     }
 
-    for i in 0.. {
-        let before_len = estimator.event_len();
-        for &(config, ref data) in &datas.data_pairs {
-            let rating_targets = data.rating_target();
-            estimator.guess_from_rating_target_order(
-                rating_targets
-                    .iter()
-                    .map(|&(time, ref list)| (config, time, list, i)),
-            )?;
-        }
+    estimator.guess_until_fixpoint(
+        |i| {
+            (datas.data_pairs.iter()).flat_map(move |&(config, ref data)| {
+                (data.rating_target().iter()).map(move |&(time, ref list)| (config, time, list, i))
+            })
+        },
+        datas.song_score_list.as_ref(),
+    )
+}
+
+/// Applies the rating target lists of a single user fetched along with `inserted_records`,
+/// and returns the range of the events added.
+///
+/// The records do not constrain the levels by themselves (see `estimate_all`),
+/// but a list is recorded at the last play when it is fetched,
+/// so the lists recorded at or after the earliest inserted record are the new ones.
+///
+/// Unlike `update_all`, the data of other users are not read again,
+/// and their lists are not revisited.
+/// For a single user, the result is the same as that of `update_all`;
+/// otherwise it may be weaker until the next full update.
+pub fn update_user_incrementally<'s, 'c>(
+    config: &'c UserConfig,
+    data: &UserDataOrdinaryAssociated<'_, 's>,
+    inserted_records: &[PlayTime],
+    song_score_list: Option<&AssociatedSongScoreList<'s>>,
+    estimator: &mut MultiUserEstimator<'s, 'c>,
+) -> anyhow::Result<Range<usize>> {
+    let Some(&earliest) = inserted_records.iter().min() else {
+        return Ok(estimator.event_len()..estimator.event_len());
+    };
+    let targets = data.rating_target();
+    let new_targets = targets
+        .iter()
+        .filter(|&&(time, _)| time >= earliest)
+        .map(|&(time, ref list)| (config, time, list, 0));
+    estimator.guess_incrementally(
+        new_targets,
+        |i| {
+            targets
+                .iter()
+                .map(move |&(time, ref list)| (config, time, list, i))
+        },
+        song_score_list,
+    )
+}
+
+/// Result of `propagate_all`.
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct PropagationReport {
+    /// Number of determined scores before the estimation.
+    pub initial: usize,
+    /// Number of determined scores when every list is evaluated only once.
+    pub one_pass: usize,
+    /// Number of determined scores after the propagation.
+    pub propagated: usize,
+    pub stats: PropagationStats,
+}
+
+/// Same as `estimate_all`, but revisits only the lists affected by new events
+/// with `Estimator::propagate`.
+/// Also reports how many scores are determined compared to evaluating every list once.
+pub fn propagate_all<'s, 'c>(
+    datas: &EstimatorDataSourceAssociated<'c, '_, 's>,
+    estimator: &mut MultiUserEstimator<'s, 'c>,
+) -> anyhow::Result<PropagationReport> {
+    let lists = (datas.data_pairs.iter())
+        .flat_map(|(config, data)| {
+            (data.rating_target().iter()).map(move |(time, list)| (*config, *time, list))
+        })
+        .collect::<Vec<_>>();
+    let list = |i: usize, iteration: usize| {
+        let (config, time, list) = lists[i];
+        (config, time, list, iteration)
+    };
+
+    let initial = estimator.num_determined_scores();
+    let one_pass = {
+        let mut estimator = estimator.clone();
+        estimator.guess_from_rating_target_order((0..lists.len()).map(|i| list(i, 0)))?;
         if let Some(data) = &datas.song_score_list {
             estimator.guess_by_sort_order(data)?;
         }
-        if before_len == estimator.event_len() {
-            return Ok(());
-        }
+        estimator.num_determined_scores()
+    };
+    let stats = estimator.propagate(lists.len(), list, datas.song_score_list.as_ref())?;
+    Ok(PropagationReport {
+        initial,
+        one_pass,
+        propagated: estimator.num_determined_scores(),
+        stats,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::maimai::{internal_lv_estimator::provenance::SourceLabel, schema::latest::PlayTime};
+
+    use super::{RatingTargetLabel, UserName};
+
+    #[test]
+    fn test_rating_target_source_label() {
+        let user = UserName::from("user".to_owned());
+        let timestamp: PlayTime = "2024-01-02T03:04:00".parse().unwrap();
+        let label = |iteration| RatingTargetLabel {
+            timestamp,
+            user: &user,
+            iteration,
+        };
+        assert_eq!(label(0).source_label(), label(3).source_label());
+        assert_eq!(SourceLabel::iteration(&label(3)), Some(3));
+        assert!(!label(3).source_label().contains("iteration"));
     }
-    bail!("Did not finish after 2^64-1 times (whoa, are humans still there?)");
 }
@@ -0,0 +1,156 @@
+//! Propagates constraints among rating target lists with a work list.
+//!
+//! Each rating target list is a constraint on the scores it contains:
+//! the ratings must sum up to its rating and respect its order.
+//! `guess_from_rating_target_order` narrows the candidates by one list at a time,
+//! so a list has to be revisited whenever the candidates of any of its scores change.
+//! Instead of sweeping all the lists until nothing changes (as `multi_user::estimate_all` does),
+//! only the lists affected by the new events are revisited.
+
+use std::{
+    collections::VecDeque,
+    fmt::{Debug, Display},
+};
+
+use hashbrown::HashMap;
+use serde::Serialize;
+
+use super::{
+    song_score::AssociatedSongScoreList, Estimator, Event, RatingTargetEntryLike,
+    RatingTargetListLike,
+};
+
+/// Statistics of `Estimator::propagate`.
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct PropagationStats {
+    /// Number of times a rating target list was evaluated.
+    pub list_evaluations: usize,
+    /// Number of times the song score list was evaluated.
+    pub song_score_list_evaluations: usize,
+    /// Number of events added.
+    pub events: usize,
+}
+
+impl<'s, LD, LL> Estimator<'s, LD, LL>
+where
+    Event<'s, LD, LL>: Display,
+{
+    /// Narrows the candidates until no list can narrow them further.
+    ///
+    /// `list(i, k)` returns the `i`-th of the `num_lists` rating target lists,
+    /// labeled as its `k`-th evaluation.
+    /// The song score list, if any, is evaluated whenever the lists are settled,
+    /// and the affected lists are revisited until it adds no more events.
+    pub fn propagate<R>(
+        &mut self,
+        num_lists: usize,
+        list: impl Fn(usize, usize) -> R,
+        song_score_list: Option<&AssociatedSongScoreList<'s>>,
+    ) -> anyhow::Result<PropagationStats>
+    where
+        R: RatingTargetListLike<'s, LL>,
+        R::Entry: Copy + Debug,
+    {
+        let start = self.event_len();
+        let mut lists_of_score = HashMap::<_, Vec<usize>>::new();
+        for i in 0..num_lists {
+            let list = list(i, 0);
+            for entries in [
+                list.target_new(),
+                list.target_old(),
+                list.candidates_new(),
+                list.candidates_old(),
+            ] {
+                for entry in entries {
+                    lists_of_score.entry(entry.score()).or_default().push(i);
+                }
+            }
+        }
+
+        let mut stats = PropagationStats::default();
+        let mut evaluations = vec![0; num_lists];
+        let mut queued = vec![true; num_lists];
+        let mut queue: VecDeque<_> = (0..num_lists).collect();
+        let enqueue_affected =
+            |events: &[Event<'s, LD, LL>], queue: &mut VecDeque<usize>, queued: &mut [bool]| {
+                for event in events {
+                    for &i in lists_of_score.get(&event.score).into_iter().flatten() {
+                        if !queued[i] {
+                            queued[i] = true;
+                            queue.push_back(i);
+                        }
+                    }
+                }
+            };
+        loop {
+            while let Some(i) = queue.pop_front() {
+                queued[i] = false;
+                let before_len = self.event_len();
+                self.guess_from_rating_target_order([list(i, evaluations[i])])?;
+                evaluations[i] += 1;
+                stats.list_evaluations += 1;
+                enqueue_affected(&self.events.0[before_len..], &mut queue, &mut queued);
+            }
+            let Some(song_score_list) = song_score_list else {
+                break;
+            };
+            let before_len = self.event_len();
+            self.guess_by_sort_order(song_score_list)?;
+            stats.song_score_list_evaluations += 1;
+            if self.event_len() == before_len {
+                break;
+            }
+            enqueue_affected(&self.events.0[before_len..], &mut queue, &mut queued);
+        }
+        stats.events = self.event_len() - start;
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::maimai::song_list::database::SongDatabase;
+
+    use super::super::test_util::{
+        all_candidates, song_score_list, songs, TestEstimator, TestList, VERSION,
+    };
+
+    const TRUTH: [u8; 6] = [130, 133, 135, 131, 136, 132];
+
+    #[test]
+    fn test_propagate_reaches_fixpoint() {
+        let songs = songs(TRUTH.len());
+        let database = SongDatabase::new(&songs).unwrap();
+        let lists = [
+            TestList::new("a", &database, &TRUTH, &[(0, 100_5000), (1, 99_5000)]),
+            TestList::new("b", &database, &TRUTH, &[(1, 100_0000), (2, 98_0000)]),
+            TestList::new(
+                "c",
+                &database,
+                &TRUTH,
+                &[(2, 100_5000), (3, 97_0000), (4, 99_0000)],
+            ),
+            TestList::new("d", &database, &TRUTH, &[(5, 100_2000), (3, 99_9000)]),
+        ];
+        let song_score_list = song_score_list(&database, &[0, 3, 5, 1, 2, 4]);
+
+        let mut sweep = TestEstimator::new(&database, VERSION).unwrap();
+        sweep.guess_by_sort_order(&song_score_list).unwrap();
+        sweep
+            .guess_until_fixpoint(
+                |i| lists.iter().map(move |x| (x, i)),
+                Some(&song_score_list),
+            )
+            .unwrap();
+
+        let mut work_list = TestEstimator::new(&database, VERSION).unwrap();
+        work_list.guess_by_sort_order(&song_score_list).unwrap();
+        let stats = work_list
+            .propagate(lists.len(), |i, k| (&lists[i], k), Some(&song_score_list))
+            .unwrap();
+
+        assert_eq!(all_candidates(&work_list), all_candidates(&sweep));
+        assert!(stats.list_evaluations >= lists.len());
+        assert!(stats.song_score_list_evaluations >= 1);
+    }
+}
@@ -0,0 +1,233 @@
+//! Exports which sources constrained which scores, as a graph.
+
+use std::{fmt::Display, io::Write};
+
+use hashbrown::HashMap;
+use serde::Serialize;
+
+use crate::maimai::rating::InternalScoreLevel;
+
+use super::{Estimator, Reason};
+
+/// A bipartite graph from the sources of events to the scores they constrained.
+#[derive(Clone, Debug, Serialize)]
+pub struct ProvenanceGraph {
+    pub sources: Vec<SourceNode>,
+    pub scores: Vec<ScoreNode>,
+    /// In the order of the events.
+    pub edges: Vec<Edge>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, strum::Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum SourceKind {
+    Database,
+    Delta,
+    List,
+    SongScoreList,
+    Assumption,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SourceNode {
+    pub kind: SourceKind,
+    pub label: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ScoreNode {
+    pub title: String,
+    /// The current candidates.
+    pub candidates: InternalScoreLevel,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Edge {
+    /// Index of the event in `Estimator::events`.
+    pub event: usize,
+    /// The iteration in which the source was applied, if the source is applied repeatedly.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iteration: Option<usize>,
+    /// Index of `ProvenanceGraph::sources`.
+    pub source: usize,
+    /// Index of `ProvenanceGraph::scores`.
+    pub score: usize,
+    /// The candidates right after the event.
+    pub candidates: InternalScoreLevel,
+}
+
+/// A label of an event, split into the source and the part specific to the event.
+pub trait SourceLabel: Display {
+    /// Events whose labels have the same source label share a single source node.
+    fn source_label(&self) -> String {
+        self.to_string()
+    }
+    fn iteration(&self) -> Option<usize> {
+        None
+    }
+}
+
+impl<'s, LD, LL> Estimator<'s, LD, LL>
+where
+    LD: SourceLabel,
+    LL: SourceLabel,
+{
+    /// Builds the provenance graph of the events.
+    /// Events from the database are omitted unless `include_database` is set,
+    /// as there is one for every score.
+    pub fn provenance(&self, include_database: bool) -> ProvenanceGraph {
+        let mut graph = ProvenanceGraph {
+            sources: vec![],
+            scores: vec![],
+            edges: vec![],
+        };
+        let mut source_ids = HashMap::new();
+        let mut score_ids = HashMap::new();
+        for (i, event) in self.events.0.iter().enumerate() {
+            let (kind, label, iteration) = match event.reason {
+                Reason::Database(_) if !include_database => continue,
+                Reason::Database(_) => (SourceKind::Database, "database".to_owned(), None),
+                Reason::Delta(_, _, ref l) => (SourceKind::Delta, l.source_label(), l.iteration()),
+                Reason::List(ref l) => (SourceKind::List, l.source_label(), l.iteration()),
+                Reason::SongScoreList(lv) => (SourceKind::SongScoreList, format!("Lv.{lv}"), None),
+                Reason::Assumption => (SourceKind::Assumption, "assumption".to_owned(), None),
+            };
+            let source = *source_ids.entry((kind, label.clone())).or_insert_with(|| {
+                graph.sources.push(SourceNode { kind, label });
+                graph.sources.len() - 1
+            });
+            let score = *score_ids.entry(event.score).or_insert_with(|| {
+                graph.scores.push(ScoreNode {
+                    title: event.score.to_string(),
+                    candidates: self.map[&event.score].candidates,
+                });
+                graph.scores.len() - 1
+            });
+            graph.edges.push(Edge {
+                event: i,
+                iteration,
+                source,
+                score,
+                candidates: event.candidates,
+            });
+        }
+        graph
+    }
+}
+
+impl ProvenanceGraph {
+    /// Keeps only the sources satisfying `f`, and the scores constrained by them.
+    pub fn retain_sources(&mut self, f: impl Fn(&SourceNode) -> bool) {
+        let source_map = reindex(&mut self.sources, f);
+        self.edges.retain_mut(|edge| {
+            source_map[edge.source].is_some_and(|i| {
+                edge.source = i;
+                true
+            })
+        });
+        let mut used = vec![false; self.scores.len()];
+        for edge in &self.edges {
+            used[edge.score] = true;
+        }
+        let mut used = used.into_iter();
+        let score_map = reindex(&mut self.scores, |_| used.next().unwrap());
+        for edge in &mut self.edges {
+            edge.score = score_map[edge.score].unwrap();
+        }
+    }
+
+    /// Writes the graph in the Graphviz DOT format.
+    /// Edges are labeled with the order of the events (and the iteration, if any)
+    /// and the resulting candidates.
+    pub fn write_dot(&self, mut writer: impl Write) -> anyhow::Result<()> {
+        writeln!(writer, "digraph provenance {{")?;
+        writeln!(writer, "  rankdir=LR;")?;
+        for (i, source) in self.sources.iter().enumerate() {
+            let label = escape(&format!("[{}] {}", source.kind, source.label));
+            writeln!(writer, "  src{i} [shape=box, label=\"{label}\"];")?;
+        }
+        for (i, score) in self.scores.iter().enumerate() {
+            let label = escape(&format!("{}\n{}", score.title, score.candidates));
+            writeln!(writer, "  score{i} [shape=ellipse, label=\"{label}\"];")?;
+        }
+        for (order, edge) in self.edges.iter().enumerate() {
+            let iteration = match edge.iteration {
+                Some(iteration) => format!(" (iteration {iteration})"),
+                None => String::new(),
+            };
+            writeln!(
+                writer,
+                "  src{} -> score{} [label=\"#{order}{iteration}: {}\"];",
+                edge.source,
+                edge.score,
+                escape(&edge.candidates.to_string()),
+            )?;
+        }
+        writeln!(writer, "}}")?;
+        Ok(())
+    }
+}
+
+/// Removes the elements not satisfying `f`,
+/// and returns the new index of each element (`None` if removed).
+fn reindex<T>(elements: &mut Vec<T>, mut f: impl FnMut(&T) -> bool) -> Vec<Option<usize>> {
+    let mut map = vec![];
+    let mut count = 0;
+    elements.retain(|x| {
+        let keep = f(x);
+        map.push(keep.then(|| {
+            count += 1;
+            count - 1
+        }));
+        keep
+    });
+    map
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::maimai::rating::InternalScoreLevel;
+
+    use super::{Edge, ProvenanceGraph, ScoreNode, SourceKind, SourceNode};
+
+    #[test]
+    fn test_retain_sources() {
+        let source = |label: &str| SourceNode {
+            kind: SourceKind::List,
+            label: label.to_owned(),
+        };
+        let score = |title: &str| ScoreNode {
+            title: title.to_owned(),
+            candidates: InternalScoreLevel::empty(),
+        };
+        let edge = |event, source, score| Edge {
+            event,
+            iteration: None,
+            source,
+            score,
+            candidates: InternalScoreLevel::empty(),
+        };
+        let mut graph = ProvenanceGraph {
+            sources: vec![source("a"), source("b")],
+            scores: vec![score("x"), score("y"), score("z")],
+            edges: vec![edge(0, 0, 0), edge(1, 1, 1), edge(2, 1, 2), edge(3, 0, 2)],
+        };
+        graph.retain_sources(|x| x.label == "b");
+        assert_eq!(graph.sources.len(), 1);
+        let titles: Vec<_> = graph.scores.iter().map(|x| &x.title[..]).collect();
+        assert_eq!(titles, ["y", "z"]);
+        let edges: Vec<_> = graph
+            .edges
+            .iter()
+            .map(|e| (e.event, e.source, e.score))
+            .collect();
+        assert_eq!(edges, [(1, 0, 0), (2, 0, 1)]);
+    }
+}
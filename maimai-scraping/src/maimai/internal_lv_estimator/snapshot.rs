@@ -0,0 +1,290 @@
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Context};
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::maimai::{
+    rating::InternalScoreLevel,
+    schema::latest::{ScoreDifficulty, ScoreGeneration, SongIcon},
+    song_list::database::{OrdinaryScoreRef, SongDatabase},
+    version::MaimaiVersion,
+};
+
+use super::{Candidates, Estimator, Event, IndexedVec, Reason};
+
+/// Incremented whenever the format of `Snapshot` changes incompatibly.
+pub const SNAPSHOT_FORMAT_VERSION: u32 = 2;
+
+/// Serializable state of an `Estimator`.
+///
+/// Scores are identified by `ScoreKey` instead of references to the database,
+/// so that a snapshot can be loaded with a different (e.g. newer) database.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Snapshot<LD, LL> {
+    pub format_version: u32,
+    pub version: MaimaiVersion,
+    pub scores: Vec<ScoreSnapshot>,
+    pub events: Vec<EventSnapshot<LD, LL>>,
+}
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+pub struct ScoreKey {
+    pub icon: SongIcon,
+    pub generation: ScoreGeneration,
+    pub difficulty: ScoreDifficulty,
+}
+impl ScoreKey {
+    pub fn new(score: OrdinaryScoreRef) -> anyhow::Result<Self> {
+        Ok(Self {
+            icon: (score.scores().song().song().icon.clone())
+                .with_context(|| format!("Song does not have an icon: {score}"))?,
+            generation: score.scores().generation(),
+            difficulty: score.difficulty(),
+        })
+    }
+
+    pub fn resolve<'s>(&self, database: &SongDatabase<'s>) -> anyhow::Result<OrdinaryScoreRef<'s>> {
+        database
+            .song_from_icon(&self.icon)?
+            .scores(self.generation)
+            .and_then(|scores| scores.score(self.difficulty))
+            .with_context(|| format!("Score not found in the database: {self:?}"))
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ScoreSnapshot {
+    pub key: ScoreKey,
+    /// Only for readability; ignored on loading.
+    pub title: String,
+    pub candidates: InternalScoreLevel,
+    /// Indices of `Snapshot::events` that constrained this score.
+    pub reasons: Vec<usize>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EventSnapshot<LD, LL> {
+    pub key: ScoreKey,
+    pub candidates: InternalScoreLevel,
+    pub allowed: InternalScoreLevel,
+    pub reason: Reason<LD, LL>,
+}
+
+/// A score whose candidates differ between two snapshots.
+#[derive(Clone, Debug, Serialize)]
+pub struct ScoreDiff<'a> {
+    pub key: &'a ScoreKey,
+    pub title: &'a str,
+    pub before: Option<InternalScoreLevel>,
+    pub after: Option<InternalScoreLevel>,
+}
+
+impl<LD, LL> Snapshot<LD, LL> {
+    /// Lists the scores whose candidates changed from `self` to `other`.
+    pub fn diff<'a, LD2, LL2>(&'a self, other: &'a Snapshot<LD2, LL2>) -> Vec<ScoreDiff<'a>> {
+        let mut map = BTreeMap::<_, (_, Option<_>, Option<_>)>::new();
+        for score in &self.scores {
+            map.entry(&score.key)
+                .or_insert((&score.title[..], None, None))
+                .1 = Some(score.candidates);
+        }
+        for score in &other.scores {
+            map.entry(&score.key)
+                .or_insert((&score.title[..], None, None))
+                .2 = Some(score.candidates);
+        }
+        map.into_iter()
+            .filter(|(_, (_, before, after))| before != after)
+            .map(|(key, (title, before, after))| ScoreDiff {
+                key,
+                title,
+                before,
+                after,
+            })
+            .collect()
+    }
+}
+
+impl<LD, LL> Reason<LD, LL> {
+    pub fn as_ref(&self) -> Reason<&LD, &LL> {
+        match self {
+            Reason::Database(x) => Reason::Database(*x),
+            Reason::Delta(a, r, l) => Reason::Delta(*a, *r, l),
+            Reason::List(l) => Reason::List(l),
+            Reason::SongScoreList(x) => Reason::SongScoreList(*x),
+            Reason::Assumption => Reason::Assumption,
+        }
+    }
+
+    pub fn try_map_labels<LD2, LL2>(
+        self,
+        label_d: impl FnOnce(LD) -> anyhow::Result<LD2>,
+        label_l: impl FnOnce(LL) -> anyhow::Result<LL2>,
+    ) -> anyhow::Result<Reason<LD2, LL2>> {
+        Ok(match self {
+            Reason::Database(x) => Reason::Database(x),
+            Reason::Delta(a, r, l) => Reason::Delta(a, r, label_d(l)?),
+            Reason::List(l) => Reason::List(label_l(l)?),
+            Reason::SongScoreList(x) => Reason::SongScoreList(x),
+            Reason::Assumption => Reason::Assumption,
+        })
+    }
+}
+
+impl<'s, LD, LL> Estimator<'s, LD, LL> {
+    /// Converts the state into a snapshot, converting labels with the given functions.
+    pub fn to_snapshot<SD, SL>(
+        &self,
+        label_d: impl Fn(&LD) -> SD,
+        label_l: impl Fn(&LL) -> SL,
+    ) -> anyhow::Result<Snapshot<SD, SL>> {
+        let mut keys = HashMap::<_, ScoreKey>::new();
+        let mut key_of = |score: OrdinaryScoreRef<'s>| {
+            use hashbrown::hash_map::Entry::*;
+            anyhow::Ok(match keys.entry(score) {
+                Occupied(e) => e.get().clone(),
+                Vacant(e) => e.insert(ScoreKey::new(score)?).clone(),
+            })
+        };
+        let mut scores = self
+            .map
+            .values()
+            .map(|candidates| {
+                anyhow::Ok(ScoreSnapshot {
+                    key: key_of(candidates.score)?,
+                    title: candidates.score.to_string(),
+                    candidates: candidates.candidates,
+                    reasons: candidates.reasons.clone(),
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        scores.sort_by(|x, y| x.key.cmp(&y.key));
+        let events = self
+            .events
+            .0
+            .iter()
+            .map(|event| {
+                anyhow::Ok(EventSnapshot {
+                    key: key_of(event.score)?,
+                    candidates: event.candidates,
+                    allowed: event.allowed,
+                    reason: (event.reason.as_ref())
+                        .try_map_labels(|l| Ok(label_d(l)), |l| Ok(label_l(l)))?,
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Snapshot {
+            format_version: SNAPSHOT_FORMAT_VERSION,
+            version: self.version,
+            scores,
+            events,
+        })
+    }
+
+    /// Restores the state from a snapshot, converting labels with the given functions.
+    ///
+    /// Scores in the database that are missing in the snapshot
+    /// are initialized with the levels in the database, as in `Estimator::new`.
+    pub fn from_snapshot<SD, SL>(
+        database: &SongDatabase<'s>,
+        snapshot: Snapshot<SD, SL>,
+        mut label_d: impl FnMut(SD) -> anyhow::Result<LD>,
+        mut label_l: impl FnMut(SL) -> anyhow::Result<LL>,
+    ) -> anyhow::Result<Self> {
+        if snapshot.format_version != SNAPSHOT_FORMAT_VERSION {
+            bail!(
+                "Unsupported snapshot format version: {} (expected {SNAPSHOT_FORMAT_VERSION})",
+                snapshot.format_version
+            );
+        }
+        let version = snapshot.version;
+
+        let mut events = IndexedVec(vec![]);
+        for event in snapshot.events {
+            events.push(Event {
+                score: event.key.resolve(database)?,
+                candidates: event.candidates,
+                allowed: event.allowed,
+                reason: event.reason.try_map_labels(&mut label_d, &mut label_l)?,
+            });
+        }
+
+        let mut map = HashMap::new();
+        for score in snapshot.scores {
+            let resolved = score.key.resolve(database)?;
+            if let Some(&i) = score.reasons.iter().find(|&&i| i >= events.0.len()) {
+                bail!("Event index {i} out of range for {}", score.title);
+            }
+            let candidates = Candidates {
+                score: resolved,
+                candidates: score.candidates,
+                reasons: score.reasons,
+            };
+            if map.insert(resolved, candidates).is_some() {
+                bail!("Duplicate score in the snapshot: {:?}", score.key);
+            }
+        }
+        for score in database.all_scores_for_version(version) {
+            if !map.contains_key(&score.score()) {
+                let candidates = Candidates::new(&mut events, version, score, false)?;
+                map.insert(score.score(), candidates);
+            }
+        }
+
+        Ok(Self {
+            version,
+            map,
+            events,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Context;
+
+    use crate::maimai::song_list::database::SongDatabase;
+
+    use super::{
+        super::test_util::{
+            all_candidates, song_score_list, songs, TestEstimator, TestLabel, TestList, VERSION,
+        },
+        Snapshot,
+    };
+
+    const TRUTH: [u8; 3] = [131, 134, 136];
+
+    #[test]
+    fn test_round_trip() {
+        let songs = songs(TRUTH.len());
+        let database = SongDatabase::new(&songs).unwrap();
+        let list = TestList::new("a", &database, &TRUTH, &[(0, 100_5000), (1, 99_0000)]);
+        let mut estimator = TestEstimator::new(&database, VERSION).unwrap();
+        estimator
+            .guess_by_sort_order(&song_score_list(&database, &[0, 1, 2]))
+            .unwrap();
+        estimator
+            .guess_from_rating_target_order([(&list, 0)])
+            .unwrap();
+
+        let save = |l: &TestLabel| (l.0.to_owned(), l.1);
+        let load = |(name, i): (String, usize)| {
+            let name = ["a"].into_iter().find(|&x| x == name).context("Unknown")?;
+            anyhow::Ok(TestLabel(name, i))
+        };
+        let snapshot = estimator.to_snapshot(save, save).unwrap();
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let loaded: Snapshot<(String, usize), (String, usize)> =
+            serde_json::from_str(&json).unwrap();
+        let restored = TestEstimator::from_snapshot(&database, loaded, load, load).unwrap();
+
+        assert_eq!(all_candidates(&restored), all_candidates(&estimator));
+        let resaved = restored.to_snapshot(save, save).unwrap();
+        assert!(snapshot.diff(&resaved).is_empty());
+        assert_eq!(
+            serde_json::to_value(&resaved).unwrap(),
+            serde_json::to_value(&snapshot).unwrap()
+        );
+    }
+}
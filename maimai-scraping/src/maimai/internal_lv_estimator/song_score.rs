@@ -168,3 +168,22 @@ impl<'s> AssociatedSongScoreList<'s> {
         Ok(Self { scores_by_level })
     }
 }
+
+#[cfg(test)]
+impl<'s> AssociatedSongScoreList<'s> {
+    /// A list of a single level, where `scores` are sorted in the order of the internal levels.
+    pub(super) fn from_sorted_scores(
+        level: ScoreLevel,
+        scores: impl IntoIterator<Item = OrdinaryScoreRef<'s>>,
+    ) -> Self {
+        let scores = (scores.into_iter().enumerate())
+            .map(|(scores_index, score)| ScoreAndOrder {
+                score,
+                order: ScoreOrder { scores_index },
+            })
+            .collect();
+        Self {
+            scores_by_level: [(level, scores)].into(),
+        }
+    }
+}
@@ -0,0 +1,178 @@
+//! Synthetic songs and rating target lists for the tests of the estimator.
+
+use std::ops::Range;
+
+use chrono::NaiveDateTime;
+use derive_more::Display;
+use itertools::Itertools;
+
+use crate::maimai::{
+    rating::{rank_coef, single_song_rating, InternalScoreLevel, ScoreConstant, ScoreLevel},
+    schema::latest::{AchievementValue, PlayTime, RatingValue, ScoreDifficulty, ScoreGeneration},
+    song_list::{
+        database::{OrdinaryScoreRef, SongDatabase},
+        OrdinaryScores, Song,
+    },
+    version::MaimaiVersion,
+};
+
+use super::{
+    song_score::AssociatedSongScoreList, Estimator, RatingTargetEntryLike, RatingTargetListLike,
+};
+
+pub const VERSION: MaimaiVersion = MaimaiVersion::Buddies;
+
+pub type TestEstimator<'s> = Estimator<'s, TestLabel, TestLabel>;
+
+/// The name of a list and the iteration.
+#[derive(Clone, Copy, Debug, Display)]
+#[display("{_0} (iteration {_1})")]
+pub struct TestLabel(pub &'static str, pub usize);
+
+pub fn lv13() -> ScoreLevel {
+    ScoreLevel::new(13, false).unwrap()
+}
+
+/// Songs named `0`, `1`, ... whose scores are all Lv.13 (13.0 to 13.6) in `VERSION`.
+pub fn songs(n: usize) -> Vec<Song> {
+    (0..n)
+        .map(|i| {
+            let mut song = Song {
+                icon: Some(
+                    format!("https://maimaidx.jp/maimai-mobile/img/Music/{i}.png")
+                        .parse()
+                        .unwrap(),
+                ),
+                ..Default::default()
+            };
+            song.name[VERSION] = Some(i.to_string().into());
+            let mut scores = OrdinaryScores {
+                version: Some(VERSION),
+                ..Default::default()
+            };
+            for difficulty in [
+                ScoreDifficulty::Basic,
+                ScoreDifficulty::Advanced,
+                ScoreDifficulty::Expert,
+                ScoreDifficulty::Master,
+            ] {
+                scores.get_score_mut(difficulty).unwrap().levels[VERSION] =
+                    Some(InternalScoreLevel::unknown(VERSION, lv13()));
+            }
+            song.scores[ScoreGeneration::Deluxe] = Some(scores);
+            song
+        })
+        .collect()
+}
+
+/// The master score of the `i`-th song.
+pub fn master<'s>(database: &SongDatabase<'s>, i: usize) -> OrdinaryScoreRef<'s> {
+    database.songs()[i]
+        .scores(ScoreGeneration::Deluxe)
+        .unwrap()
+        .score(ScoreDifficulty::Master)
+        .unwrap()
+}
+
+pub fn constant(x: u8) -> ScoreConstant {
+    ScoreConstant::try_from(x).unwrap()
+}
+
+/// The candidates of every score, to compare the results of estimators.
+pub fn all_candidates(estimator: &TestEstimator) -> Vec<(String, InternalScoreLevel)> {
+    estimator
+        .get_scores()
+        .map(|x| (x.score().to_string(), x.candidates()))
+        .sorted_by(|x, y| x.0.cmp(&y.0))
+        .collect()
+}
+
+/// A rating target list consisting only of the new target entries,
+/// all of which contribute to the rating.
+pub struct TestList<'s> {
+    name: &'static str,
+    rating: u16,
+    entries: Vec<TestEntry<'s>>,
+}
+#[derive(Clone, Copy, Debug)]
+pub struct TestEntry<'s> {
+    score: OrdinaryScoreRef<'s>,
+    achievement: AchievementValue,
+}
+impl<'s> TestList<'s> {
+    /// Makes a list of the master scores of the `(song, achievement)` pairs
+    /// consistent with the actual internal levels `truth` of the master scores.
+    pub fn new(
+        name: &'static str,
+        database: &SongDatabase<'s>,
+        truth: &[u8],
+        entries: &[(usize, u32)],
+    ) -> Self {
+        let entries = entries
+            .iter()
+            .map(|&(i, achievement)| {
+                let achievement = AchievementValue::try_from(achievement).unwrap();
+                let a = achievement;
+                let rating = single_song_rating(constant(truth[i]), a, rank_coef(a)).get();
+                let entry = TestEntry {
+                    score: master(database, i),
+                    achievement,
+                };
+                (rating, entry)
+            })
+            .sorted_by_key(|&(rating, entry)| (rating, entry.achievement))
+            .rev()
+            .collect_vec();
+        Self {
+            name,
+            rating: entries.iter().map(|x| x.0).sum(),
+            entries: entries.into_iter().map(|x| x.1).collect(),
+        }
+    }
+}
+
+impl<'s> RatingTargetEntryLike<'s> for TestEntry<'s> {
+    fn score(&self) -> OrdinaryScoreRef<'s> {
+        self.score
+    }
+    fn achievement(&self) -> AchievementValue {
+        self.achievement
+    }
+}
+/// A list labeled with the iteration, as in `multi_user`.
+impl<'s> RatingTargetListLike<'s, TestLabel> for (&TestList<'s>, usize) {
+    fn played_within(&self, _: Range<PlayTime>) -> bool {
+        true
+    }
+    fn play_time(&self) -> NaiveDateTime {
+        VERSION.start_time()
+    }
+    fn rating(&self) -> RatingValue {
+        RatingValue::from(self.0.rating)
+    }
+    type Entry = TestEntry<'s>;
+    type Entries = Vec<TestEntry<'s>>;
+    fn target_new(&self) -> Self::Entries {
+        self.0.entries.clone()
+    }
+    fn target_old(&self) -> Self::Entries {
+        vec![]
+    }
+    fn candidates_new(&self) -> Self::Entries {
+        vec![]
+    }
+    fn candidates_old(&self) -> Self::Entries {
+        vec![]
+    }
+    fn label(&self) -> TestLabel {
+        TestLabel(self.0.name, self.1)
+    }
+}
+
+/// A song score list of Lv.13 listing the master scores of `songs` in this order.
+pub fn song_score_list<'s>(
+    database: &SongDatabase<'s>,
+    songs: &[usize],
+) -> AssociatedSongScoreList<'s> {
+    AssociatedSongScoreList::from_sorted_scores(lv13(), songs.iter().map(|&i| master(database, i)))
+}
@@ -0,0 +1,278 @@
+use std::collections::BTreeMap;
+
+use enum_map::{Enum, EnumMap};
+use serde::Serialize;
+
+use super::{
+    achievement::Mistake,
+    schema::latest::{
+        AchievementValue, JudgeCount, JudgeCountWithCP, JudgeCountWithoutCP, JudgeResult,
+        PlayRecord,
+    },
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Enum, Serialize)]
+pub enum NoteType {
+    Tap,
+    Hold,
+    Slide,
+    Touch,
+    Break,
+}
+impl NoteType {
+    /// Relative weight of a note in the base score (tap = 1).
+    pub fn base_weight(self) -> u64 {
+        use NoteType::*;
+        match self {
+            Tap | Touch => 1,
+            Hold => 2,
+            Slide => 3,
+            Break => 5,
+        }
+    }
+
+    pub fn all() -> [Self; 5] {
+        use NoteType::*;
+        [Tap, Hold, Slide, Touch, Break]
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Enum, Serialize)]
+pub enum JudgeGrade {
+    CriticalPerfect,
+    Perfect,
+    Great,
+    Good,
+    Miss,
+}
+impl JudgeGrade {
+    /// Deduction from the base score of a note, in tenths of its value.
+    ///
+    /// For break notes, the judge result does not tell the sub-grade of great
+    /// (which deducts 2, 4 or 5 tenths), so the smallest one is used.
+    pub fn base_deduction(self, note_type: NoteType) -> u64 {
+        // Break mistakes are counted per note, and the others per tap
+        let unit = match note_type {
+            NoteType::Break => note_type.base_weight(),
+            _ => 1,
+        };
+        Mistake::of_judge(self, note_type).map_or(0, |m| m.note_deduction() / unit)
+    }
+
+    /// Deduction from the break bonus of a break note, in twentieths.
+    ///
+    /// A perfect deducts either 5 or 10 depending on the timing,
+    /// which is not recorded; the smaller one is used.
+    pub fn bonus_deduction(self) -> u64 {
+        Mistake::of_judge(self, NoteType::Break).map_or(0, Mistake::bonus_deduction)
+    }
+
+    pub fn all() -> [Self; 5] {
+        use JudgeGrade::*;
+        [CriticalPerfect, Perfect, Great, Good, Miss]
+    }
+}
+
+/// Number of notes for each note type and judge grade.
+pub type JudgeTable = EnumMap<NoteType, EnumMap<JudgeGrade, u32>>;
+
+pub fn judge_table(judge: JudgeResult) -> JudgeTable {
+    fn without_cp(x: JudgeCountWithoutCP) -> EnumMap<JudgeGrade, u32> {
+        use JudgeGrade::*;
+        enum_map::enum_map! {
+            CriticalPerfect => 0,
+            Perfect => x.perfect(),
+            Great => x.great(),
+            Good => x.good(),
+            Miss => x.miss(),
+        }
+    }
+    fn with_cp(x: JudgeCountWithCP) -> EnumMap<JudgeGrade, u32> {
+        let mut ret = without_cp(x.others());
+        ret[JudgeGrade::CriticalPerfect] = x.critical_perfect();
+        ret
+    }
+    fn count(x: JudgeCount) -> EnumMap<JudgeGrade, u32> {
+        match x {
+            JudgeCount::Nothing => EnumMap::default(),
+            JudgeCount::JudgeCountWithCP(x) => with_cp(x),
+            JudgeCount::JudgeCountWithoutCP(x) => without_cp(x),
+        }
+    }
+    enum_map::enum_map! {
+        NoteType::Tap => count(judge.tap()),
+        NoteType::Hold => count(judge.hold()),
+        NoteType::Slide => count(judge.slide()),
+        NoteType::Touch => count(judge.touch()),
+        NoteType::Break => with_cp(judge.break_()),
+    }
+}
+
+/// Achievement lost by each note type and judge grade, in percent.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct LossBreakdown {
+    pub by_judge: EnumMap<NoteType, EnumMap<JudgeGrade, f64>>,
+    /// The part of the actual loss that cannot be attributed from the judge counts,
+    /// i.e. break perfects and greats that were worse than assumed.
+    pub unexplained: f64,
+}
+impl LossBreakdown {
+    pub fn new(judge: JudgeResult, achievement: AchievementValue) -> Self {
+        let table = judge_table(judge);
+        let total_weight: u64 = table
+            .iter()
+            .map(|(t, c)| c.values().sum::<u32>() as u64 * t.base_weight())
+            .sum();
+        let break_count: u64 = table[NoteType::Break].values().sum::<u32>() as u64;
+
+        let mut by_judge = EnumMap::<NoteType, EnumMap<JudgeGrade, f64>>::default();
+        for (note_type, counts) in &table {
+            for (grade, &count) in counts {
+                let count = count as f64;
+                let mut loss = 0.;
+                if total_weight > 0 {
+                    let deduction =
+                        (note_type.base_weight() * grade.base_deduction(note_type)) as f64 / 10.;
+                    loss += count * deduction / total_weight as f64 * 100.;
+                }
+                if note_type == NoteType::Break && break_count > 0 {
+                    let deduction = grade.bonus_deduction() as f64 / 20.;
+                    loss += count * deduction / break_count as f64;
+                }
+                by_judge[note_type][grade] = loss;
+            }
+        }
+        let explained: f64 = by_judge.values().flat_map(|x| x.values()).sum();
+        let actual = (101_0000 - achievement.get().min(101_0000)) as f64 / 10000.;
+        Self {
+            by_judge,
+            // Achievement is truncated to 4 digits, so ignore differences below that
+            unexplained: (actual - explained).max(0.),
+        }
+    }
+
+    pub fn by_note_type(&self) -> EnumMap<NoteType, f64> {
+        EnumMap::from_fn(|t| self.by_judge[t].values().sum())
+    }
+
+    pub fn by_grade(&self) -> EnumMap<JudgeGrade, f64> {
+        EnumMap::from_fn(|g| self.by_judge.values().map(|x| x[g]).sum())
+    }
+
+    pub fn total(&self) -> f64 {
+        self.by_note_type().values().sum::<f64>() + self.unexplained
+    }
+
+    /// The note type that cost the most achievement, if any achievement was lost.
+    pub fn costliest_note_type(&self) -> Option<(NoteType, f64)> {
+        self.by_note_type()
+            .into_iter()
+            .filter(|&(_, loss)| loss > 0.)
+            .max_by(|x, y| x.1.total_cmp(&y.1))
+    }
+
+    pub fn add(&mut self, other: &Self) {
+        for (t, g) in NoteType::all()
+            .into_iter()
+            .flat_map(|t| JudgeGrade::all().map(|g| (t, g)))
+        {
+            self.by_judge[t][g] += other.by_judge[t][g];
+        }
+        self.unexplained += other.unexplained;
+    }
+
+    pub fn scale(&mut self, factor: f64) {
+        for x in self.by_judge.values_mut().flat_map(|x| x.values_mut()) {
+            *x *= factor;
+        }
+        self.unexplained *= factor;
+    }
+
+    /// Average of the breakdowns.  Returns `None` if empty.
+    pub fn average<'a>(breakdowns: impl IntoIterator<Item = &'a Self>) -> Option<Self> {
+        let mut ret = Self::default();
+        let mut count = 0;
+        for x in breakdowns {
+            ret.add(x);
+            count += 1;
+        }
+        (count > 0).then(|| {
+            ret.scale(1. / count as f64);
+            ret
+        })
+    }
+}
+
+/// Groups records by `key` and returns the number of records
+/// and the average breakdown of each group.
+pub fn average_by<'d, K: Ord>(
+    records: impl IntoIterator<Item = &'d PlayRecord>,
+    key: impl Fn(&PlayRecord) -> K,
+) -> BTreeMap<K, (usize, LossBreakdown)> {
+    let mut groups = BTreeMap::<_, Vec<_>>::new();
+    for record in records {
+        let loss = LossBreakdown::new(record.judge_result(), record.achievement_result().value());
+        groups.entry(key(record)).or_default().push(loss);
+    }
+    groups
+        .into_iter()
+        .map(|(key, losses)| {
+            let average = LossBreakdown::average(&losses).unwrap();
+            (key, (losses.len(), average))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::maimai::schema::latest::{
+        AchievementValue, JudgeCount, JudgeCountWithCP, JudgeCountWithoutCP, JudgeResult,
+    };
+
+    use super::{JudgeGrade, LossBreakdown, NoteType};
+
+    fn count(cp: u32, p: u32, great: u32, good: u32, miss: u32) -> JudgeCountWithCP {
+        JudgeCountWithCP::builder()
+            .critical_perfect(cp)
+            .others(
+                JudgeCountWithoutCP::builder()
+                    .perfect(p)
+                    .great(great)
+                    .good(good)
+                    .miss(miss)
+                    .build(),
+            )
+            .build()
+    }
+
+    #[test]
+    fn test_loss_breakdown() {
+        // 100 taps and 10 breaks: total weight = 150
+        let judge = JudgeResult::builder()
+            .fast(0)
+            .late(0)
+            .tap(JudgeCount::JudgeCountWithCP(count(90, 7, 3, 0, 0)))
+            .hold(JudgeCount::Nothing)
+            .slide(JudgeCount::Nothing)
+            .touch(JudgeCount::Nothing)
+            .break_(count(9, 1, 0, 0, 0))
+            .build();
+        // tap greats: 3 * 0.2 / 150 * 100 = 0.4%, break perfect: 0.25 / 10 = 0.025%
+        let achievement = AchievementValue::try_from(100_5750).unwrap();
+        let loss = LossBreakdown::new(judge, achievement);
+        assert!((loss.by_judge[NoteType::Tap][JudgeGrade::Great] - 0.4).abs() < 1e-9);
+        assert!((loss.by_judge[NoteType::Break][JudgeGrade::Perfect] - 0.025).abs() < 1e-9);
+        assert!(loss.unexplained < 1e-9);
+        assert_eq!(loss.costliest_note_type().unwrap().0, NoteType::Tap);
+        assert!((loss.total() - 0.425).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_deduction() {
+        let base = |note_type| JudgeGrade::all().map(|g| g.base_deduction(note_type));
+        assert_eq!(base(NoteType::Hold), [0, 0, 2, 5, 10]);
+        assert_eq!(base(NoteType::Break), [0, 0, 2, 6, 10]);
+        let bonus = JudgeGrade::all().map(JudgeGrade::bonus_deduction);
+        assert_eq!(bonus, [0, 5, 12, 14, 20]);
+    }
+}
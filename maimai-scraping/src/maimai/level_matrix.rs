@@ -0,0 +1,206 @@
+//! Matrix of pairs of internal levels appearing in the same set of scores.
+//!
+//! Rows and columns are indexed by internal levels from 15.0 down to 1.0.
+//! A set of scores (e.g. the deluxe scores of a song) is put in the cell `(y, x)`
+//! if it has two scores of levels `y` and `x`, where `y` is not higher than `x`.
+
+use std::{fmt::Write as _, io::Write};
+
+use itertools::Itertools;
+use log::warn;
+
+use super::{
+    rating::ScoreConstant,
+    schema::latest::ScoreGeneration,
+    song_list::database::{OrdinaryScoresRef, SongDatabase},
+    version::MaimaiVersion,
+};
+
+const MIN: u8 = 10;
+const MAX: u8 = 150;
+pub const SIZE: usize = (MAX - MIN + 1) as usize;
+
+pub struct LevelMatrix<'s> {
+    version: MaimaiVersion,
+    /// Each cell is sorted by the pronunciation of the song.
+    cells: Vec<Vec<Vec<OrdinaryScoresRef<'s>>>>,
+}
+
+impl<'s> LevelMatrix<'s> {
+    /// Scores whose internal level is not determined are counted in their lowest candidate.
+    pub fn new(database: &SongDatabase<'s>, version: MaimaiVersion) -> Self {
+        let mut cells = vec![vec![vec![]; SIZE]; SIZE];
+        for song in database.songs() {
+            for scores in song.scoreses() {
+                let levels = scores
+                    .all_scores()
+                    .filter_map(|x| {
+                        let score = x.for_version(version)?;
+                        let Some(level) = score.level() else {
+                            warn!("Level not found for {x}");
+                            return None;
+                        };
+                        let level = level.get_if_unique().or_else(|| {
+                            let lowest = level.candidates().next();
+                            match lowest {
+                                Some(_) => warn!(
+                                    "Internal level unknown for {x} (falling back to lowest possible)"
+                                ),
+                                None => warn!("No internal level candidate for {x} (!!!)"),
+                            }
+                            lowest
+                        })?;
+                        Some((x.difficulty(), index(level)))
+                    })
+                    .collect_vec();
+                for &(a, x) in &levels {
+                    for &(b, y) in &levels {
+                        if (x, a) < (y, b) {
+                            cells[y][x].push(scores);
+                        }
+                    }
+                }
+            }
+        }
+        for cell in cells.iter_mut().flatten() {
+            cell.sort_by_key(|x| x.song().song().latest_pronunciation());
+        }
+        Self { version, cells }
+    }
+
+    pub fn version(&self) -> MaimaiVersion {
+        self.version
+    }
+
+    /// The internal level of the `i`-th row or column.
+    pub fn level(i: usize) -> ScoreConstant {
+        ScoreConstant::try_from(MAX - i as u8).unwrap()
+    }
+
+    pub fn cell(&self, y: usize, x: usize) -> &[OrdinaryScoresRef<'s>] {
+        &self.cells[y][x]
+    }
+
+    /// The names of the songs in each cell, separated by newlines.
+    /// The generation is appended to the songs having both standard and deluxe scores.
+    pub fn labels(&self) -> Vec<Vec<String>> {
+        self.cells
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| cell.iter().map(|&x| label(x)).join("\n"))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Writes the labels with a header row and a header column of levels.
+    pub fn write_csv(&self, writer: impl Write) -> anyhow::Result<()> {
+        let mut writer = csv::Writer::from_writer(writer);
+        writer.write_record(std::iter::once(String::new()).chain(headers()))?;
+        for (level, row) in headers().zip(self.labels()) {
+            writer.write_record(std::iter::once(level).chain(row))?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// A standalone HTML page showing the number of score sets in each cell as a heatmap.
+    /// The song names are shown as tooltips.
+    pub fn to_html(&self) -> String {
+        let max = self.cells.iter().flatten().map(|x| x.len()).max();
+        let max = max.unwrap_or(0).max(1) as f64;
+        let mut ret = format!(
+            r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Score level matrix ({:?})</title>
+<style>
+table {{ border-collapse: collapse; font-size: 10px; }}
+td, th {{ border: 1px solid #ddd; min-width: 16px; height: 16px; text-align: center; }}
+</style>
+</head>
+<body>
+<table>
+<tr><th></th>"#,
+            self.version
+        );
+        for level in headers() {
+            let _ = write!(ret, "<th>{level}</th>");
+        }
+        ret += "</tr>\n";
+        for ((i, row), level) in self.cells.iter().enumerate().zip(headers()) {
+            let _ = write!(ret, "<tr><th>{level}</th>");
+            for (j, cell) in row.iter().enumerate() {
+                if j > i || cell.is_empty() {
+                    ret += "<td></td>";
+                    continue;
+                }
+                // The lightness goes from 90% down to 35% as the count increases
+                let ratio = (cell.len() as f64).ln_1p() / max.ln_1p();
+                let lightness = 90. - 55. * ratio;
+                let title = cell.iter().map(|&x| escape_html(&label(x))).join("&#10;");
+                let _ = write!(
+                    ret,
+                    r#"<td style="background: hsl(10, 80%, {lightness:.0}%)" title="{title}">{}</td>"#,
+                    cell.len()
+                );
+            }
+            ret += "</tr>\n";
+        }
+        ret += "</table>\n</body>\n</html>\n";
+        ret
+    }
+}
+
+fn index(level: ScoreConstant) -> usize {
+    (MAX - u8::from(level)) as usize
+}
+
+fn headers() -> impl Iterator<Item = String> {
+    (0..SIZE).map(|i| LevelMatrix::level(i).to_string())
+}
+
+fn label(scores: OrdinaryScoresRef) -> String {
+    let name = scores.song().latest_song_name();
+    let has_alternative = scores.song().scoreses().count() >= 2;
+    match (has_alternative, scores.generation()) {
+        (false, _) => name.to_string(),
+        (true, ScoreGeneration::Deluxe) => format!("{name} (DX)"),
+        (true, ScoreGeneration::Standard) => format!("{name} (Std)"),
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => ret += "&amp;",
+            '<' => ret += "&lt;",
+            '>' => ret += "&gt;",
+            '"' => ret += "&quot;",
+            '\'' => ret += "&#39;",
+            c => ret.push(c),
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::maimai::rating::ScoreConstant;
+
+    use super::{escape_html, index, LevelMatrix, SIZE};
+
+    #[test]
+    fn test_index() {
+        let constant = |x: u8| ScoreConstant::try_from(x).unwrap();
+        assert_eq!(index(constant(150)), 0);
+        assert_eq!(index(constant(10)), SIZE - 1);
+        for i in 0..SIZE {
+            assert_eq!(index(LevelMatrix::level(i)), i);
+        }
+        assert_eq!(escape_html(r#"<a & "b">"#), "&lt;a &amp; &quot;b&quot;&gt;");
+    }
+}
@@ -0,0 +1,281 @@
+//! Changes of internal levels between two versions.
+
+use std::{collections::BTreeMap, fmt::Write as _, io::Write};
+
+use serde::Serialize;
+
+use super::{
+    internal_lv_estimator::Estimator,
+    rating::{InternalScoreLevel, ScoreLevel},
+    song_list::database::{OrdinaryScoreRef, SongDatabase},
+    version::MaimaiVersion,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, strum::Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum ChangeKind {
+    Up,
+    Down,
+    Unchanged,
+    /// The candidates overlap, so the direction cannot be told.
+    Unknown,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct LvChange<'s> {
+    pub score: OrdinaryScoreRef<'s>,
+    pub from: InternalScoreLevel,
+    pub to: InternalScoreLevel,
+    pub kind: ChangeKind,
+}
+
+/// A row of the report, in the form written to CSV or JSON.
+#[derive(Clone, Debug, Serialize)]
+pub struct LvChangeRow {
+    pub song: String,
+    pub generation: &'static str,
+    pub difficulty: &'static str,
+    pub from: String,
+    pub to: String,
+    pub kind: ChangeKind,
+}
+
+/// The maximum number of scores listed in `LvChangeReport::summary`.
+pub const SUMMARY_MAX_CHANGES: usize = 20;
+
+#[derive(Clone, Debug)]
+pub struct LvChangeReport<'s> {
+    pub from: MaimaiVersion,
+    pub to: MaimaiVersion,
+    /// Sorted by kind, and then by score.
+    pub changes: Vec<LvChange<'s>>,
+}
+
+impl ChangeKind {
+    pub fn new(from: InternalScoreLevel, to: InternalScoreLevel) -> Self {
+        let (Some(from_min), Some(from_max), Some(to_min), Some(to_max)) = (
+            from.candidates().min(),
+            from.candidates().max(),
+            to.candidates().min(),
+            to.candidates().max(),
+        ) else {
+            return Self::Unknown;
+        };
+        if from_max < to_min {
+            Self::Up
+        } else if to_max < from_min {
+            Self::Down
+        } else if from.is_unique() && to.is_unique() {
+            Self::Unchanged
+        } else {
+            Self::Unknown
+        }
+    }
+}
+
+impl<'s> LvChangeReport<'s> {
+    /// Compares the levels of every score existing in both `from` and `to`.
+    ///
+    /// The levels are taken from the database, except for the version of `estimator`
+    /// (if given), for which its candidates are used instead.
+    pub fn new<LD, LL>(
+        database: &SongDatabase<'s>,
+        estimator: Option<&Estimator<'s, LD, LL>>,
+        from: MaimaiVersion,
+        to: MaimaiVersion,
+    ) -> Self {
+        let level = |score: OrdinaryScoreRef<'s>, version| match estimator {
+            Some(estimator) if estimator.version() == version => {
+                estimator.get(score).map(|x| x.candidates())
+            }
+            _ => score.for_version(version)?.level(),
+        };
+        let mut changes: Vec<_> = database
+            .all_scores_for_version(to)
+            .filter_map(|score| {
+                let score = score.score();
+                let (from, to) = (level(score, from)?, level(score, to)?);
+                Some(LvChange {
+                    score,
+                    from,
+                    to,
+                    kind: ChangeKind::new(from, to),
+                })
+            })
+            .collect();
+        changes.sort_by_key(|x| (x.kind, x.score));
+        Self { from, to, changes }
+    }
+
+    /// Retains only the changes from or to `level`.
+    pub fn retain_level(&mut self, level: ScoreLevel) {
+        let (from, to) = (self.from, self.to);
+        self.changes
+            .retain(|x| x.from.into_level(from) == level || x.to.into_level(to) == level);
+    }
+
+    pub fn counts(&self) -> BTreeMap<ChangeKind, usize> {
+        let mut ret = BTreeMap::new();
+        for change in &self.changes {
+            *ret.entry(change.kind).or_default() += 1;
+        }
+        ret
+    }
+
+    pub fn rows(&self) -> Vec<LvChangeRow> {
+        self.changes
+            .iter()
+            .map(|x| LvChangeRow {
+                song: x.score.scores().song().latest_song_name().to_string(),
+                generation: x.score.scores().generation().abbrev(),
+                difficulty: x.score.difficulty().abbrev(),
+                from: x.from.to_string(),
+                to: x.to.to_string(),
+                kind: x.kind,
+            })
+            .collect()
+    }
+
+    pub fn write_csv(&self, writer: impl Write) -> anyhow::Result<()> {
+        let mut writer = csv::Writer::from_writer(writer);
+        for row in self.rows() {
+            writer.serialize(row)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Formats the changes other than `Unchanged` as markdown tables, one per kind.
+    pub fn to_markdown(&self) -> String {
+        let mut ret = format!(
+            "# Internal level changes: {:?} → {:?}\n",
+            self.from, self.to
+        );
+        for (kind, count) in self.counts() {
+            if kind == ChangeKind::Unchanged {
+                continue;
+            }
+            let _ = write!(
+                ret,
+                "\n## {kind} ({count})\n\n| Score | {:?} | {:?} |\n|---|---|---|\n",
+                self.from, self.to
+            );
+            for x in self.changes.iter().filter(|x| x.kind == kind) {
+                let _ = writeln!(ret, "| {} | {} | {} |", x.score, x.from, x.to);
+            }
+        }
+        ret
+    }
+
+    /// A short text for notifications: the counts, and the scores whose level moved.
+    /// At most `SUMMARY_MAX_CHANGES` scores are listed, followed by the number of the rest.
+    pub fn summary(&self) -> String {
+        let counts = self.counts();
+        let count = |kind| counts.get(&kind).copied().unwrap_or(0);
+        let mut ret = format!(
+            "Internal levels {:?} → {:?}: {} up, {} down, {} unchanged, {} unknown",
+            self.from,
+            self.to,
+            count(ChangeKind::Up),
+            count(ChangeKind::Down),
+            count(ChangeKind::Unchanged),
+            count(ChangeKind::Unknown),
+        );
+        let moved = self.changes.iter().filter_map(|x| match x.kind {
+            ChangeKind::Up => Some(("↑", x)),
+            ChangeKind::Down => Some(("↓", x)),
+            _ => None,
+        });
+        let mut rest = 0;
+        for (i, (mark, x)) in moved.enumerate() {
+            if i < SUMMARY_MAX_CHANGES {
+                let _ = write!(ret, "\n{mark} {}: {} → {}", x.score, x.from, x.to);
+            } else {
+                rest += 1;
+            }
+        }
+        if rest > 0 {
+            let _ = write!(ret, "\n... and {rest} more");
+        }
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::maimai::{
+        rating::{InternalScoreLevel, ScoreConstant, ScoreLevel},
+        schema::latest::{ScoreDifficulty, ScoreGeneration},
+        song_list::{database::SongDatabase, OrdinaryScores, Song},
+        version::MaimaiVersion,
+    };
+
+    use super::{ChangeKind, LvChange, LvChangeReport, SUMMARY_MAX_CHANGES};
+
+    #[test]
+    fn test_change_kind() {
+        let known = |x: u8| InternalScoreLevel::known(ScoreConstant::try_from(x).unwrap());
+        let range = |x: u8, y: u8| {
+            let level = ScoreLevel::new(13, false).unwrap();
+            let mut ret = InternalScoreLevel::unknown(MaimaiVersion::CirclePlus, level);
+            ret.retain(|z| (x..=y).contains(&u8::from(z)));
+            ret
+        };
+        assert_eq!(ChangeKind::new(known(130), known(132)), ChangeKind::Up);
+        assert_eq!(ChangeKind::new(known(132), known(130)), ChangeKind::Down);
+        assert_eq!(
+            ChangeKind::new(known(131), known(131)),
+            ChangeKind::Unchanged
+        );
+        assert_eq!(ChangeKind::new(known(130), range(131, 133)), ChangeKind::Up);
+        assert_eq!(
+            ChangeKind::new(known(131), range(130, 132)),
+            ChangeKind::Unknown
+        );
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut song = Song {
+            icon: Some(
+                "https://maimaidx.jp/maimai-mobile/img/Music/a.png"
+                    .parse()
+                    .unwrap(),
+            ),
+            ..Default::default()
+        };
+        song.name[MaimaiVersion::Prism] = Some("a".to_owned().into());
+        song.scores[ScoreGeneration::Deluxe] = Some(OrdinaryScores {
+            version: Some(MaimaiVersion::Prism),
+            ..Default::default()
+        });
+        let songs = [song];
+        let database = SongDatabase::new(&songs).unwrap();
+        let score = database.songs()[0]
+            .scores(ScoreGeneration::Deluxe)
+            .unwrap()
+            .score(ScoreDifficulty::Master)
+            .unwrap();
+        let known = |x: u8| InternalScoreLevel::known(ScoreConstant::try_from(x).unwrap());
+        let change = |kind| LvChange {
+            score,
+            from: known(130),
+            to: known(131),
+            kind,
+        };
+        let report = |changes| LvChangeReport {
+            from: MaimaiVersion::Prism,
+            to: MaimaiVersion::PrismPlus,
+            changes,
+        };
+
+        let summary = report(vec![change(ChangeKind::Up), change(ChangeKind::Unchanged)]).summary();
+        assert_eq!(summary.lines().count(), 2);
+
+        let changes = vec![change(ChangeKind::Up); SUMMARY_MAX_CHANGES + 5];
+        let summary = report(changes).summary();
+        assert_eq!(summary.lines().count(), SUMMARY_MAX_CHANGES + 2);
+        assert_eq!(summary.lines().last(), Some("... and 5 more"));
+    }
+}
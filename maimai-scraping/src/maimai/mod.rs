@@ -1,11 +1,20 @@
+pub mod achievement;
 pub mod associated_user_data;
 pub mod data_collector;
+pub mod deluxscore;
 pub mod favorite_songs;
 pub mod internal_lv_estimator;
+pub mod judge_analysis;
+pub mod level_matrix;
+pub mod lv_changes;
 pub mod parser;
 pub mod rating;
+pub mod record_verifier;
 pub mod schema;
+pub mod sessions;
 pub mod song_list;
+pub mod song_query;
+pub mod stats;
 pub mod version;
 
 use hashbrown::HashMap;
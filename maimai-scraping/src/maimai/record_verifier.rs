@@ -0,0 +1,292 @@
+//! Recomputes the achievement and the DX score of a play record
+//! from its judge counts, to find bugs in the parser or in the data.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::Serialize;
+
+use super::{
+    achievement::{mistakes_from_judge_result, Mistake, NoteProfile},
+    judge_analysis::{judge_table, JudgeGrade},
+    schema::latest::{AchievementValue, JudgeCount, JudgeResult, PlayRecord},
+};
+
+/// A mismatch between a play record and the value computed from its judges.
+#[derive(Clone, Debug, Serialize)]
+pub enum Discrepancy {
+    /// The note counts in the record differ from those of the score.
+    NoteCount {
+        expected: NoteProfile,
+        found: NoteProfile,
+    },
+    /// The recorded achievement cannot be obtained with the judges.
+    Achievement {
+        recorded: AchievementValue,
+        candidates: BTreeSet<AchievementValue>,
+    },
+    DeluxscoreValue {
+        recorded: u32,
+        computed: u32,
+    },
+    DeluxscoreMax {
+        recorded: u32,
+        computed: u32,
+    },
+}
+impl std::fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Discrepancy::NoteCount { expected, found } => {
+                write!(
+                    f,
+                    "Note count mismatch: expected {expected:?}, found {found:?}"
+                )
+            }
+            Discrepancy::Achievement {
+                recorded,
+                candidates,
+            } => {
+                write!(f, "Achievement {recorded} is not in [")?;
+                for (i, x) in candidates.iter().enumerate() {
+                    let sep = if i == 0 { "" } else { ", " };
+                    write!(f, "{sep}{x}")?;
+                }
+                write!(f, "]")
+            }
+            Discrepancy::DeluxscoreValue { recorded, computed } => {
+                write!(f, "DX score is {recorded}, but computed {computed}")
+            }
+            Discrepancy::DeluxscoreMax { recorded, computed } => {
+                write!(f, "Max DX score is {recorded}, but computed {computed}")
+            }
+        }
+    }
+}
+
+/// All the achievements that can be obtained with the judges.
+///
+/// The set has more than one element when there are break perfects or greats,
+/// as their timing is not recorded.
+pub fn achievement_candidates(judge: JudgeResult) -> BTreeSet<AchievementValue> {
+    let profile = NoteProfile::from_judge_result(judge);
+    let base = mistakes_from_judge_result(judge);
+    let perfect = base[Mistake::BreakPerfectClose];
+    let great = base[Mistake::BreakGreatClose];
+    let mut ret = BTreeSet::new();
+    for perfect_far in 0..=perfect {
+        for great_mid in 0..=great {
+            for great_far in 0..=great - great_mid {
+                let mut mistakes = base;
+                mistakes[Mistake::BreakPerfectClose] = perfect - perfect_far;
+                mistakes[Mistake::BreakPerfectFar] = perfect_far;
+                mistakes[Mistake::BreakGreatClose] = great - great_mid - great_far;
+                mistakes[Mistake::BreakGreatMid] = great_mid;
+                mistakes[Mistake::BreakGreatFar] = great_far;
+                ret.extend(profile.achievement(&mistakes));
+            }
+        }
+    }
+    ret
+}
+
+/// Computes the DX score and its maximum.
+/// Returns `None` if critical perfects are not recorded for some note type.
+pub fn deluxscore(judge: JudgeResult) -> Option<(u32, u32)> {
+    let with_cp = [judge.tap(), judge.hold(), judge.slide(), judge.touch()]
+        .iter()
+        .all(|x| !matches!(x, JudgeCount::JudgeCountWithoutCP(_)));
+    if !with_cp {
+        return None;
+    }
+    let table = judge_table(judge);
+    let (mut value, mut max) = (0, 0);
+    for counts in table.values() {
+        for (grade, &count) in counts {
+            value += count
+                * match grade {
+                    JudgeGrade::CriticalPerfect => 3,
+                    JudgeGrade::Perfect => 2,
+                    JudgeGrade::Great => 1,
+                    JudgeGrade::Good | JudgeGrade::Miss => 0,
+                };
+            max += count * 3;
+        }
+    }
+    Some((value, max))
+}
+
+/// Verifies a play record.
+/// If `notes` is given, the note counts in the record are also compared against it.
+pub fn verify_record(record: &PlayRecord, notes: Option<NoteProfile>) -> Vec<Discrepancy> {
+    let judge = record.judge_result();
+    let mut ret = vec![];
+
+    let found = NoteProfile::from_judge_result(judge);
+    if let Some(expected) = notes {
+        if expected != found {
+            ret.push(Discrepancy::NoteCount { expected, found });
+        }
+    }
+
+    let recorded = record.achievement_result().value();
+    let candidates = achievement_candidates(judge);
+    if !candidates.contains(&recorded) {
+        ret.push(Discrepancy::Achievement {
+            recorded,
+            candidates,
+        });
+    }
+
+    if let Some((value, max)) = deluxscore(judge) {
+        let score = record.deluxscore_result().score();
+        if score.value() != value {
+            ret.push(Discrepancy::DeluxscoreValue {
+                recorded: score.value(),
+                computed: value,
+            });
+        }
+        if score.max() != max {
+            ret.push(Discrepancy::DeluxscoreMax {
+                recorded: score.max(),
+                computed: max,
+            });
+        }
+    }
+
+    ret
+}
+
+/// Infers the note counts of a score from its play records.
+///
+/// Returns the candidates in descending order of the number of supporting records.
+/// Candidates supported by the same number of records are sorted by their note counts
+/// (compared in the order of tap, hold, slide, touch and break),
+/// so that the result does not depend on the order of the records.
+/// Ideally there is only one candidate; otherwise some records are broken.
+pub fn infer_note_profile<'d>(
+    records: impl IntoIterator<Item = &'d PlayRecord>,
+) -> Vec<(NoteProfile, usize)> {
+    let mut counts = BTreeMap::<_, usize>::new();
+    for record in records {
+        let profile = NoteProfile::from_judge_result(record.judge_result());
+        if profile.total() > 0 {
+            *counts.entry(profile).or_default() += 1;
+        }
+    }
+    let mut ret: Vec<_> = counts.into_iter().collect();
+    ret.sort_by(|x, y| y.1.cmp(&x.1).then(x.0.cmp(&y.0)));
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use crate::maimai::schema::latest::{
+        AchievementValue, JudgeCount, JudgeCountWithCP, JudgeCountWithoutCP, JudgeResult,
+        PlayRecord, SampleRecord,
+    };
+
+    use super::{achievement_candidates, deluxscore, infer_note_profile};
+
+    fn with_cp(critical_perfect: u32, perfect: u32, great: u32) -> JudgeCountWithCP {
+        JudgeCountWithCP::builder()
+            .critical_perfect(critical_perfect)
+            .others(
+                JudgeCountWithoutCP::builder()
+                    .perfect(perfect)
+                    .great(great)
+                    .good(0)
+                    .miss(0)
+                    .build(),
+            )
+            .build()
+    }
+
+    /// A result of a score consisting only of taps and breaks.
+    fn judge(tap: JudgeCount, break_: JudgeCountWithCP) -> JudgeResult {
+        JudgeResult::builder()
+            .fast(0)
+            .late(0)
+            .tap(tap)
+            .hold(JudgeCount::Nothing)
+            .slide(JudgeCount::Nothing)
+            .touch(JudgeCount::Nothing)
+            .break_(break_)
+            .build()
+    }
+
+    fn achievements(values: &[u32]) -> BTreeSet<AchievementValue> {
+        (values.iter())
+            .map(|&x| AchievementValue::try_from(x).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_achievement_candidates() {
+        // Without breaks, the break bonus is given in full
+        let taps = judge(
+            JudgeCount::JudgeCountWithCP(with_cp(99, 0, 1)),
+            with_cp(0, 0, 0),
+        );
+        assert_eq!(achievement_candidates(taps), achievements(&[100_8000]));
+        // A break perfect loses a quarter or a half of the bonus of a break
+        let breaks = judge(
+            JudgeCount::JudgeCountWithCP(with_cp(95, 0, 0)),
+            with_cp(4, 1, 0),
+        );
+        assert_eq!(
+            achievement_candidates(breaks),
+            achievements(&[100_9000, 100_9500])
+        );
+    }
+
+    #[test]
+    fn test_deluxscore() {
+        let breaks = judge(
+            JudgeCount::JudgeCountWithCP(with_cp(95, 0, 0)),
+            with_cp(4, 1, 0),
+        );
+        assert_eq!(deluxscore(breaks), Some((299, 300)));
+        let without_cp = JudgeCountWithoutCP::builder()
+            .perfect(95)
+            .great(0)
+            .good(0)
+            .miss(0)
+            .build();
+        let old = judge(
+            JudgeCount::JudgeCountWithoutCP(without_cp),
+            with_cp(4, 1, 0),
+        );
+        assert_eq!(deluxscore(old), None);
+    }
+
+    #[test]
+    fn test_infer_note_profile() {
+        let record = |time, taps, breaks| -> PlayRecord {
+            let judge = judge(
+                JudgeCount::JudgeCountWithCP(with_cp(taps, 0, 0)),
+                with_cp(breaks, 0, 0),
+            );
+            SampleRecord::builder().time(time).judge(judge).build()
+        };
+        let records = [
+            record("2024-01-01T10:00:00", 100, 5),
+            record("2024-01-01T10:05:00", 101, 4),
+            record("2024-01-01T10:10:00", 100, 5),
+            record("2024-01-01T10:15:00", 99, 6),
+            record("2024-01-01T10:20:00", 0, 0),
+        ];
+        let summary = |records: &[PlayRecord]| {
+            infer_note_profile(records)
+                .into_iter()
+                .map(|(profile, count)| (profile.tap(), profile.break_(), count))
+                .collect::<Vec<_>>()
+        };
+        let expected = [(100, 5, 2), (99, 6, 1), (101, 4, 1)];
+        assert_eq!(summary(&records), expected);
+        let mut reversed = records;
+        reversed.reverse();
+        assert_eq!(summary(&reversed), expected);
+    }
+}
@@ -158,7 +158,7 @@ impl PlayTime {
     }
 }
 
-#[derive(Clone, PartialEq, Eq, Debug, From, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Eq, Debug, From, Display, Serialize, Deserialize)]
 pub struct PlaceName(String);
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Into, Serialize, Deserialize)]
@@ -378,6 +378,12 @@ pub struct DeluxscoreResult {
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub struct DeluxscoreRank(u8);
 
+impl DeluxscoreRank {
+    pub fn get(self) -> u8 {
+        self.0
+    }
+}
+
 impl TryFrom<u8> for DeluxscoreRank {
     type Error = u8;
     fn try_from(value: u8) -> Result<Self, Self::Error> {
@@ -695,6 +701,123 @@ pub enum Category {
 #[as_ref(forward)]
 pub struct ArtistName(String);
 
+/// A play record with only the given properties meaningful, for tests.
+#[cfg(test)]
+#[derive(TypedBuilder)]
+#[builder(build_method(into = PlayRecord))]
+pub(crate) struct SampleRecord<'a> {
+    /// In the form of `2024-01-02T03:04:00`.
+    time: &'a str,
+    #[builder(default = 1)]
+    track: u8,
+    #[builder(default = "place")]
+    place: &'a str,
+    #[builder(default = "song")]
+    name: &'a str,
+    /// The file name of the cover art, which defaults to `name`.
+    #[builder(default = name)]
+    icon: &'a str,
+    #[builder(default = ScoreDifficulty::Master)]
+    difficulty: ScoreDifficulty,
+    #[builder(default = 100_0000)]
+    achievement: u32,
+    #[builder(default = FullComboKind::Nothing)]
+    full_combo: FullComboKind,
+    /// Defaults to a result without any notes.
+    #[builder(default, setter(strip_option))]
+    judge: Option<JudgeResult>,
+}
+#[cfg(test)]
+impl From<SampleRecord<'_>> for PlayRecord {
+    fn from(x: SampleRecord) -> Self {
+        let zero = ValueWithMax::new(0, 0).unwrap();
+        let icon = |dir, name| format!("https://maimaidx.jp/maimai-mobile/img/{dir}/{name}.png");
+        let judge = JudgeCountWithoutCP::builder()
+            .perfect(0)
+            .great(0)
+            .good(0)
+            .miss(0)
+            .build();
+        PlayRecord::builder()
+            .played_at(
+                PlayedAt::builder()
+                    .idx(Idx::default())
+                    .time(x.time.parse().unwrap())
+                    .place(Some(x.place.to_owned().into()))
+                    .track(TrackIndex::try_from(x.track).unwrap())
+                    .build(),
+            )
+            .song_metadata(
+                SongMetadata::builder()
+                    .name(x.name.to_owned().into())
+                    .cover_art(icon("Music", x.icon).parse().unwrap())
+                    .build(),
+            )
+            .score_metadata(
+                ScoreMetadata::builder()
+                    .generation(ScoreGeneration::Deluxe)
+                    .difficulty(x.difficulty)
+                    .build(),
+            )
+            .score_level(None)
+            .utage_metadata(None)
+            .cleared(true)
+            .achievement_result(
+                AchievementResult::builder()
+                    .value(AchievementValue::try_from(x.achievement).unwrap())
+                    .new_record(false)
+                    .rank(AchievementRank::D)
+                    .build(),
+            )
+            .deluxscore_result(
+                DeluxscoreResult::builder()
+                    .score(zero)
+                    .rank(DeluxscoreRank(0))
+                    .new_record(false)
+                    .build(),
+            )
+            .combo_result(
+                ComboResult::builder()
+                    .full_combo_kind(x.full_combo)
+                    .combo(zero)
+                    .build(),
+            )
+            .battle_result(None)
+            .matching_result(None)
+            .life_result(LifeResult::Nothing)
+            .tour_members(TourMemberList(vec![TourMember::builder()
+                .star(1)
+                .icon(icon("Chara", "chara").parse().unwrap())
+                .level(1)
+                .build()]))
+            .rating_result(
+                RatingResult::builder()
+                    .rating(RatingValue::from(10000))
+                    .delta(0)
+                    .delta_sign(RatingDeltaSign::Keep)
+                    .border_color(RatingBorderColor::Normal)
+                    .build(),
+            )
+            .judge_result(x.judge.unwrap_or_else(|| {
+                JudgeResult::builder()
+                    .fast(0)
+                    .late(0)
+                    .tap(JudgeCount::Nothing)
+                    .hold(JudgeCount::Nothing)
+                    .slide(JudgeCount::Nothing)
+                    .touch(JudgeCount::Nothing)
+                    .break_(
+                        JudgeCountWithCP::builder()
+                            .critical_perfect(0)
+                            .others(judge)
+                            .build(),
+                    )
+                    .build()
+            }))
+            .build()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use chrono::NaiveDate;
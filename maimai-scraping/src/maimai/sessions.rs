@@ -0,0 +1,290 @@
+//! Splits play records into credits and sessions.
+//!
+//! A *credit* is a sequence of tracks played on a single coin,
+//! detected by `TrackIndex` resetting to 1.
+//! A *session* is a sequence of credits played at the same place
+//! without a long break in between.
+
+use std::collections::BTreeSet;
+
+use chrono::{Duration, NaiveDate};
+use getset::{CopyGetters, Getters};
+
+use super::schema::{
+    latest::{PlaceName, PlayRecord, PlayTime, UserName},
+    ver_20210316_2338::RatingValue,
+};
+
+#[derive(Clone, Copy, Debug, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct SessionConfig {
+    /// Credits separated by more than this are never in the same credit.
+    /// Guards against a missing first track.
+    max_gap_in_credit: Duration,
+    /// Credits separated by more than this are considered different sessions.
+    max_gap_in_session: Duration,
+}
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            max_gap_in_credit: Duration::minutes(20),
+            max_gap_in_session: Duration::minutes(60),
+        }
+    }
+}
+impl SessionConfig {
+    pub fn new(max_gap_in_credit: Duration, max_gap_in_session: Duration) -> Self {
+        Self {
+            max_gap_in_credit,
+            max_gap_in_session,
+        }
+    }
+}
+
+/// Tracks played in a single credit.  Never empty.
+#[derive(Debug, Getters)]
+#[getset(get = "pub")]
+pub struct Credit<'d> {
+    tracks: Vec<&'d PlayRecord>,
+}
+impl<'d> Credit<'d> {
+    fn first(&self) -> &'d PlayRecord {
+        self.tracks[0]
+    }
+    fn last(&self) -> &'d PlayRecord {
+        self.tracks[self.tracks.len() - 1]
+    }
+
+    pub fn start(&self) -> PlayTime {
+        self.first().played_at().time()
+    }
+    pub fn end(&self) -> PlayTime {
+        self.last().played_at().time()
+    }
+    /// Time between the first and the last track.
+    /// The length of the first track is not included, as it is unknown.
+    pub fn duration(&self) -> Duration {
+        self.end().get() - self.start().get()
+    }
+    pub fn place(&self) -> Option<&'d PlaceName> {
+        self.first().played_at().place().as_ref()
+    }
+    pub fn companions(&self) -> BTreeSet<&'d UserName> {
+        companions(self.tracks.iter().copied())
+    }
+    pub fn rating_before(&self) -> RatingValue {
+        rating_before(self.first())
+    }
+    pub fn rating_after(&self) -> RatingValue {
+        self.last().rating_result().rating()
+    }
+    pub fn rating_delta(&self) -> i16 {
+        self.rating_after().get() as i16 - self.rating_before().get() as i16
+    }
+}
+
+/// Credits played in a single visit to an arcade.  Never empty.
+#[derive(Debug, Getters)]
+#[getset(get = "pub")]
+pub struct Session<'d> {
+    credits: Vec<Credit<'d>>,
+}
+impl<'d> Session<'d> {
+    fn first(&self) -> &Credit<'d> {
+        &self.credits[0]
+    }
+    fn last(&self) -> &Credit<'d> {
+        &self.credits[self.credits.len() - 1]
+    }
+
+    pub fn tracks(&self) -> impl Iterator<Item = &'d PlayRecord> + '_ {
+        self.credits.iter().flat_map(|c| c.tracks.iter().copied())
+    }
+    pub fn track_count(&self) -> usize {
+        self.credits.iter().map(|c| c.tracks.len()).sum()
+    }
+
+    pub fn start(&self) -> PlayTime {
+        self.first().start()
+    }
+    pub fn end(&self) -> PlayTime {
+        self.last().end()
+    }
+    pub fn duration(&self) -> Duration {
+        self.end().get() - self.start().get()
+    }
+    pub fn natural_date(&self) -> NaiveDate {
+        self.start().to_natural_date()
+    }
+    pub fn place(&self) -> Option<&'d PlaceName> {
+        self.first().place()
+    }
+    pub fn companions(&self) -> BTreeSet<&'d UserName> {
+        companions(self.tracks())
+    }
+    pub fn rating_before(&self) -> RatingValue {
+        self.first().rating_before()
+    }
+    pub fn rating_after(&self) -> RatingValue {
+        self.last().rating_after()
+    }
+    pub fn rating_delta(&self) -> i16 {
+        self.rating_after().get() as i16 - self.rating_before().get() as i16
+    }
+}
+
+/// Splits records into credits.
+/// `records` must be sorted in chronological order, like `RecordMap::values`.
+pub fn credits<'d>(
+    records: impl IntoIterator<Item = &'d PlayRecord>,
+    config: SessionConfig,
+) -> Vec<Credit<'d>> {
+    let mut ret = Vec::<Credit>::new();
+    for record in records {
+        let continues = ret.last().is_some_and(|credit| {
+            u8::from(record.played_at().track()) != 1
+                && record.played_at().place() == credit.last().played_at().place()
+                && record.played_at().time().get() - credit.end().get() <= config.max_gap_in_credit
+        });
+        match ret.last_mut() {
+            Some(credit) if continues => credit.tracks.push(record),
+            _ => ret.push(Credit {
+                tracks: vec![record],
+            }),
+        }
+    }
+    ret
+}
+
+/// Groups credits into sessions.
+pub fn sessions<'d>(
+    records: impl IntoIterator<Item = &'d PlayRecord>,
+    config: SessionConfig,
+) -> Vec<Session<'d>> {
+    let mut ret = Vec::<Session>::new();
+    for credit in credits(records, config) {
+        let continues = ret.last().is_some_and(|session| {
+            credit.place() == session.place()
+                && credit.start().get() - session.end().get() <= config.max_gap_in_session
+        });
+        match ret.last_mut() {
+            Some(session) if continues => session.credits.push(credit),
+            _ => ret.push(Session {
+                credits: vec![credit],
+            }),
+        }
+    }
+    ret
+}
+
+fn companions<'d>(records: impl Iterator<Item = &'d PlayRecord>) -> BTreeSet<&'d UserName> {
+    records
+        .filter_map(|r| r.matching_result().as_ref())
+        .flat_map(|m| {
+            let players: &[_] = m.other_players().as_ref();
+            players.iter().map(|p| p.user_name())
+        })
+        .collect()
+}
+
+fn rating_before(record: &PlayRecord) -> RatingValue {
+    let result = record.rating_result();
+    RatingValue::from((result.rating().get() as i16 - result.delta()) as u16)
+}
+
+/// Returns the sessions whose natural date is `date`.
+pub fn sessions_on<'a, 'd>(
+    sessions: &'a [Session<'d>],
+    date: NaiveDate,
+) -> impl Iterator<Item = &'a Session<'d>> {
+    sessions.iter().filter(move |s| s.natural_date() == date)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::maimai::schema::latest::{PlayRecord, SampleRecord};
+
+    use super::{credits, sessions, sessions_on, SessionConfig};
+
+    fn record(time: &str, track: u8, place: &str) -> PlayRecord {
+        let time = format!("2024-01-01T{time}:00");
+        SampleRecord::builder()
+            .time(&time)
+            .track(track)
+            .place(place)
+            .build()
+    }
+
+    fn tracks(credit: &super::Credit) -> Vec<u8> {
+        (credit.tracks().iter())
+            .map(|r| u8::from(r.played_at().track()))
+            .collect()
+    }
+
+    #[test]
+    fn test_credits() {
+        let records = [
+            record("10:00", 1, "a"),
+            record("10:05", 2, "a"),
+            record("10:10", 3, "a"),
+            // The track number is reset
+            record("10:15", 1, "a"),
+            // Exactly at the gap threshold
+            record("10:35", 2, "a"),
+            // Beyond the gap threshold, as if the first track were missing
+            record("10:56", 3, "a"),
+            // At another place
+            record("10:57", 4, "b"),
+        ];
+        let credits = credits(&records, SessionConfig::default());
+        assert_eq!(
+            credits.iter().map(tracks).collect::<Vec<_>>(),
+            [vec![1, 2, 3], vec![1, 2], vec![3], vec![4]]
+        );
+    }
+
+    #[test]
+    fn test_sessions() {
+        let records = [
+            record("10:00", 1, "a"),
+            record("10:05", 2, "a"),
+            // Exactly at the gap threshold
+            record("11:05", 1, "a"),
+            // Beyond the gap threshold
+            record("12:06", 1, "a"),
+            record("12:10", 2, "a"),
+            // At another place
+            record("12:15", 1, "b"),
+        ];
+        let sessions = sessions(&records, SessionConfig::default());
+        assert_eq!(
+            (sessions.iter())
+                .map(|s| (s.credits().len(), s.track_count()))
+                .collect::<Vec<_>>(),
+            [(2, 3), (1, 2), (1, 1)]
+        );
+        assert_eq!(sessions[0].duration(), chrono::Duration::minutes(65));
+    }
+
+    #[test]
+    fn test_sessions_on() {
+        let records = [
+            "2024-01-01T10:00:00",
+            "2024-01-02T04:59:00",
+            "2024-01-02T06:00:00",
+        ]
+        .map(|time| SampleRecord::builder().time(time).build());
+        let sessions = sessions(&records, SessionConfig::default());
+        let starts = |date: &str| {
+            sessions_on(&sessions, date.parse().unwrap())
+                .map(|s| s.start().get().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            starts("2024-01-01"),
+            ["2024-01-01 10:00:00", "2024-01-02 04:59:00"]
+        );
+        assert_eq!(starts("2024-01-02"), ["2024-01-02 06:00:00"]);
+        assert!(starts("2024-01-03").is_empty());
+    }
+}
@@ -0,0 +1,186 @@
+//! Parser of the `in_lv_data` files, whose entries are HTML fragments.
+
+use std::str::FromStr;
+
+use anyhow::{bail, Context};
+use hashbrown::HashMap;
+use joinery::JoinableIterator;
+use lazy_format::lazy_format;
+use maimai_scraping_utils::regex;
+use serde::Deserialize;
+
+use crate::maimai::{
+    rating::ScoreLevel,
+    schema::latest::{ScoreDifficulty, ScoreGeneration},
+    song_list::SongAbbreviation,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct InLvData {
+    pub(super) unknown: HashMap<UnknownKey, Vec<UnknownValue>>,
+    pub(super) known: HashMap<KnownKey, Vec<Vec<KnownValue>>>,
+}
+
+#[derive(PartialEq, Eq, Hash, Debug, Deserialize)]
+pub(super) struct KnownKey(String);
+impl KnownKey {
+    pub(super) fn gen(level: u8) -> Self {
+        Self(format!("lv{level}_rslt"))
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Debug, Deserialize)]
+pub(super) struct KnownValue(String);
+impl KnownValue {
+    pub(super) fn parse(&self) -> anyhow::Result<Entry> {
+        let entry = parse_entry(&self.0)?;
+        if !entry.additional.is_empty() {
+            bail!("Unexpected additional data found in {self:?}");
+        }
+        Ok(entry.entry)
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Debug, Deserialize)]
+pub(super) struct UnknownKey(String);
+impl UnknownKey {
+    pub(super) fn gen(level: ScoreLevel) -> Self {
+        let pm = if level.plus { 'p' } else { 'm' };
+        Self(format!("lv{}{pm}", level.level))
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Debug, Deserialize)]
+pub(super) struct UnknownValue(String);
+impl UnknownValue {
+    pub(super) fn parse(&self) -> anyhow::Result<EntryWithAdditional> {
+        parse_entry(&self.0)
+    }
+}
+
+fn parse_difficulty(s: &str) -> anyhow::Result<ScoreDifficulty> {
+    use ScoreDifficulty::*;
+    Ok(match s {
+        "b" => Basic,
+        "a" => Advanced,
+        "e" => Expert,
+        "m" => Master,
+        "r" => ReMaster,
+        _ => bail!("Unexpected difficulty: {s:?}"),
+    })
+}
+fn difficulty_char(difficulty: ScoreDifficulty) -> char {
+    use ScoreDifficulty::*;
+    match difficulty {
+        Basic => 'b',
+        Advanced => 'a',
+        Expert => 'e',
+        Master => 'm',
+        ReMaster => 'r',
+        Utage => 'u',
+    }
+}
+
+#[derive(Debug)]
+pub(super) struct EntryWithAdditional {
+    pub(super) entry: Entry,
+    pub(super) additional: Vec<(ScoreDifficulty, ScoreLevel)>,
+}
+#[derive(Debug)]
+pub(super) struct Entry {
+    pub(super) difficulty: ScoreDifficulty,
+    #[allow(unused)]
+    new_song: bool,
+    pub(super) song_nickname: SongAbbreviation,
+    dx: bool,
+}
+fn parse_entry(s: &str) -> anyhow::Result<EntryWithAdditional> {
+    let pattern = regex!(
+        r#"(?x)
+            <span\ class='wk_(?<difficulty>[baemr]) (?<new_song2> _n)?'>
+                (?<new_song> <u>)?
+                    (?<song_name> .*?)
+                    (?<dx> \[dx\])?
+                (</u>)?
+            </span>
+            (
+                \( (?<additional> [^)]* ) \)
+            )?
+            "#
+    );
+    let captures = pattern
+        .captures(s)
+        .with_context(|| format!("Unexpected string: {s:?}"))?;
+    let difficulty = parse_difficulty(&captures["difficulty"])?;
+    let new_song = captures.name("new_song").is_some();
+    let new_song2 = captures.name("new_song2").is_some();
+    let song_nickname = captures["song_name"].to_owned().into();
+    let dx = captures.name("dx").is_some();
+    let additional = match captures.name("additional") {
+        None => vec![],
+        Some(got) => {
+            let pattern = regex!(
+                r#"(?x)
+                    <span\ class='wk_(?<difficulty>[baemr])'>
+                        (?<level> .*)
+                    </span>
+                    "#
+            );
+            let mut res = vec![];
+            for element in got.as_str().split(',') {
+                let captures = pattern
+                    .captures(element)
+                    .with_context(|| format!("Unexpected additional string: {element:?}"))?;
+                let difficulty = parse_difficulty(&captures["difficulty"])?;
+                let level = ScoreLevel::from_str(&captures["level"])?;
+                res.push((difficulty, level));
+            }
+            res
+        }
+    };
+
+    let reconstruct = {
+        let additional_is_empty = additional.is_empty();
+        let make_additional = || {
+            additional
+                .iter()
+                .map(|&(d, lv)| {
+                    lazy_format!("<span class='wk_{d}'>{lv}</span>", d = difficulty_char(d))
+                })
+                .join_with(',')
+        };
+        let additional = lazy_format!(
+            if additional_is_empty => ""
+            else => ("({})", make_additional())
+        );
+        format!(
+            "<span class='wk_{d}{n}'>{us}{song_nickname}{dx}{ut}</span>{additional}",
+            d = difficulty_char(difficulty),
+            n = if new_song2 { "_n" } else { "" },
+            us = if new_song { "<u>" } else { "" },
+            dx = if dx { "[dx]" } else { "" },
+            ut = if new_song { "</u>" } else { "" },
+        )
+    };
+    if s != reconstruct {
+        bail!("Input: {s:?}, reconstructed: {reconstruct:?}")
+    }
+    Ok(EntryWithAdditional {
+        entry: Entry {
+            difficulty,
+            new_song,
+            song_nickname,
+            dx,
+        },
+        additional,
+    })
+}
+impl Entry {
+    pub(super) fn generation(&self) -> ScoreGeneration {
+        if self.dx {
+            ScoreGeneration::Deluxe
+        } else {
+            ScoreGeneration::Standard
+        }
+    }
+}
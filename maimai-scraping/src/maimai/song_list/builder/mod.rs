@@ -0,0 +1,1931 @@
+//! Builds the song database from the various resources,
+//! such as the official song lists, `in_lv` files and the wiki.
+//!
+//! Each resource is merged into the songs collected so far.
+//! A resource contradicting the stored data is an error,
+//! while less serious findings are collected in `BuildReport`.
+
+use std::{collections::BTreeMap, fmt::Debug, iter::successors, path::PathBuf, str::FromStr};
+
+use anyhow::{anyhow, bail, Context};
+use chrono::{NaiveDate, NaiveDateTime};
+use derive_more::Display;
+use enum_iterator::Sequence;
+use enum_map::EnumMap;
+use hashbrown::{hash_map::Entry as HEntry, HashMap, HashSet};
+use itertools::{chain, EitherOrBoth, Itertools};
+use maimai_scraping_utils::{
+    fs_json_util::{read_json, read_toml},
+    regex,
+};
+use serde::Deserialize;
+
+use crate::maimai::{
+    achievement::ScoreNotes,
+    rating::{InternalScoreLevel, ScoreConstant, ScoreLevel},
+    schema::latest::{ScoreDifficulty, ScoreGeneration, SongIcon, SongName},
+    song_list::{
+        in_lv::{self, kind::Bitmask, Song as InLvSong, SongRaw},
+        official::{self, ScoreDetails},
+        official_snapshot,
+        song_score::SongScoreList,
+        OrdinaryScore, OrdinaryScores, RemoveState, Song, SongAbbreviation, UtageIdentifier,
+    },
+    version::MaimaiVersion,
+};
+
+pub use in_lv_data::InLvData;
+use in_lv_data::{KnownKey, UnknownKey};
+pub use removed_songs_wiki::RemovedSongsWiki;
+
+mod in_lv_data;
+mod removed_songs_wiki;
+
+/// Where `BuildInputs::load` reads the resources from.
+#[derive(Clone, Debug)]
+pub struct InputPaths {
+    pub in_lv_dir: PathBuf,
+    pub in_lv_bitmask_dir: PathBuf,
+    pub in_lv_data_dir: PathBuf,
+    /// Directory containing the supplemental files, such as `removed_songs.json`.
+    pub database_dir: PathBuf,
+    pub official_song_list_paths: Vec<PathBuf>,
+
+    pub in_lv_until: Option<MaimaiVersion>,
+    pub in_lv_bitmask_until: Option<MaimaiVersion>,
+    pub in_lv_data_until: Option<MaimaiVersion>,
+
+    pub song_score_list_config: Option<PathBuf>,
+    pub use_hint_in_song_score_list: bool,
+}
+
+pub type InLvSongMask = InLvSong<in_lv::kind::Bitmask>;
+pub type UtageIdentifierMergeMap =
+    HashMap<(SongIcon, UtageIdentifier<'static>), UtageIdentifier<'static>>;
+pub type InLvCorrectionMap<K = in_lv::kind::Levels> = HashMap<
+    SongIcon,
+    HashMap<
+        (MaimaiVersion, ScoreGeneration, ScoreDifficulty),
+        [<K as in_lv::kind::Kind>::Value; 2],
+    >,
+>;
+pub type InLvDataCorrectionMap = HashMap<
+    SongIcon,
+    HashMap<(MaimaiVersion, ScoreGeneration, ScoreDifficulty), [InternalScoreLevel; 2]>,
+>;
+/// Collects the resources for the song list.
+#[derive(Default)]
+pub struct BuildInputs {
+    pub in_lv: BTreeMap<MaimaiVersion, Vec<InLvSong>>,
+    pub in_lv_bitmask: BTreeMap<MaimaiVersion, Vec<InLvSongMask>>,
+    pub in_lv_data: BTreeMap<MaimaiVersion, InLvData>,
+
+    pub in_lv_supplemental: Vec<(MaimaiVersion, InLvSong)>,
+
+    pub removed_songs_wiki: RemovedSongsWiki,
+    pub removed_songs_supplemental: Vec<RemovedSongSupplemental>,
+
+    pub official_song_lists: Vec<OfficialSongList>,
+
+    pub additional_abbrevs: Vec<(SongAbbreviation, SongName)>,
+
+    pub utage_identifier_merge: UtageIdentifierMergeMap,
+
+    pub version_supplemental: Vec<VersionSupplemental>,
+
+    pub note_counts: Vec<ScoreNotes>,
+
+    pub in_lv_override: InLvCorrectionMap,
+    pub in_lv_bitmask_override: InLvCorrectionMap<in_lv::kind::Bitmask>,
+    pub in_lv_data_override: InLvDataCorrectionMap,
+
+    pub song_score_list: Option<(SongScoreListConfig, SongScoreList)>,
+}
+#[derive(Deserialize)]
+pub struct SongScoreListConfig {
+    list_path: PathBuf,
+    allow_missing_level: Vec<SongIcon>,
+    song_name_override: HashMap<SongName, SongName>,
+    icon_supplemental: HashMap<SongName, SongIcon>,
+    level_supplemental:
+        HashMap<SongIcon, BTreeMap<ScoreGeneration, BTreeMap<ScoreDifficulty, String>>>,
+}
+
+impl BuildInputs {
+    pub fn load(paths: &InputPaths) -> anyhow::Result<Self> {
+        let mut ret = BuildInputs::default();
+
+        // Read in_lv
+        for version in successors(Some(MaimaiVersion::Festival), MaimaiVersion::next)
+            .take_while(|&version| paths.in_lv_until.is_none_or(|until| version <= until))
+        {
+            let path = format!("{}.json", i8::from(version));
+            let levels = in_lv::load(paths.in_lv_dir.join(path))?;
+            assert!(ret.in_lv.insert(version, levels).is_none());
+        }
+
+        // Read in_lv_bitmask
+        for version in successors(Some(MaimaiVersion::Prism), MaimaiVersion::next)
+            //
+            .take_while(|&version| {
+                paths
+                    .in_lv_bitmask_until
+                    .is_none_or(|until| version <= until)
+            })
+        {
+            let path = format!("{}.json", i8::from(version));
+            let levels = in_lv::load_mask(paths.in_lv_bitmask_dir.join(path))?;
+            assert!(ret.in_lv_bitmask.insert(version, levels).is_none());
+        }
+
+        // Read in_lv_data
+        for version in successors(Some(MaimaiVersion::SplashPlus), MaimaiVersion::next)
+            .take_while(|&version| paths.in_lv_data_until.is_none_or(|until| version <= until))
+        {
+            let path = format!("{}.json", i8::from(version));
+            let data: InLvData = read_json(paths.in_lv_data_dir.join(path))?;
+            assert!(ret.in_lv_data.insert(version, data).is_none());
+        }
+
+        ret.in_lv_supplemental = {
+            let songs: Vec<(MaimaiVersion, SongRaw)> =
+                read_json(paths.database_dir.join("in_lv_supplemental.json"))?;
+            songs
+                .into_iter()
+                .map(|(version, song)| anyhow::Ok((version, song.try_into()?)))
+                .try_collect()?
+        };
+
+        // Read removed song list from wiki source
+        ret.removed_songs_wiki =
+            RemovedSongsWiki::read(paths.database_dir.join("removed_songs_wiki.txt"))?;
+
+        // Read supplemental removed song list
+        ret.removed_songs_supplemental = read_json(paths.database_dir.join("removed_songs.json"))?;
+
+        // Read official song list json
+        for path in &paths.official_song_list_paths {
+            let timestamp = official_snapshot::timestamp_from_path(path)?;
+            let songs: Vec<official::SongRaw> = read_json(path)?;
+            let list = OfficialSongList {
+                timestamp,
+                songs: songs.into_iter().map(TryInto::try_into).try_collect()?,
+            };
+            ret.official_song_lists.push(list)
+        }
+        // Sort the list by timestamp, and then...
+        ret.official_song_lists.sort_by_key(|x| x.timestamp);
+        // "debounce" the song list.  Sometimes, the song list are not updated even after the
+        // new version starts.  This is a trivial workaround using the heuristic that if the
+        // song list is not changed, it is not updated.
+        ret.official_song_lists.dedup_by(|x, y| x.songs == y.songs);
+
+        // Read additional_abbrevs
+        let abbrevs_path = paths.database_dir.join("additional_abbrevs.json");
+        if abbrevs_path.is_file() {
+            ret.additional_abbrevs = read_json(&abbrevs_path)?;
+        }
+
+        ret.utage_identifier_merge =
+            read_json::<_, Vec<(SongIcon, UtageIdentifier, UtageIdentifier)>>(
+                paths.database_dir.join("utage_identifier_merge.json"),
+            )?
+            .into_iter()
+            .map(|(a, b, c)| ((a, b), c))
+            .collect();
+
+        ret.version_supplemental = read_json(paths.database_dir.join("version_supplemental.json"))?;
+
+        // Read note counts
+        let note_counts_path = paths.database_dir.join("note_counts.json");
+        if note_counts_path.is_file() {
+            ret.note_counts = read_json(&note_counts_path)?;
+        }
+
+        // Read `in_lv` override map
+        ret.in_lv_override = read_json::<
+            _,
+            Vec<(
+                SongIcon,
+                Vec<((MaimaiVersion, ScoreGeneration, ScoreDifficulty), [f64; 2])>,
+            )>,
+        >(paths.database_dir.join("in_lv_override.json"))?
+        .into_iter()
+        .map(|(icon, values)| {
+            let map = values
+                .into_iter()
+                .map(|(key, [x, y])| anyhow::Ok((key, [x.try_into()?, y.try_into()?])))
+                .collect::<Result<_, _>>()?;
+            anyhow::Ok((icon, map))
+        })
+        .collect::<Result<_, _>>()?;
+
+        // Read `in_lv_bitmask` override map
+        ret.in_lv_bitmask_override =
+            read_json::<
+                _,
+                Vec<(
+                    SongIcon,
+                    Vec<((MaimaiVersion, ScoreGeneration, ScoreDifficulty), [f64; 2])>,
+                )>,
+            >(paths.database_dir.join("in_lv_bitmask_override.json"))?
+            .into_iter()
+            .map(|(icon, values)| {
+                let map = values
+                    .into_iter()
+                    .map(|(key, [x, y])| anyhow::Ok((key, [x.try_into()?, y.try_into()?])))
+                    .collect::<Result<_, _>>()?;
+                anyhow::Ok((icon, map))
+            })
+            .collect::<Result<_, _>>()?;
+
+        // Read `in_lv_data` override map
+        ret.in_lv_data_override = read_json::<
+            _,
+            Vec<(
+                SongIcon,
+                Vec<((MaimaiVersion, ScoreGeneration, ScoreDifficulty), [f64; 2])>,
+            )>,
+        >(paths.database_dir.join("in_lv_data_override.json"))?
+        .into_iter()
+        .map(|(icon, values)| {
+            let map = values
+                .into_iter()
+                .map(|(key, [x, y])| {
+                    let parse = |x: f64| {
+                        anyhow::Ok(in_lv::InternalScoreLevel::try_from(x)?.into_new(key.0))
+                    };
+                    anyhow::Ok((key, [parse(x)?, parse(y)?]))
+                })
+                .collect::<Result<_, _>>()?;
+            anyhow::Ok((icon, map))
+        })
+        .collect::<Result<_, _>>()?;
+
+        if let Some(path) = &paths.song_score_list_config {
+            let mut config: SongScoreListConfig = read_toml(path)?;
+            let mut data: SongScoreList = read_json(&config.list_path)?;
+            if paths.use_hint_in_song_score_list {
+                config
+                    .icon_supplemental
+                    .extend(data.song_name_to_icon_hint.drain(..));
+            }
+            ret.song_score_list = Some((config, data));
+        } else if paths.use_hint_in_song_score_list {
+            bail!("--use-hint-in-song-score-list must be used only when using --song-score-list-config");
+        }
+
+        Ok(ret)
+    }
+}
+
+/// Findings during the build that do not stop it.
+#[derive(Default, Debug)]
+pub struct BuildReport {
+    pub warnings: Vec<BuildWarning>,
+    /// Differences between the built songs and the latest official song list.
+    pub conflicts: Vec<Conflict>,
+}
+
+#[derive(Clone, Debug, Display)]
+pub enum BuildWarning {
+    #[display("New song was found: {_0}")]
+    NewSong(String),
+    #[display("New score was added: {_0}")]
+    NewScore(String),
+    #[display("Inserting Re:Master: {_0}")]
+    NewReMaster(String),
+    #[display("Skipping missing-level check for {_0}")]
+    MissingLevelAllowed(String),
+}
+
+#[derive(Clone, Debug, Display)]
+pub enum Conflict {
+    #[display(
+        "These scores differ by {fields:?} at version {version:?}\n\n{collected}\n\n{official}"
+    )]
+    Differs {
+        version: MaimaiVersion,
+        fields: Vec<String>,
+        collected: String,
+        official: String,
+    },
+    #[display("Only collected songs have {_0}")]
+    OnlyCollected(String),
+    #[display("Only official songs have {_0}")]
+    OnlyOfficial(String),
+}
+
+/// Merges all the resources into the song list.
+/// Unless `skip_official_song_verification` is set, the result is compared with the latest
+/// official song list, and the differences are reported as `BuildReport::conflicts`.
+pub fn build(
+    inputs: &BuildInputs,
+    skip_official_song_verification: bool,
+) -> anyhow::Result<(Vec<Song>, BuildReport)> {
+    let mut results = Results::default();
+    for list in &inputs.official_song_lists {
+        results
+            .read_official_song_list(list, &inputs.utage_identifier_merge)
+            .with_context(|| {
+                format!(
+                    "While processing official song list at {:?}",
+                    list.timestamp
+                )
+            })?;
+    }
+    for (&version, in_lv) in &inputs.in_lv {
+        results.read_in_lv(version, in_lv, Some(&inputs.in_lv_override))?;
+    }
+    for &(version, ref song) in &inputs.in_lv_supplemental {
+        results.read_in_lv(version, std::slice::from_ref(song), None)?;
+    }
+    for (&version, in_lv_bitmask) in &inputs.in_lv_bitmask {
+        results.read_in_lv(version, in_lv_bitmask, Some(&inputs.in_lv_bitmask_override))?;
+    }
+    results.read_removed_songs_wiki(&inputs.removed_songs_wiki)?;
+    results.read_removed_songs_supplemental(&inputs.removed_songs_supplemental)?;
+    results.read_additional_abbrevs(&inputs.additional_abbrevs)?;
+    for (&version, in_lv_data) in &inputs.in_lv_data {
+        results.read_in_lv_data(version, in_lv_data, &inputs.in_lv_data_override)?;
+    }
+    results.read_version_supplental(&inputs.version_supplemental)?;
+    results.read_note_counts(&inputs.note_counts)?;
+    if let Some((config, song_score)) = &inputs.song_score_list {
+        results.read_song_score_list(config, song_score)?;
+    }
+
+    if !skip_official_song_verification {
+        results.verify_latest_official_songs(
+            inputs
+                .official_song_lists
+                .last()
+                .context("There should be at least one official song")?,
+        )?;
+    }
+
+    Ok((results.songs.0, results.report))
+}
+
+/// Accumulates the actual song list as well as look up tables.
+#[derive(Default)]
+struct Results {
+    songs: SongList,
+    icon_to_song: HashMap<SongIcon, SongIndex>,
+    name_to_song: HashMap<SongName, HashSet<SongIndex>>,
+    abbrev_to_song: HashMap<SongAbbreviation, SongIndex>,
+    report: BuildReport,
+}
+
+impl Results {
+    fn read_official_song_list(
+        &mut self,
+        list: &OfficialSongList,
+        utage_identifier_merge: &UtageIdentifierMergeMap,
+    ) -> anyhow::Result<()> {
+        let mut found_utage = HashSet::new();
+
+        for data in &list.songs {
+            let (index, song) = match self.icon_to_song.entry(data.image().clone()) {
+                HEntry::Occupied(e) => {
+                    let index = *e.get();
+                    let song = self.songs.get_mut(index);
+                    (index, song)
+                }
+                HEntry::Vacant(e) => {
+                    let (index, song) = self.songs.create_new();
+                    e.insert(index);
+                    (index, song)
+                }
+            };
+
+            (|| {
+                let version = MaimaiVersion::of_time(list.timestamp + chrono::Duration::hours(9))
+                    .with_context(|| {
+                    format!("No matching version for timestamp {:?}", list.timestamp)
+                })?;
+
+                // Song name
+                let (song_name, utage_song_name_overwrite): (SongName, Option<SongName>) =
+                    match data.details() {
+                        ScoreDetails::Ordinary(_) => (data.title().clone(), None),
+                        ScoreDetails::Utage(u) => {
+                            let (name, overwrite) = {
+                                let name: &str = data.title().as_ref();
+                                match name.strip_prefix(&format!("[{}]", u.kanji())) {
+                                    Some(name) => (name, None),
+                                    None => {
+                                        let name = name
+                                            .split_once(']')
+                                            .context("Utage score does not contain `]`")?
+                                            .1;
+                                        (name, Some(data.title().to_owned()))
+                                    }
+                                }
+                            };
+                            (name.to_owned().into(), overwrite)
+                            // if let Some(name) = &song.name[version] {
+                            //     if format!("[{}]{name}", u.kanji()) != data.title().as_ref() {
+                            //         bail!("Unexpected title");
+                            //     }
+                            // } else {
+                            //     self.
+                            // }
+                        }
+                    };
+                merge_options(&mut song.name[version], Some(&song_name))?;
+                self.name_to_song
+                    .entry(song_name)
+                    .or_default()
+                    .insert(index);
+
+                // Song kana
+                merge_options(&mut song.pronunciation[version], Some(data.title_kana()))?;
+                // Artist
+                merge_options(&mut song.artist[version], Some(data.artist()))?;
+                // Icon
+                merge_options(&mut song.icon, Some(data.image()))?;
+                // Unused: release, sort, new
+                merge_locked(&mut song.locked_history, list.timestamp, data.locked());
+
+                if version < data.version().version() {
+                    bail!("Conflicting version: song {data:?} found in version {version:?}");
+                }
+
+                match data.details() {
+                    ScoreDetails::Ordinary(ordinary_data) => {
+                        merge_options(
+                            &mut song.category[version],
+                            Some(&ordinary_data.category()),
+                        )?;
+                        for (generation, scores_data) in [
+                            (ScoreGeneration::Standard, ordinary_data.standard()),
+                            (ScoreGeneration::Deluxe, ordinary_data.deluxe()),
+                        ] {
+                            let Some(scores_data) = scores_data else {
+                                continue;
+                            };
+                            let scores =
+                                song.scores[generation].get_or_insert_with(OrdinaryScores::default);
+                            if !(ordinary_data.standard().is_some()
+                                && ordinary_data.deluxe().is_some())
+                            {
+                                // If both standard and deluxe scores exist,
+                                // then the `release` field may not describe which of them the
+                                // release date refers to.
+                                // Otherwise, we can determine the release date right now.
+                                merge_options(
+                                    &mut scores.version,
+                                    Some(&data.version().version()),
+                                )?;
+                            }
+
+                            let lv = |v| InternalScoreLevel::unknown(version, v);
+                            merge_levels(
+                                &mut scores.basic.levels[version],
+                                lv(scores_data.basic()),
+                                version,
+                            )?;
+                            merge_levels(
+                                &mut scores.advanced.levels[version],
+                                lv(scores_data.advanced()),
+                                version,
+                            )?;
+                            merge_levels(
+                                &mut scores.expert.levels[version],
+                                lv(scores_data.expert()),
+                                version,
+                            )?;
+                            merge_levels(
+                                &mut scores.master.levels[version],
+                                lv(scores_data.master()),
+                                version,
+                            )?;
+                            if let Some(level) = scores_data.re_master() {
+                                let re_master =
+                                    scores.re_master.get_or_insert_with(Default::default);
+                                merge_levels(&mut re_master.levels[version], lv(level), version)?;
+                            }
+                        }
+                    }
+                    ScoreDetails::Utage(utage_data) => {
+                        if utage_identifier_merge
+                            .contains_key(&(data.image().clone(), utage_data.identifier()))
+                        {
+                            // In the future, we need to merge the data, but for now we are just
+                            // skipping
+                            return Ok(());
+                        }
+                        if !found_utage.insert((index, utage_data.identifier().to_owned())) {
+                            bail!("Duplicate utage scores found: {:?}", data);
+                        }
+                        let identifier = utage_data.identifier();
+                        match song
+                            .utage_scores
+                            .iter()
+                            .find(|u| u.identifier() == identifier)
+                        {
+                            Some(u) => {
+                                if !u.eq_without_name_overwrite(utage_data) {
+                                    bail!(
+                                        "Utage score conflict: stored {u:?}, found {utage_data:?}",
+                                    );
+                                }
+                            }
+                            None => {
+                                let utage_data = utage_data
+                                    .clone()
+                                    .with_name_overwrite(utage_song_name_overwrite);
+                                song.utage_scores.push(utage_data);
+                            }
+                        }
+                    }
+                }
+                Ok(())
+            })()
+            .with_context(|| format!("While incorporating {data:?} into {song:?}"))?;
+        }
+        Ok(())
+    }
+
+    fn read_song_score_list(
+        &mut self,
+        config: &SongScoreListConfig,
+        song_score: &SongScoreList,
+    ) -> anyhow::Result<()> {
+        let version = MaimaiVersion::latest();
+
+        for groups in song_score.by_difficulty.values() {
+            for entry in groups.iter().flat_map(|x| &x.entries) {
+                let song = if let Some(icon) = song_score.idx_to_icon_map.get(entry.idx()) {
+                    let &index = self
+                        .icon_to_song
+                        .get(icon)
+                        .context("Disambiguated song must exist")?;
+                    self.songs.get_mut(index)
+                } else {
+                    let name = entry.song_name();
+                    let name = config.song_name_override.get(name).unwrap_or(name).clone();
+                    match self.name_to_song.entry(name) {
+                        HEntry::Occupied(e) => {
+                            let indices = e.get();
+                            if indices.len() != 1 {
+                                bail!("Song is not unique: {entry:?}");
+                            }
+                            let &index = indices.iter().next().unwrap();
+                            self.songs.get_mut(index)
+                        }
+                        // New song from this version!
+                        HEntry::Vacant(e) => {
+                            (self.report.warnings)
+                                .push(BuildWarning::NewSong(format!("{entry:?}")));
+                            let (index, song) = self.songs.create_new();
+                            e.insert(HashSet::from_iter([index]));
+                            song
+                        }
+                    }
+                };
+
+                // Register song name
+                merge_options(&mut song.name[version], Some(entry.song_name()))?;
+                // Register icon
+                if let Some(icon) = config.icon_supplemental.get(entry.song_name()) {
+                    merge_options(&mut song.icon, Some(icon))?;
+                }
+
+                let scores = song.scores[entry.metadata().generation()].get_or_insert_with(|| {
+                    (self.report.warnings).push(BuildWarning::NewScore(format!("{entry:?}")));
+                    OrdinaryScores {
+                        easy: None,
+                        basic: Default::default(),
+                        advanced: Default::default(),
+                        expert: Default::default(),
+                        master: Default::default(),
+                        re_master: None,
+                        version: Some(version),
+                    }
+                });
+                let score = match entry.metadata().difficulty() {
+                    ScoreDifficulty::Basic => &mut scores.basic,
+                    ScoreDifficulty::Advanced => &mut scores.advanced,
+                    ScoreDifficulty::Expert => &mut scores.expert,
+                    ScoreDifficulty::Master => &mut scores.master,
+                    ScoreDifficulty::ReMaster => scores.re_master.get_or_insert_with(|| {
+                        (self.report.warnings)
+                            .push(BuildWarning::NewReMaster(format!("{entry:?}")));
+                        Default::default()
+                    }),
+                    ScoreDifficulty::Utage => bail!("Found utage score: {entry:?}"),
+                };
+                merge_levels(
+                    &mut score.levels[version],
+                    InternalScoreLevel::unknown(version, entry.level()),
+                    version,
+                )?;
+            }
+        }
+
+        for (icon, map) in &config.level_supplemental {
+            let song = self.songs.get_mut(
+                *self
+                    .icon_to_song
+                    .get(icon)
+                    .with_context(|| format!("Invalid icon for level_supplemental: {icon:?}"))?,
+            );
+            for (&generation, map) in map {
+                let scores = song.scores[generation].as_mut().with_context(|| {
+                    format!("Invalid generation for level_supplemental: {icon:?}")
+                })?;
+                for (difficulty, level) in map {
+                    use ScoreDifficulty::*;
+                    let score = match difficulty {
+                        Basic => &mut scores.basic,
+                        Advanced => &mut scores.advanced,
+                        Expert => &mut scores.expert,
+                        Master => &mut scores.master,
+                        ReMaster => scores.re_master.as_mut().with_context(|| {
+                            format!("No ReMaster score for {icon:?} {generation:?}")
+                        })?,
+                        Utage => bail!("Utage is not allowed here"),
+                    };
+                    let level = InternalScoreLevel::unknown(version, ScoreLevel::from_str(level)?);
+                    merge_levels(&mut score.levels[version], level, version)?;
+                }
+            }
+        }
+
+        // All unremoved songs should have levels associated
+        for song in &self.songs.0 {
+            if song.removed() {
+                continue;
+            }
+            if (song.icon.as_ref()).is_some_and(|icon| config.allow_missing_level.contains(icon)) {
+                (self.report.warnings).push(BuildWarning::MissingLevelAllowed(format!(
+                    "{:?}",
+                    song.latest_song_name()
+                )));
+                continue;
+            }
+            for (generation, scores) in &song.scores {
+                let Some(scores) = scores else { continue };
+                let check = |difficulty: ScoreDifficulty,
+                             score: &OrdinaryScore|
+                 -> anyhow::Result<()> {
+                    if score.levels[version].is_none() {
+                        bail!("Score level for the current version is missing ({generation:?} {difficulty:?}): {song:#?}");
+                    }
+                    Ok(())
+                };
+                use ScoreDifficulty::*;
+                check(Basic, &scores.basic)?;
+                check(Advanced, &scores.advanced)?;
+                check(Expert, &scores.expert)?;
+                check(Master, &scores.master)?;
+                if let Some(score) = &scores.re_master {
+                    check(ReMaster, score)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+trait InLvKind: in_lv::kind::Kind {
+    fn merge_levels(
+        levels: &mut Option<InternalScoreLevel>,
+        value: Self::Value,
+        version: MaimaiVersion,
+    ) -> anyhow::Result<()>;
+    fn desc() -> &'static str;
+}
+impl InLvKind for in_lv::kind::Levels {
+    fn merge_levels(
+        levels: &mut Option<InternalScoreLevel>,
+        value: Self::Value,
+        version: MaimaiVersion,
+    ) -> anyhow::Result<()> {
+        merge_levels(levels, value.into_new(version), version)
+    }
+    fn desc() -> &'static str {
+        "levels"
+    }
+}
+impl InLvKind for in_lv::kind::Bitmask {
+    fn merge_levels(
+        levels: &mut Option<InternalScoreLevel>,
+        value: Self::Value,
+        version: MaimaiVersion,
+    ) -> anyhow::Result<()> {
+        let Some(level) = levels else {
+            bail!("Unable to determine base level");
+        };
+        let new = InternalScoreLevel::new(version, level.into_level(version), value)?;
+        merge_levels(levels, new, version)
+    }
+    fn desc() -> &'static str {
+        "bitmask"
+    }
+}
+
+impl Results {
+    /// This function is to be called at most once per version.
+    fn read_in_lv<K: InLvKind>(
+        &mut self,
+        version: MaimaiVersion,
+        in_lv: &[InLvSong<K>],
+        overrides: Option<&InLvCorrectionMap<K>>,
+    ) -> anyhow::Result<()> {
+        // generation: ScoreGeneration,
+        // version: MaimaiVersion,
+        // levels: ScoreLevels,
+        // song_name: SongName,
+        // song_name_abbrev: String,
+        // icon: SongIcon,
+        for data in in_lv {
+            // Use `icon` and `song_name`
+            let (index, song) = match self.icon_to_song.entry(data.icon().to_owned()) {
+                HEntry::Occupied(i) => {
+                    let index = *i.get();
+                    let song = self.songs.get_mut(index);
+                    (index, song)
+                }
+                HEntry::Vacant(e) => {
+                    let (index, song) = self.songs.create_new();
+                    e.insert(index);
+                    (index, song)
+                }
+            };
+            merge_options(&mut song.icon, Some(data.icon()))?;
+            song.name[version] = Some(data.song_name().to_owned());
+
+            Self::read_in_lv_song(
+                &mut self.name_to_song,
+                &mut self.abbrev_to_song,
+                index,
+                song,
+                version,
+                data,
+                overrides,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn read_in_lv_song<K: InLvKind>(
+        name_to_song: &mut HashMap<SongName, HashSet<SongIndex>>,
+        abbrev_to_song: &mut HashMap<SongAbbreviation, SongIndex>,
+        index: SongIndex,
+        song: &mut Song,
+        version: MaimaiVersion,
+        data: &InLvSong<K>,
+        overrides: Option<&InLvCorrectionMap<K>>,
+    ) -> Result<(), anyhow::Error> {
+        (|| {
+            // Update song name map
+            name_to_song
+                .entry(data.song_name().to_owned())
+                .or_default()
+                .insert(index);
+
+            // Update abbreviation map, check if contradiction occurs
+            let abbrev: SongAbbreviation = data.song_name_abbrev().to_owned().into();
+            Self::register_abbrev(abbrev_to_song, &abbrev, index)?;
+
+            // Record `song_name_abbrev`
+            song.abbreviation[version] = Some(abbrev.clone());
+            let scores = song.scores[data.generation()].get_or_insert_with(OrdinaryScores::default);
+
+            // When `in_lv`'s `v` equals `0`, it means its ジングルベル Std
+            // (which is classified to Ver.Maimai);
+            // if it's `v` equals `1`, it means it's a song for either Maimai or MaimaiPlus.
+            // But mistakenly, these are parsed as Maimai and MaimaiPlus, respectively.
+            // In fact, we cannot distinguish from `v` data if it is 1, so we should leave the
+            // version blank in this case.
+            if !matches!(data.version(), MaimaiVersion::MaimaiPlus) {
+                merge_options(&mut scores.version, Some(&data.version()))?;
+            }
+
+            // Record `levels` (indexed by `generation` and `version`)
+            let get = |difficulty: ScoreDifficulty| {
+                let Some(level) = data.levels().get(difficulty) else {
+                    return Ok(None);
+                };
+                let Some(overrides) = overrides else {
+                    return Ok(Some(level));
+                };
+                let key = (version, data.generation(), difficulty);
+                let Some(&[before, after]) = overrides.get(data.icon()).and_then(|r| r.get(&key))
+                else {
+                    return Ok(Some(level));
+                };
+                if before != level {
+                    bail!(
+                        "Request to override {key:?} from {before:?} to {after:?}, but found {level:?}"
+                    );
+                }
+                Ok(Some(after))
+            };
+            K::merge_levels(
+                &mut scores.basic.levels[version],
+                get(ScoreDifficulty::Basic)?.unwrap(),
+                version,
+            )?;
+            K::merge_levels(
+                &mut scores.advanced.levels[version],
+                get(ScoreDifficulty::Advanced)?.unwrap(),
+                version,
+            )?;
+            K::merge_levels(
+                &mut scores.expert.levels[version],
+                get(ScoreDifficulty::Expert)?.unwrap(),
+                version,
+            )?;
+            K::merge_levels(
+                &mut scores.master.levels[version],
+                get(ScoreDifficulty::Master)?.unwrap(),
+                version,
+            )?;
+            if let Some(level) = get(ScoreDifficulty::ReMaster)? {
+                K::merge_levels(
+                    &mut scores.re_master.get_or_insert_with(Default::default).levels[version],
+                    level,
+                    version,
+                )?;
+            }
+            // scores.basic.levels[version] = Some(data.levels().get(ScoreDifficulty::Basic).unwrap());
+            // scores.advanced.levels[version] =
+            //     Some(data.levels().get(ScoreDifficulty::Advanced).unwrap());
+            // scores.expert.levels[version] =
+            //     Some(data.levels().get(ScoreDifficulty::Expert).unwrap());
+            // scores.master.levels[version] =
+            //     Some(data.levels().get(ScoreDifficulty::Master).unwrap());
+            // if let Some(level) = data.levels().get(ScoreDifficulty::ReMaster) {
+            //     scores.re_master.get_or_insert_with(Default::default).levels[version] = Some(level);
+            // }
+
+            anyhow::Ok(())
+        })()
+        .with_context(|| {
+            format!("While incorporating {data:?} of in_lv {} version {version:?} into {song:?}", K::desc())
+        })
+    }
+}
+
+impl Results {
+    fn register_abbrev(
+        abbrev_to_song: &mut HashMap<SongAbbreviation, SongIndex>,
+        abbrev: &SongAbbreviation,
+        index: SongIndex,
+    ) -> anyhow::Result<()> {
+        match abbrev_to_song.entry(abbrev.clone()) {
+            HEntry::Occupied(i) => {
+                if *i.get() != index {
+                    bail!("At least two songs are associated to nickname {abbrev:?}")
+                }
+            }
+            HEntry::Vacant(e) => {
+                e.insert(index);
+            }
+        }
+        Ok(())
+    }
+
+    fn read_removed_songs_wiki(&mut self, songs: &RemovedSongsWiki) -> anyhow::Result<()> {
+        for data in &songs.songs {
+            // Create or get song from song name
+            let song_name = SongName::from(data.song_name.to_owned());
+            let (index, song) = match self.name_to_song.entry(song_name.clone()) {
+                // Song is already registered by inner_lv
+                HEntry::Occupied(e) => match Vec::from_iter(e.get())[..] {
+                    [&index] => {
+                        let song = self.songs.get_mut(index);
+                        (index, song)
+                    }
+                    ref multiple => bail!(
+                        "Song name {:?} is not unique: {:?}",
+                        &data.song_name,
+                        multiple.iter().map(|&&s| self.songs.get(s)).collect_vec(),
+                    ),
+                },
+                // Song is unique in removed_songs_wiki
+                HEntry::Vacant(e) => {
+                    let (index, song) = self.songs.create_new();
+                    e.insert(HashSet::from_iter([index]));
+                    (index, song)
+                }
+            };
+
+            // Register song name as abbrevation (is this correct?)
+            match self.abbrev_to_song.entry(data.song_name.to_owned().into()) {
+                HEntry::Occupied(i) => {
+                    if *i.get() != index {
+                        bail!(
+                            "At least two songs are associated to nickname {:?}: {:?} and {:?}",
+                            &data.song_name,
+                            self.songs.get(index),
+                            self.songs.get(*i.get()),
+                        )
+                    }
+                }
+                HEntry::Vacant(e) => {
+                    e.insert(index);
+                }
+            };
+
+            let remove_date = NaiveDate::parse_from_str(&data.date, "%Y/%m/%d")
+                .with_context(|| format!("Unexpected date: {data:?}"))?;
+            let last_version = enum_iterator::all()
+                .find_or_last(|x: &MaimaiVersion| remove_date <= x.start_date())
+                .expect("MaimaiVersion has at least one element")
+                .previous()
+                .with_context(|| format!("No corresponding version for remove date: {data:?}"))?;
+            merge_options(&mut song.name[last_version], Some(&song_name))?;
+            merge_remove_state(&mut song.remove_state, remove_date)?;
+
+            for levels in chain([&data.levels], &data.another) {
+                let generation = match levels.0[0] {
+                    Some(_) => ScoreGeneration::Standard,
+                    None => ScoreGeneration::Deluxe,
+                };
+                if !levels.0[1..5].iter().all(|x| x.is_some()) {
+                    bail!("Missing levels between BASIC and MASTER: {data:?}");
+                }
+                let make = |i: usize| {
+                    levels.0[i].map(|level| {
+                        let mut map = EnumMap::default();
+                        let version = if i == 0 {
+                            MaimaiVersion::Finale.min(last_version)
+                        } else {
+                            last_version
+                        };
+                        map[version] = Some(InternalScoreLevel::unknown(version, level));
+                        OrdinaryScore {
+                            levels: map.into(),
+                            notes: None,
+                        }
+                    })
+                };
+                song.scores[generation].get_or_insert_with(|| OrdinaryScores {
+                    easy: make(0),
+                    basic: make(1).unwrap(),
+                    advanced: make(2).unwrap(),
+                    expert: make(3).unwrap(),
+                    master: make(4).unwrap(),
+                    re_master: make(5),
+                    version: None,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn read_removed_songs_supplemental(
+        &mut self,
+        removed_songs_supplemental: &[RemovedSongSupplemental],
+    ) -> anyhow::Result<()> {
+        for data in removed_songs_supplemental {
+            let (index, song) = match self.name_to_song.get(&data.name) {
+                None => bail!("No song matches for {data:?}"),
+                Some(x) => match Vec::from_iter(x)[..] {
+                    [&index] => (index, self.songs.get_mut(index)),
+                    ref multiple => bail!(
+                        "Song name {:?} is not unique: {:?}",
+                        &data.name,
+                        multiple.iter().map(|&&s| self.songs.get(s)).collect_vec(),
+                    ),
+                },
+            };
+
+            // Register icon
+            merge_options(&mut song.icon, data.icon.as_ref())?;
+
+            // Regsiter the song name itself as abbreviation
+            if let Some(abbrev) = &data.abbrev {
+                Self::register_abbrev(&mut self.abbrev_to_song, abbrev, index)?;
+            }
+
+            // Register levels
+            for &(version, ref levels) in &data.levels {
+                let data = InLvSong::try_from(levels.clone())?;
+                // Before calling `read_in_lv_song`, we need to merge those fields not covered by that function.
+                // According to the implementation of `read_in_lv`, `icon` and `song_name` qualify.
+                merge_options(&mut song.name[version], Some(data.song_name()))?;
+                merge_options(&mut song.icon, Some(data.icon()))?;
+
+                // Now we can leave the rest to this function.
+                Self::read_in_lv_song(
+                    &mut self.name_to_song,
+                    &mut self.abbrev_to_song,
+                    index,
+                    song,
+                    version,
+                    &data,
+                    None,
+                )?;
+            }
+
+            // Register bitmasks
+            for &(version, ref levels) in &data.bitmasks {
+                let data = InLvSong::<Bitmask>::try_from(levels.clone())?;
+                merge_options(&mut song.name[version], Some(data.song_name()))?;
+                merge_options(&mut song.icon, Some(data.icon()))?;
+
+                // Now we can leave the rest to this function.
+                Self::read_in_lv_song(
+                    &mut self.name_to_song,
+                    &mut self.abbrev_to_song,
+                    index,
+                    song,
+                    version,
+                    &data,
+                    None,
+                )?;
+            }
+
+            // Register removed date
+            merge_remove_state(&mut song.remove_state, data.date)?;
+        }
+        Ok(())
+    }
+
+    fn read_in_lv_data(
+        &mut self,
+        version: MaimaiVersion,
+        data: &InLvData,
+        overrides: &InLvDataCorrectionMap,
+    ) -> anyhow::Result<()> {
+        let try_override =
+            |level: InternalScoreLevel,
+             icon: &Option<SongIcon>,
+             key: (MaimaiVersion, ScoreGeneration, ScoreDifficulty)| {
+                if let Some(&[before, after]) = icon
+                    .as_ref()
+                    .and_then(|icon| overrides.get(icon))
+                    .and_then(|x| x.get(&key))
+                {
+                    if level == before {
+                        Ok(after)
+                    } else {
+                        bail!("Request to override {key:?} from {before:?} to {after:?}, but found {level:?}")
+                    }
+                } else {
+                    Ok(level)
+                }
+            };
+
+        // Process unknown songs.
+        // Remove entry once process so that no data unprocessed is left.
+        let mut unknown: HashMap<_, _> = data.unknown.iter().collect();
+        if !unknown
+            .remove(&UnknownKey::gen("14".parse()?))
+            .is_some_and(|x| x.is_empty())
+        {
+            bail!("Lv.14 is not empty");
+        }
+        for level in 10..14 {
+            for plus in [false, true] {
+                let level = ScoreLevel::new(level, plus)?;
+                let data = unknown
+                    .remove(&UnknownKey::gen(level))
+                    .with_context(|| format!("No unknown entry found for {level}"))?;
+                for entry in data {
+                    let entry = entry.parse()?;
+                    let missing_song =
+                        || format!("Missing song: {:?} (on version {:?})", entry.entry, version);
+
+                    let song = self.songs.get_mut(
+                        *self
+                            .abbrev_to_song
+                            .get(&entry.entry.song_nickname)
+                            .with_context(missing_song)?,
+                    );
+                    let scores = song.scores[entry.entry.generation()]
+                        .as_mut()
+                        .with_context(missing_song)?;
+                    let mut set = |difficulty, level| {
+                        merge_levels(
+                            &mut scores
+                                .get_score_mut(difficulty)
+                                .with_context(missing_song)?
+                                .levels[version],
+                            try_override(
+                                InternalScoreLevel::unknown(version, level),
+                                &song.icon,
+                                (version, entry.entry.generation(), entry.entry.difficulty),
+                            )?,
+                            version,
+                        )
+                        .with_context(|| format!("While processing {entry:?} in {version:?}"))?;
+                        anyhow::Ok(())
+                    };
+                    set(entry.entry.difficulty, level)?;
+                    for &(difficulty, level) in &entry.additional {
+                        set(difficulty, level)?;
+                    }
+                }
+            }
+        }
+        if !unknown.is_empty() {
+            bail!("Additional data found: {:?}", unknown);
+        }
+
+        // Process known songs.
+        // Remove entry once process so that no data unprocessed is left.
+        let mut known: HashMap<_, _> = data.known.iter().collect();
+        for level in 5..=15 {
+            let data = known
+                .remove(&KnownKey::gen(level))
+                .with_context(|| format!("No known entry found for {level}"))?;
+            let expected_len = if level == 15 { 1 } else { 10 };
+            if data.len() != expected_len {
+                bail!(
+                    "Unexpected length for level {level}: expected {expected_len}, found {}",
+                    data.len()
+                );
+            }
+            for (entries, fractional) in data.iter().rev().zip(0..) {
+                let level = ScoreConstant::try_from(level * 10 + fractional)
+                    .map_err(|e| anyhow!("Unexpected internal lv: {e}"))?;
+                for entry in entries {
+                    let entry = entry.parse()?;
+                    let missing_song =
+                        || format!("Missing song: {:?} (on version {:?})", entry, version);
+                    if regex!(r"^Lv([1-6]|7\+?)表記の全譜面").is_match(entry.song_nickname.as_ref())
+                    {
+                        continue;
+                    }
+
+                    let song = self.songs.get_mut(
+                        *self
+                            .abbrev_to_song
+                            .get(&entry.song_nickname)
+                            .with_context(missing_song)?,
+                    );
+                    let scores = song.scores[entry.generation()]
+                        .as_mut()
+                        .with_context(missing_song)?;
+                    merge_levels(
+                        &mut scores
+                            .get_score_mut(entry.difficulty)
+                            .with_context(missing_song)?
+                            .levels[version],
+                        try_override(
+                            InternalScoreLevel::known(level),
+                            &song.icon,
+                            (version, entry.generation(), entry.difficulty),
+                        )?,
+                        version,
+                    )
+                    .with_context(|| format!("While processing {entry:?} in {version:?}"))?;
+                }
+            }
+        }
+        if !known.is_empty() {
+            bail!("Additional data found: {:?}", data.unknown);
+        }
+
+        Ok(())
+    }
+
+    fn read_additional_abbrevs(
+        &mut self,
+        additional_abbrevs: &[(SongAbbreviation, SongName)],
+    ) -> anyhow::Result<()> {
+        for (abbrev, name) in additional_abbrevs {
+            let indices = self
+                .name_to_song
+                .get(name)
+                .with_context(|| format!("No song named {name:?}"))?;
+            if indices.len() != 1 {
+                bail!("Multiple songs named {name:?}");
+            }
+            let &index = indices.iter().next().unwrap();
+            Self::register_abbrev(&mut self.abbrev_to_song, abbrev, index)?;
+        }
+        Ok(())
+    }
+
+    fn read_version_supplental(
+        &mut self,
+        version_supplemental: &[VersionSupplemental],
+    ) -> anyhow::Result<()> {
+        for data in version_supplemental {
+            let song = self.songs.get_mut(
+                self.icon_to_song
+                    .get(&data.icon)
+                    .copied()
+                    .with_context(|| format!("No song matches {:?}", data.icon))?,
+            );
+            let score = match &mut song.scores[data.generation] {
+                Some(score) => score,
+                None => bail!(
+                    "Song does not have generation {:?}: {:#?}",
+                    data.generation,
+                    song
+                ),
+            };
+            if score.version.is_some() {
+                bail!(
+                    "Version is already stored for generation {:?}: {:#?}",
+                    data.generation,
+                    song
+                );
+            }
+            score.version = Some(data.version);
+        }
+        Ok(())
+    }
+
+    fn read_note_counts(&mut self, note_counts: &[ScoreNotes]) -> anyhow::Result<()> {
+        for data in note_counts {
+            let song = self.songs.get_mut(
+                self.icon_to_song
+                    .get(&data.icon)
+                    .copied()
+                    .with_context(|| format!("No song matches {:?}", data.icon))?,
+            );
+            let score = song.scores[data.generation]
+                .as_mut()
+                .and_then(|scores| scores.get_score_mut(data.difficulty))
+                .with_context(|| format!("Score does not exist: {data:?}"))?;
+            merge_options(&mut score.notes, Some(&data.notes))?;
+        }
+        Ok(())
+    }
+
+    fn verify_latest_official_songs(&mut self, list: &OfficialSongList) -> anyhow::Result<()> {
+        let version = MaimaiVersion::latest();
+        if list.timestamp < version.start_time() {
+            bail!("The latest official song list is not of the latest version");
+        }
+
+        let mut conflicts = vec![];
+        let mut collected_songs = vec![];
+        let mut collected_utages = vec![];
+        for song in &self.songs.0 {
+            if !song.removed() {
+                if song.scores.values().any(|x| x.is_some()) {
+                    collected_songs.push(song);
+                }
+                for score in &song.utage_scores {
+                    collected_utages.push((song, score));
+                }
+            }
+        }
+
+        let mut official_songs = vec![];
+        let mut official_utages = vec![];
+        for song in &list.songs {
+            match song.details() {
+                ScoreDetails::Ordinary(score) => official_songs.push((song, score)),
+                ScoreDetails::Utage(score) => official_utages.push((song, score)),
+            }
+        }
+
+        collected_songs.sort_by_key(|x| &x.icon);
+        official_songs.sort_by_key(|x| x.0.image());
+        for item in collected_songs.iter().zip_longest(&official_songs) {
+            match item {
+                EitherOrBoth::Both(collected, (song, score)) => {
+                    let level_ok =
+                        |x: ScoreLevel| move |y: InternalScoreLevel| x == y.into_level(version);
+                    let ok = |generation: ScoreGeneration| {
+                        move |levels: official::Levels| {
+                            Some(match &collected.scores[generation] {
+                                None => "Missing score",
+                                Some(collected) => {
+                                    if !collected.basic.levels[version]
+                                        .is_some_and(level_ok(levels.basic()))
+                                    {
+                                        "basic"
+                                    } else if !collected.advanced.levels[version]
+                                        .is_some_and(level_ok(levels.advanced()))
+                                    {
+                                        "advanced"
+                                    } else if !collected.expert.levels[version]
+                                        .is_some_and(level_ok(levels.expert()))
+                                    {
+                                        "expert"
+                                    } else if !collected.master.levels[version]
+                                        .is_some_and(level_ok(levels.master()))
+                                    {
+                                        "master"
+                                    } else {
+                                        let res = match (&collected.re_master, levels.re_master()) {
+                                            (Some(x), Some(y)) => {
+                                                x.levels[version].is_some_and(level_ok(y))
+                                            }
+                                            (None, None) => true,
+                                            _ => false,
+                                        };
+                                        if res {
+                                            return None;
+                                        } else {
+                                            "remaster"
+                                        }
+                                    }
+                                }
+                            })
+                            // .as_ref()
+                            // .is_some_and(|collected| {})
+                        }
+                    };
+                    let item_wrong = [
+                        (
+                            collected.name[version].as_ref() == Some(song.title()),
+                            "song name",
+                        ),
+                        (
+                            collected.pronunciation[version].as_ref() == Some(song.title_kana()),
+                            "song kana",
+                        ),
+                        (
+                            collected.artist[version].as_ref() == Some(song.artist()),
+                            "artist",
+                        ),
+                        (collected.icon.as_ref() == Some(song.image()), "icon"),
+                        // (
+                        //     collected
+                        //         .scores
+                        //         .values()
+                        //         .flatten()
+                        //         .filter_map(|v| v.version)
+                        //         .any(|version| version == song.version().version()),
+                        //     "version",
+                        // ),
+                        (
+                            collected.locked_history.values().last().copied()
+                                == Some(song.locked()),
+                            "locked",
+                        ),
+                        (
+                            collected.category[version] == Some(score.category()),
+                            "category",
+                        ),
+                    ]
+                    .into_iter()
+                    .filter_map(|(x, y)| (!x).then_some(y))
+                    .collect_vec();
+                    let score_wrong = [
+                        (
+                            score.standard().and_then(ok(ScoreGeneration::Standard)),
+                            "standard score",
+                        ),
+                        (
+                            score.deluxe().and_then(ok(ScoreGeneration::Deluxe)),
+                            "deluxe score",
+                        ),
+                    ]
+                    .into_iter()
+                    .filter_map(|(l, g)| l.map(|l| format!("{g} ({l})")));
+                    let fields = item_wrong
+                        .into_iter()
+                        .map(str::to_owned)
+                        .chain(score_wrong)
+                        .collect_vec();
+                    if !fields.is_empty() {
+                        conflicts.push(Conflict::Differs {
+                            version,
+                            fields,
+                            collected: format!("{collected:#?}"),
+                            official: format!("{song:#?}"),
+                        });
+                    }
+                }
+                EitherOrBoth::Left(x) => conflicts.push(Conflict::OnlyCollected(format!("{x:?}"))),
+                EitherOrBoth::Right(x) => conflicts.push(Conflict::OnlyOfficial(format!("{x:?}"))),
+            }
+        }
+
+        collected_utages.sort_by_key(|x| (&x.0.icon, x.1.identifier()));
+        official_utages.sort_by_key(|x| (x.0.image(), x.1.identifier()));
+
+        // for (i, song) in collected_utages.iter().enumerate() {
+        //     println!(
+        //         "{i} {:?} {:?}",
+        //         song.0.name.values().flatten().last(),
+        //         song.0.icon
+        //     );
+        // }
+        // for (i, song) in official_utages.iter().enumerate() {
+        //     println!("{i} {} {:?}", song.0.title(), song.0.image());
+        // }
+
+        for item in collected_utages.iter().zip_longest(&official_utages) {
+            match item {
+                EitherOrBoth::Both((collected, x), (song, y)) => {
+                    let wrong = [
+                        (
+                            collected.name[version].as_ref().is_some_and(|name| {
+                                match x.name_overwrite() {
+                                    None => {
+                                        format!("[{}]{name}", x.kanji())
+                                            == AsRef::<str>::as_ref(song.title())
+                                    }
+                                    Some(name) => name == song.title(),
+                                }
+                            }),
+                            "song name",
+                        ),
+                        (
+                            collected.pronunciation[version].as_ref() == Some(song.title_kana()),
+                            "song kana",
+                        ),
+                        (
+                            collected.artist[version].as_ref() == Some(song.artist()),
+                            "artist",
+                        ),
+                        (x.eq_without_name_overwrite(y), "utage score"),
+                    ]
+                    .into_iter()
+                    .filter_map(|(x, y)| (!x).then_some(y))
+                    .collect_vec();
+                    if !wrong.is_empty() {
+                        conflicts.push(Conflict::Differs {
+                            version,
+                            fields: wrong.into_iter().map(str::to_owned).collect(),
+                            collected: format!("{collected:#?}"),
+                            official: format!("{song:#?}"),
+                        });
+                    }
+                }
+                EitherOrBoth::Left(x) => conflicts.push(Conflict::OnlyCollected(format!("{x:?}"))),
+                EitherOrBoth::Right(x) => conflicts.push(Conflict::OnlyOfficial(format!("{x:?}"))),
+            }
+        }
+
+        self.report.conflicts.extend(conflicts);
+        Ok(())
+    }
+}
+
+fn merge_levels(
+    x: &mut Option<InternalScoreLevel>,
+    y: InternalScoreLevel,
+    version: MaimaiVersion,
+) -> anyhow::Result<()> {
+    enum Verdict {
+        Assign(InternalScoreLevel),
+        Inconsistent(InternalScoreLevel),
+    }
+    // use InternalScoreLevel::*;
+    use Verdict::*;
+    let verdict = match x {
+        None => Assign(y),
+        &mut Some(x) => {
+            if x.into_level(version) != y.into_level(version) {
+                Inconsistent(x)
+            } else {
+                Assign(x.intersection(y))
+            }
+            // match (x0, y) {
+            //     (Unknown(x), Unknown(y)) => {
+            //         if x == y {
+            //             Keep
+            //         } else {
+            //             Inconsistent(x0)
+            //         }
+            //     }
+            //     (Unknown(x), Known(y)) => {
+            //         if y.to_lv(version) == x {
+            //             Assign
+            //         } else {
+            //             Inconsistent(x0)
+            //         }
+            //     }
+            //     (Known(x), Unknown(y)) => {
+            //         if x.to_lv(version) == y {
+            //             Keep
+            //         } else {
+            //             Inconsistent(x0)
+            //         }
+            //     }
+            //     (Known(x), Known(y)) => {
+            //         if x == y {
+            //             Keep
+            //         } else {
+            //             Inconsistent(x0)
+            //         }
+            //     }
+            // },
+        }
+    };
+    match verdict {
+        Assign(y) => {
+            *x = Some(y);
+            Ok(())
+        }
+        Inconsistent(x) => bail!("Inconsistent levels: known to be {x:?}, found {y:?}"),
+    }
+    // if let Assign = verdict {
+    //     *x = Some(y);
+    // }
+    // if let Inconsistent(x) = verdict {
+    //     bail!("Inconsistent levels: known to be {x:?}, found {y:?}")
+    // } else {
+    //     Ok(())
+    // }
+}
+
+fn merge_remove_state(
+    remove_state: &mut RemoveState,
+    remove_date: NaiveDate,
+) -> anyhow::Result<()> {
+    match *remove_state {
+        RemoveState::Present => *remove_state = RemoveState::Removed(remove_date),
+        RemoveState::Removed(known_remove_date) => {
+            if remove_date != known_remove_date {
+                bail!("Conflicting remove date: stored {remove_date}, found {known_remove_date}");
+            }
+        }
+        RemoveState::Revived(_, _) => {
+            bail!("Revived songs should be patched later manually")
+        }
+    }
+    Ok(())
+}
+
+/// Records the lock state only when it changes,
+/// so that `locked_history` consists of the transitions.
+fn merge_locked(
+    locked_history: &mut BTreeMap<NaiveDateTime, bool>,
+    timestamp: NaiveDateTime,
+    locked: bool,
+) {
+    let last = locked_history.range(..=timestamp).next_back();
+    if last.map(|(_, &x)| x) != Some(locked) {
+        locked_history.insert(timestamp, locked);
+    }
+}
+
+fn merge_options<T>(x: &mut Option<T>, y: Option<&T>) -> anyhow::Result<()>
+where
+    T: Eq + Clone + Debug,
+{
+    if let Some(y) = y {
+        match x {
+            Some(x) if x != y => bail!("Value mismatch: {x:?} stored, tried to assign {y:?}"),
+            _ => *x = Some(y.clone()),
+        }
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+struct SongList(Vec<Song>);
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+/// Virtual pointer to an element of `Results::songs`.
+struct SongIndex(usize);
+impl SongList {
+    fn get(&self, index: SongIndex) -> &Song {
+        &self.0[index.0]
+    }
+    fn get_mut(&mut self, index: SongIndex) -> &mut Song {
+        &mut self.0[index.0]
+    }
+    fn create_new(&mut self) -> (SongIndex, &mut Song) {
+        let index = SongIndex(self.0.len());
+        self.0.push(Song::default());
+        (index, self.get_mut(index))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RemovedSongSupplemental {
+    icon: Option<SongIcon>,
+    name: SongName,
+    #[allow(unused)]
+    date: NaiveDate,
+    abbrev: Option<SongAbbreviation>,
+    #[serde(default)]
+    levels: Vec<(MaimaiVersion, SongRaw)>,
+    #[serde(default)]
+    bitmasks: Vec<(MaimaiVersion, SongRaw<Bitmask>)>,
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub struct OfficialSongList {
+    pub timestamp: NaiveDateTime,
+    pub songs: Vec<official::Song>,
+}
+
+#[derive(Deserialize)]
+pub struct VersionSupplemental {
+    icon: SongIcon,
+    generation: ScoreGeneration,
+    version: MaimaiVersion,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use chrono::NaiveDate;
+    use hashbrown::HashMap;
+    use itertools::Itertools;
+
+    use crate::maimai::{
+        rating::{InternalScoreLevel, ScoreConstant, ScoreLevel},
+        schema::latest::{ScoreDifficulty, ScoreGeneration, SongIcon},
+        song_list::{
+            in_lv,
+            official::{self, ScoreDetails},
+            OrdinaryScores, RemoveState, Song,
+        },
+        version::MaimaiVersion,
+    };
+
+    use super::{
+        merge_levels, merge_locked, merge_options, merge_remove_state, InLvCorrectionMap, InLvData,
+        InLvDataCorrectionMap, InLvSong, OfficialSongList, RemovedSongsWiki, Results,
+    };
+
+    #[test]
+    fn test_merge_levels() {
+        let version = MaimaiVersion::Prism;
+        let level = ScoreLevel::new(13, true).unwrap();
+        let unknown = InternalScoreLevel::unknown(version, level);
+        let known = |x: u8| InternalScoreLevel::known(ScoreConstant::try_from(x).unwrap());
+
+        let mut x = None;
+        merge_levels(&mut x, unknown, version).unwrap();
+        assert_eq!(x, Some(unknown));
+        merge_levels(&mut x, known(137), version).unwrap();
+        assert_eq!(x, Some(known(137)));
+        // Merging a wider range keeps the narrower one, but a different level is an error
+        merge_levels(&mut x, unknown, version).unwrap();
+        assert_eq!(x, Some(known(137)));
+        // Only the level is checked, so disjoint candidates leave nothing
+        merge_levels(&mut x, known(136), version).unwrap();
+        assert!(x.unwrap().is_empty());
+        x = Some(known(137));
+        assert!(merge_levels(&mut x, known(130), version).is_err());
+        assert!(merge_levels(&mut x, known(140), version).is_err());
+        assert_eq!(x, Some(known(137)));
+    }
+
+    #[test]
+    fn test_merge_remove_state() {
+        let date = |d| NaiveDate::from_ymd_opt(2024, 1, d).unwrap();
+        let mut state = RemoveState::Present;
+        merge_remove_state(&mut state, date(1)).unwrap();
+        assert!(matches!(state, RemoveState::Removed(d) if d == date(1)));
+        merge_remove_state(&mut state, date(1)).unwrap();
+        assert!(merge_remove_state(&mut state, date(2)).is_err());
+
+        let mut state = RemoveState::Revived(date(1), date(2));
+        assert!(merge_remove_state(&mut state, date(1)).is_err());
+    }
+
+    #[test]
+    fn test_merge_options() {
+        let mut x = None;
+        merge_options(&mut x, None).unwrap();
+        assert_eq!(x, None);
+        merge_options(&mut x, Some(&1)).unwrap();
+        assert_eq!(x, Some(1));
+        merge_options(&mut x, None).unwrap();
+        merge_options(&mut x, Some(&1)).unwrap();
+        assert!(merge_options(&mut x, Some(&2)).is_err());
+        assert_eq!(x, Some(1));
+    }
+
+    #[test]
+    fn test_merge_locked() {
+        let time = |d| {
+            NaiveDate::from_ymd_opt(2024, 1, d)
+                .unwrap()
+                .and_hms_opt(12, 0, 0)
+                .unwrap()
+        };
+        let mut history = BTreeMap::new();
+        for (d, locked) in [(1, true), (2, true), (3, false), (4, false), (5, true)] {
+            merge_locked(&mut history, time(d), locked);
+        }
+        assert_eq!(
+            history.into_iter().collect_vec(),
+            [(time(1), true), (time(3), false), (time(5), true)]
+        );
+    }
+
+    fn utage_song(kanji: &str, level: &str) -> official::Song {
+        serde_json::from_value::<official::SongRaw>(serde_json::json!({
+            "title": format!("[{kanji}]Song"),
+            "title_kana": "SONG",
+            "artist": "Artist",
+            "catcode": "宴会場",
+            "image_url": "0123456789abcdef.png",
+            "sort": "1",
+            "version": "25000",
+            "lev_utage": format!("{level}?"),
+            "comment": "comment",
+            "kanji": kanji,
+        }))
+        .unwrap()
+        .try_into()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_utage_identifier_merge() {
+        let timestamp = MaimaiVersion::Prism.start_time() + chrono::Duration::days(1);
+        let list = |songs| OfficialSongList { timestamp, songs };
+
+        // The same utage score in different lists is stored once
+        let mut results = Results::default();
+        for _ in 0..2 {
+            let songs = vec![utage_song("協", "13")];
+            results
+                .read_official_song_list(&list(songs), &HashMap::new())
+                .unwrap();
+        }
+        assert_eq!(results.songs.0.len(), 1);
+        assert_eq!(results.songs.0[0].utage_scores.len(), 1);
+
+        // ... but not in the same list
+        let mut results = Results::default();
+        let songs = vec![utage_song("協", "13"), utage_song("協", "13")];
+        assert!(results
+            .read_official_song_list(&list(songs), &HashMap::new())
+            .is_err());
+
+        // A score to be merged into another is skipped
+        let (merged, kept) = (utage_song("協", "14"), utage_song("協", "13"));
+        let (ScoreDetails::Utage(merged_score), ScoreDetails::Utage(kept_score)) =
+            (merged.details(), kept.details())
+        else {
+            unreachable!()
+        };
+        let merge = HashMap::from_iter([(
+            (merged.image().clone(), merged_score.identifier().to_owned()),
+            kept_score.identifier().to_owned(),
+        )]);
+        let mut results = Results::default();
+        let songs = vec![utage_song("協", "13"), utage_song("協", "14")];
+        results
+            .read_official_song_list(&list(songs), &merge)
+            .unwrap();
+        let scores = &results.songs.0[0].utage_scores;
+        assert_eq!(scores.len(), 1);
+        assert_eq!(scores[0].level(), ScoreLevel::new(13, false).unwrap());
+    }
+
+    const VERSION: MaimaiVersion = MaimaiVersion::Buddies;
+
+    /// A deluxe song in `VERSION` whose abbreviation is `song`.
+    fn in_lv_song() -> InLvSong {
+        serde_json::from_value::<in_lv::SongRaw>(serde_json::json!({
+            "dx": 1,
+            "v": i8::from(VERSION),
+            "lv": [-5.0, -8.0, -11.0, -13.7, 0.0],
+            "n": "Song",
+            "nn": "song",
+            "ico": "0123456789abcdef",
+        }))
+        .unwrap()
+        .try_into()
+        .unwrap()
+    }
+
+    fn key(difficulty: ScoreDifficulty) -> (MaimaiVersion, ScoreGeneration, ScoreDifficulty) {
+        (VERSION, ScoreGeneration::Deluxe, difficulty)
+    }
+
+    fn deluxe(song: &Song) -> &OrdinaryScores {
+        song.scores[ScoreGeneration::Deluxe].as_ref().unwrap()
+    }
+
+    #[test]
+    fn test_read_in_lv() {
+        let song = in_lv_song();
+        let icon: SongIcon = song.icon().clone();
+        let in_lv_level = |x: f64| in_lv::InternalScoreLevel::try_from(x).unwrap();
+        let overrides = |before| -> InLvCorrectionMap {
+            let levels = [in_lv_level(before), in_lv_level(11.2)];
+            HashMap::from_iter([(
+                icon.clone(),
+                HashMap::from_iter([(key(ScoreDifficulty::Expert), levels)]),
+            )])
+        };
+
+        let mut results = Results::default();
+        results
+            .read_in_lv(VERSION, &[in_lv_song()], Some(&overrides(-11.0)))
+            .unwrap();
+        assert_eq!(results.songs.0.len(), 1);
+        let song = &results.songs.0[0];
+        assert_eq!(song.icon, Some(icon.clone()));
+        assert_eq!(song.name[VERSION], Some("Song".to_owned().into()));
+        assert_eq!(song.abbreviation[VERSION], Some("song".to_owned().into()));
+        let scores = deluxe(song);
+        assert_eq!(scores.version, Some(VERSION));
+        assert!(scores.re_master.is_none());
+        let known = InternalScoreLevel::known(ScoreConstant::try_from(112).unwrap());
+        assert_eq!(scores.expert.levels[VERSION], Some(known));
+        let master = InternalScoreLevel::unknown(VERSION, ScoreLevel::new(13, true).unwrap());
+        assert_eq!(scores.master.levels[VERSION], Some(master));
+
+        // The level to override must match the data
+        let mut results = Results::default();
+        assert!(results
+            .read_in_lv(VERSION, &[in_lv_song()], Some(&overrides(-10.0)))
+            .is_err());
+    }
+
+    /// `in_lv_data` with the known entries of `(internal level, entry)`.
+    fn in_lv_data(known: &[(u8, &str)]) -> InLvData {
+        let mut map = serde_json::Map::new();
+        for level in 10..14 {
+            for pm in ['m', 'p'] {
+                map.insert(format!("lv{level}{pm}"), serde_json::json!([]));
+            }
+        }
+        map.insert("lv14m".to_owned(), serde_json::json!([]));
+        for level in 5..=15 {
+            let mut rows = vec![vec![]; if level == 15 { 1 } else { 10 }];
+            for &(constant, entry) in known {
+                if constant / 10 == level {
+                    // Ordered from the highest fractional part
+                    rows[9 - (constant % 10) as usize].push(entry);
+                }
+            }
+            map.insert(format!("lv{level}_rslt"), serde_json::json!(rows));
+        }
+        serde_json::from_value(serde_json::json!({
+            "unknown": map.iter().filter(|x| !x.0.ends_with("_rslt")).collect::<BTreeMap<_, _>>(),
+            "known": map.iter().filter(|x| x.0.ends_with("_rslt")).collect::<BTreeMap<_, _>>(),
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_read_in_lv_data_override() {
+        let song = in_lv_song();
+        let icon: SongIcon = song.icon().clone();
+        let known = |x: u8| InternalScoreLevel::known(ScoreConstant::try_from(x).unwrap());
+        let overrides = |before| -> InLvDataCorrectionMap {
+            let levels = [known(before), known(138)];
+            HashMap::from_iter([(
+                icon.clone(),
+                HashMap::from_iter([(key(ScoreDifficulty::Master), levels)]),
+            )])
+        };
+        let data = in_lv_data(&[(137, "<span class='wk_m'>song[dx]</span>")]);
+
+        let mut results = Results::default();
+        results.read_in_lv(VERSION, &[in_lv_song()], None).unwrap();
+        results
+            .read_in_lv_data(VERSION, &data, &overrides(137))
+            .unwrap();
+        let master = &deluxe(&results.songs.0[0]).master;
+        assert_eq!(master.levels[VERSION], Some(known(138)));
+
+        // The level to override must match the data
+        let mut results = Results::default();
+        results.read_in_lv(VERSION, &[in_lv_song()], None).unwrap();
+        assert!(results
+            .read_in_lv_data(VERSION, &data, &overrides(136))
+            .is_err());
+    }
+
+    #[test]
+    fn test_read_removed_songs_wiki() {
+        let wiki = RemovedSongsWiki::parse(
+            "\
+|>|>|>|>|>|>|>|>|>|LEFT:''2024/03/21 - 1曲2譜面''|
+|bgcolor(#ffcc00):''POPS''|[[Old Song]]|Artist| |3|6|8|11+| |150|
+|^|^|^|2|4|7|10|12| |150|
+",
+        )
+        .unwrap();
+        let mut results = Results::default();
+        results.read_removed_songs_wiki(&wiki).unwrap();
+        assert_eq!(results.songs.0.len(), 1);
+        let song = &results.songs.0[0];
+
+        // Removed at the start of Buddies+, so last available in Buddies
+        let removed = NaiveDate::from_ymd_opt(2024, 3, 21).unwrap();
+        assert!(matches!(song.remove_state, RemoveState::Removed(d) if d == removed));
+        assert_eq!(song.name[VERSION], Some("Old Song".to_owned().into()));
+        assert_eq!(song.name[MaimaiVersion::BuddiesPlus], None);
+
+        // Scores without Easy are deluxe ones
+        let unknown = |level| Some(InternalScoreLevel::unknown(VERSION, level));
+        assert!(deluxe(song).easy.is_none());
+        let master = &deluxe(song).master;
+        assert_eq!(master.levels[VERSION], unknown("11+".parse().unwrap()));
+        let standard = song.scores[ScoreGeneration::Standard].as_ref().unwrap();
+        let easy = standard.easy.as_ref().unwrap();
+        let finale = MaimaiVersion::Finale;
+        assert_eq!(
+            easy.levels[finale],
+            Some(InternalScoreLevel::unknown(finale, "2".parse().unwrap()))
+        );
+        assert_eq!(
+            standard.master.levels[VERSION],
+            unknown("12".parse().unwrap())
+        );
+        assert!(standard.re_master.is_none());
+    }
+}
@@ -0,0 +1,148 @@
+//! Parser of the list of removed songs, taken from the source of the wiki page.
+
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use fs_err::read_to_string;
+use itertools::Itertools;
+use maimai_scraping_utils::regex;
+
+use crate::maimai::rating::ScoreLevel;
+
+#[derive(Default, Debug)]
+pub struct RemovedSongsWiki {
+    pub(super) songs: Vec<RemovedSongWiki>,
+}
+#[derive(Debug)]
+pub(super) struct RemovedSongWiki {
+    pub(super) date: String,
+    #[allow(unused)]
+    genre: String,
+    pub(super) song_name: String,
+    pub(super) levels: LevelSet,
+    pub(super) another: Option<LevelSet>,
+}
+#[derive(Debug)]
+pub(super) struct LevelSet(pub(super) [Option<ScoreLevel>; 6]);
+
+impl RemovedSongsWiki {
+    pub fn read(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Self::parse(&read_to_string(path)?)
+    }
+
+    pub(super) fn parse(text: &str) -> anyhow::Result<Self> {
+        let mut songs: Vec<RemovedSongWiki> = vec![];
+        let mut current_genre = None;
+        let mut current_date = None;
+
+        for line in text.lines().filter_map(|s| s.strip_prefix('|')) {
+            let skip = [
+                "T:100%|c",
+                "center:40|center:230|center:200|CENTER:16|CENTER:17|CENTER:18|CENTER:25|CENTER:25|CENTER:25|center:30|c",
+                "center:40|center:230|center:200|CENTER:16|CENTER:17|CENTER:18|CENTER:25|CENTER:25|CENTER:25|center:30|center:40|c",
+                "!''ジャンル''|!''曲名''|!''アーティスト''|>|>|>|>|>|!center:''難易度''|!''BPM''|",
+                "!''ジャンル''|!''曲名''|!''アーティスト''|>|>|>|>|>|!center:''難易度''|!''BPM''|!''収録日''|",
+                "^|^|^|bgcolor(#00ced1):''&color(gray){Ea}''|bgcolor(#98fb98):''Ba''|bgcolor(#ffa500):''Ad''|bgcolor(#fa8080):''Ex''|bgcolor(#ee82ee):''Ma''|bgcolor(#ffceff):''Re:''|^|",
+                "^|^|^|bgcolor(#00ced1):''Ea''|bgcolor(#98fb98):''Ba''|bgcolor(#ffa500):''Ad''|bgcolor(#fa8080):''Ex''|bgcolor(#ee82ee):''Ma''|bgcolor(#ffceff):''Re:''|^|^|",
+                "center:|center:|center:|center:bgcolor(#87ceee)|bgcolor(#c0ff20):center|bgcolor(#ffe080):center|bgcolor(#ffa0c0):center|bgcolor(#e2a9f3):center|bgcolor(#ffdeff):center|c",
+                "center:|center:|center:|center:bgcolor(#87ceee)|bgcolor(#c0ff20):center|bgcolor(#ffe080):center|bgcolor(#ffa0c0):center|bgcolor(#E2A9F3):center|bgcolor(#ffdeff):center|center:|center:|c",
+                "bgcolor(#ffa07a):CENTER:|CENTER:|CENTER:|bgcolor(#ffa07a):CENTER:|bgcolor(#ffa07a):CENTER:||||bgcolor(#ffa07a):CENTER:|c",
+                "''宴''|"
+            ];
+            if skip.contains(&line) {
+                continue;
+            }
+
+            let p = regex!(
+                r"^(>\|){9,10}LEFT:''\s*(【(?<version>.*) アップデート】\s*)?(?<date>\d+/\d+/\d+) - (\d+曲|宴会場)\d+譜面(\(内.*\))?''\|$"
+            );
+            if let Some(captures) = p.captures(line) {
+                let _version = captures.name("version").map(|p| p.as_str());
+                let date = captures.name("date").unwrap().as_str();
+                current_date = Some(date);
+            } else {
+                let row = line.split('|').collect_vec();
+                if ![2, 11, 12].contains(&row.len()) {
+                    bail!("Unexpected number of rows: {row:?}");
+                }
+                if row[0] != "^" {
+                    let p = regex!(r"^bgcolor\(#[0-9a-f]{6}\):''(.*)''$");
+                    let genre = p
+                        .captures(row[0])
+                        .with_context(|| format!("Unexpected genre: {:?}", row[0]))?
+                        .get(1)
+                        .unwrap()
+                        .as_str();
+                    current_genre = Some(genre);
+                }
+                // Ignoring utage score for now
+                if row.len() == 2
+                    || current_genre == Some("宴")
+                    || row[3] == "bgcolor(#ffa07a):''星''"
+                    || row[8].ends_with('?')
+                {
+                    continue;
+                }
+                // let data: [&str; 10] = row[1..11].try_into().unwrap();
+                // let data = data.map(|s| s.to_owned());
+                if row[9].ends_with("復活") {
+                    continue;
+                }
+
+                let parse_level = |s: &str| {
+                    (["", " ", "-"].iter().all(|&t| t != s))
+                        .then(|| {
+                            let captures = regex!(
+                                r"(?x)
+                                &color\(gray\)\{
+                                    (?<level_gray> \d+ \+? )
+                                \}
+                                |
+                                    (?<level_norm> \d+ \+? )
+                            "
+                            )
+                            .captures(s)
+                            .with_context(|| format!("Unexpected level: {line:?}"))?;
+                            let c = (captures.name("level_gray"))
+                                .or(captures.name("level_norm"))
+                                .unwrap();
+                            anyhow::Ok(c.as_str().parse()?)
+                        })
+                        .transpose()
+                };
+                let levels = LevelSet(
+                    row[3..9]
+                        .iter()
+                        .map(|s| parse_level(s))
+                        .collect::<Result<Vec<_>, _>>()?
+                        .try_into()
+                        .unwrap(),
+                );
+
+                if row[1] == "^" {
+                    songs
+                        .last_mut()
+                        .with_context(|| format!("Unexpected continued `^`: {line:?}"))?
+                        .another = Some(levels);
+                } else {
+                    let song_name = regex!(r"\[\[([^>]*)(>.*)?\]\]")
+                        .captures(row[1])
+                        .with_context(|| format!("Unexpected song name: {line:?}"))?
+                        .get(1)
+                        .unwrap()
+                        .as_str()
+                        .to_owned();
+                    songs.push(RemovedSongWiki {
+                        date: current_date.context("Date missing")?.to_owned(),
+                        genre: current_genre.context("Genre missing")?.to_owned(),
+                        song_name,
+                        levels,
+                        another: None,
+                    });
+                }
+            }
+        }
+
+        Ok(Self { songs })
+    }
+}
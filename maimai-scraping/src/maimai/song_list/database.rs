@@ -8,6 +8,7 @@ use hashbrown::HashMap;
 use itertools::Itertools;
 
 use crate::maimai::{
+    achievement::NoteProfile,
     rating::InternalScoreLevel,
     schema::latest::{ScoreDifficulty, ScoreGeneration, SongIcon, SongName},
     song_list::RemoveState,
@@ -276,6 +277,10 @@ impl<'s> OrdinaryScoreRef<'s> {
             level: self.score.levels[version],
         })
     }
+
+    pub fn notes(self) -> Option<NoteProfile> {
+        self.score.notes
+    }
 }
 impl Display for OrdinaryScoreRef<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -0,0 +1,423 @@
+//! Differences between two builds of the song database.
+
+use std::{
+    collections::BTreeSet,
+    fmt::{Display, Write as _},
+};
+
+use anyhow::bail;
+use chrono::NaiveDateTime;
+use enum_iterator::all;
+use hashbrown::HashMap;
+use itertools::{EitherOrBoth, Itertools};
+use serde::Serialize;
+
+use super::{optional_enum_map::OptionalEnumMap, OrdinaryScore, OrdinaryScores, Song};
+use crate::maimai::{
+    schema::latest::{ScoreDifficulty, ScoreGeneration, SongIcon, SongName},
+    version::MaimaiVersion,
+};
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SongDbDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    /// Songs existing in both builds with at least one change.
+    pub changed: Vec<SongDiff>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SongDiff {
+    pub song: String,
+    pub changes: Vec<Change>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Change {
+    /// `field` is one of `name`, `category`, `artist`, `pronunciation` and `abbreviation`.
+    Field {
+        field: &'static str,
+        version: MaimaiVersion,
+        from: Option<String>,
+        to: Option<String>,
+    },
+    Scores {
+        generation: ScoreGeneration,
+        from: bool,
+        to: bool,
+    },
+    /// `level_changed` is false if only the internal level (candidates) changed.
+    Level {
+        generation: ScoreGeneration,
+        difficulty: ScoreDifficulty,
+        version: MaimaiVersion,
+        from: Option<String>,
+        to: Option<String>,
+        level_changed: bool,
+    },
+    RemoveState {
+        from: String,
+        to: String,
+    },
+    Locked {
+        timestamp: NaiveDateTime,
+        from: Option<bool>,
+        to: Option<bool>,
+    },
+    UtageAdded {
+        score: String,
+    },
+    UtageRemoved {
+        score: String,
+    },
+}
+
+/// How songs in the two builds are associated.
+/// Songs without an icon (only removed songs can lack one) are identified by their names.
+#[derive(PartialEq, Eq, Hash)]
+enum SongKey<'a> {
+    Icon(&'a SongIcon),
+    Name(Option<&'a SongName>),
+}
+
+impl SongDbDiff {
+    pub fn new(old: &[Song], new: &[Song]) -> anyhow::Result<Self> {
+        let old_index = index_songs(old)?;
+        let new_index = index_songs(new)?;
+
+        let mut ret = Self {
+            added: vec![],
+            removed: vec![],
+            changed: vec![],
+        };
+        for (key, old_song) in &old_index {
+            match new_index.get(key) {
+                None => ret.removed.push(song_label(old_song)),
+                Some(new_song) => {
+                    let changes = song_changes(old_song, new_song);
+                    if !changes.is_empty() {
+                        ret.changed.push(SongDiff {
+                            song: song_label(new_song),
+                            changes,
+                        });
+                    }
+                }
+            }
+        }
+        for (key, new_song) in &new_index {
+            if !old_index.contains_key(key) {
+                ret.added.push(song_label(new_song));
+            }
+        }
+        ret.added.sort();
+        ret.removed.sort();
+        ret.changed.sort_by(|x, y| x.song.cmp(&y.song));
+        Ok(ret)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+fn index_songs(songs: &[Song]) -> anyhow::Result<HashMap<SongKey<'_>, &Song>> {
+    let mut ret = HashMap::new();
+    for song in songs {
+        let key = match &song.icon {
+            Some(icon) => SongKey::Icon(icon),
+            None => SongKey::Name(song.latest_song_name()),
+        };
+        if ret.insert(key, song).is_some() {
+            bail!("Song cannot be identified: {}", song_label(song));
+        }
+    }
+    Ok(ret)
+}
+
+fn song_label(song: &Song) -> String {
+    match song.latest_song_name() {
+        Some(name) => name.to_string(),
+        None => format!("(unnamed song with icon {:?})", song.icon),
+    }
+}
+
+fn song_changes(old: &Song, new: &Song) -> Vec<Change> {
+    let mut ret = vec![];
+    field_changes(&mut ret, "name", &old.name, &new.name, |x| x.to_string());
+    field_changes(&mut ret, "category", &old.category, &new.category, |x| {
+        format!("{x:?}")
+    });
+    field_changes(&mut ret, "artist", &old.artist, &new.artist, |x| {
+        x.to_string()
+    });
+    field_changes(
+        &mut ret,
+        "pronunciation",
+        &old.pronunciation,
+        &new.pronunciation,
+        |x| x.to_string(),
+    );
+    field_changes(
+        &mut ret,
+        "abbreviation",
+        &old.abbreviation,
+        &new.abbreviation,
+        |x| x.to_string(),
+    );
+
+    for (generation, old_scores) in old.scores.iter() {
+        match (old_scores, &new.scores[generation]) {
+            (None, None) => {}
+            (Some(old), Some(new)) => level_changes(&mut ret, generation, old, new),
+            (old, new) => ret.push(Change::Scores {
+                generation,
+                from: old.is_some(),
+                to: new.is_some(),
+            }),
+        }
+    }
+
+    let (from, to) = (
+        format!("{:?}", old.remove_state),
+        format!("{:?}", new.remove_state),
+    );
+    if from != to {
+        ret.push(Change::RemoveState { from, to });
+    }
+
+    for item in (old.locked_history.iter()).merge_join_by(&new.locked_history, |x, y| x.0.cmp(y.0))
+    {
+        let (timestamp, from, to) = match item {
+            EitherOrBoth::Both((&t, &x), (_, &y)) if x != y => (t, Some(x), Some(y)),
+            EitherOrBoth::Both(..) => continue,
+            EitherOrBoth::Left((&t, &x)) => (t, Some(x), None),
+            EitherOrBoth::Right((&t, &y)) => (t, None, Some(y)),
+        };
+        ret.push(Change::Locked {
+            timestamp,
+            from,
+            to,
+        });
+    }
+
+    let utage = |song: &Song| -> BTreeSet<String> {
+        (song.utage_scores.iter())
+            .map(|x| format!("[{}] {}", x.kanji(), x.level()))
+            .collect()
+    };
+    let (old_utage, new_utage) = (utage(old), utage(new));
+    for score in new_utage.difference(&old_utage) {
+        let score = score.clone();
+        ret.push(Change::UtageAdded { score });
+    }
+    for score in old_utage.difference(&new_utage) {
+        let score = score.clone();
+        ret.push(Change::UtageRemoved { score });
+    }
+
+    ret
+}
+
+fn field_changes<T>(
+    changes: &mut Vec<Change>,
+    field: &'static str,
+    old: &OptionalEnumMap<MaimaiVersion, T>,
+    new: &OptionalEnumMap<MaimaiVersion, T>,
+    show: impl Fn(&T) -> String,
+) {
+    for version in all::<MaimaiVersion>() {
+        let from = old[version].as_ref().map(&show);
+        let to = new[version].as_ref().map(&show);
+        if from != to {
+            changes.push(Change::Field {
+                field,
+                version,
+                from,
+                to,
+            });
+        }
+    }
+}
+
+fn level_changes(
+    changes: &mut Vec<Change>,
+    generation: ScoreGeneration,
+    old: &OrdinaryScores,
+    new: &OrdinaryScores,
+) {
+    use ScoreDifficulty::*;
+    let pairs: [(_, Option<&OrdinaryScore>, Option<&OrdinaryScore>); 5] = [
+        (Basic, Some(&old.basic), Some(&new.basic)),
+        (Advanced, Some(&old.advanced), Some(&new.advanced)),
+        (Expert, Some(&old.expert), Some(&new.expert)),
+        (Master, Some(&old.master), Some(&new.master)),
+        (ReMaster, old.re_master.as_ref(), new.re_master.as_ref()),
+    ];
+    for (difficulty, old, new) in pairs {
+        for version in all::<MaimaiVersion>() {
+            let from = old.and_then(|x| x.levels[version]);
+            let to = new.and_then(|x| x.levels[version]);
+            if from == to {
+                continue;
+            }
+            let level_changed =
+                from.map(|x| x.into_level(version)) != to.map(|x| x.into_level(version));
+            changes.push(Change::Level {
+                generation,
+                difficulty,
+                version,
+                from: from.map(|x| x.to_string()),
+                to: to.map(|x| x.to_string()),
+                level_changed,
+            });
+        }
+    }
+}
+
+impl Display for SongDbDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for song in &self.added {
+            writeln!(f, "+ {song}")?;
+        }
+        for song in &self.removed {
+            writeln!(f, "- {song}")?;
+        }
+        for song in &self.changed {
+            writeln!(f, "* {}", song.song)?;
+            for change in &song.changes {
+                writeln!(f, "    {change}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let show = |x: &Option<String>| x.clone().unwrap_or_else(|| "(none)".to_owned());
+        match self {
+            Change::Field {
+                field,
+                version,
+                from,
+                to,
+            } => write!(f, "{field} ({version:?}): {} → {}", show(from), show(to)),
+            Change::Scores {
+                generation,
+                from,
+                to,
+            } => {
+                let exist = |x: bool| if x { "exists" } else { "absent" };
+                write!(
+                    f,
+                    "{} scores: {} → {}",
+                    generation.abbrev(),
+                    exist(*from),
+                    exist(*to)
+                )
+            }
+            Change::Level {
+                generation,
+                difficulty,
+                version,
+                from,
+                to,
+                level_changed,
+            } => {
+                let mut kind = String::from("internal level");
+                if *level_changed {
+                    let _ = write!(kind, " (level changed)");
+                }
+                write!(
+                    f,
+                    "{kind} of {} {} ({version:?}): {} → {}",
+                    generation.abbrev(),
+                    difficulty.abbrev(),
+                    show(from),
+                    show(to)
+                )
+            }
+            Change::RemoveState { from, to } => write!(f, "remove state: {from} → {to}"),
+            Change::Locked {
+                timestamp,
+                from,
+                to,
+            } => write!(f, "locked at {timestamp}: {from:?} → {to:?}"),
+            Change::UtageAdded { score } => write!(f, "utage added: {score}"),
+            Change::UtageRemoved { score } => write!(f, "utage removed: {score}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use crate::maimai::{
+        rating::{InternalScoreLevel, ScoreLevel},
+        schema::latest::{ScoreDifficulty, ScoreGeneration},
+        song_list::{OrdinaryScores, RemoveState, Song},
+        version::MaimaiVersion,
+    };
+
+    use super::{Change, SongDbDiff};
+
+    fn song(name: &str, icon: &str) -> Song {
+        let mut ret = Song {
+            icon: Some(
+                format!("https://maimaidx.jp/maimai-mobile/img/Music/{icon}.png")
+                    .parse()
+                    .unwrap(),
+            ),
+            ..Default::default()
+        };
+        ret.name[MaimaiVersion::Prism] = Some(name.to_owned().into());
+        ret.scores[ScoreGeneration::Deluxe] = Some(OrdinaryScores::default());
+        ret
+    }
+
+    #[test]
+    fn test_song_db_diff() {
+        let version = MaimaiVersion::Prism;
+        let level = |x: u8| {
+            let lv = ScoreLevel::new(13, true).unwrap();
+            let mut ret = InternalScoreLevel::unknown(version, lv);
+            ret.retain(|y| u8::from(y) >= x);
+            ret
+        };
+
+        let mut old = vec![song("a", "0"), song("b", "1"), song("c", "2")];
+        let mut new = vec![song("a", "0"), song("B", "1"), song("d", "3")];
+        new[0].remove_state = RemoveState::Removed(NaiveDate::from_ymd_opt(2025, 1, 1).unwrap());
+        for (song, x) in [(&mut old[1], 138), (&mut new[1], 137)] {
+            let scores = song.scores[ScoreGeneration::Deluxe].as_mut().unwrap();
+            scores.master.levels[version] = Some(level(x));
+        }
+
+        let diff = SongDbDiff::new(&old, &new).unwrap();
+        assert_eq!(diff.added, ["d"]);
+        assert_eq!(diff.removed, ["c"]);
+        let changed: Vec<_> = diff
+            .changed
+            .iter()
+            .map(|x| (&x.song[..], x.changes.len()))
+            .collect();
+        assert_eq!(changed, [("B", 2), ("a", 1)]);
+        assert!(matches!(
+            diff.changed[0].changes[..],
+            [
+                Change::Field { field: "name", .. },
+                Change::Level {
+                    difficulty: ScoreDifficulty::Master,
+                    level_changed: false,
+                    ..
+                },
+            ]
+        ));
+        assert!(matches!(
+            diff.changed[1].changes[..],
+            [Change::RemoveState { .. }]
+        ));
+    }
+}
@@ -1,6 +1,6 @@
 use std::{fmt::Debug, marker::PhantomData, path::PathBuf};
 
-use anyhow::{anyhow, bail};
+use anyhow::{anyhow, bail, Context};
 use chrono::NaiveDate;
 use derive_more::Display;
 use getset::{CopyGetters, Getters};
@@ -15,6 +15,8 @@ use crate::maimai::{
     version::MaimaiVersion,
 };
 
+use super::database::OrdinaryScoresRef;
+
 pub fn load(path: impl Into<PathBuf> + Debug) -> anyhow::Result<Vec<Song>> {
     load_impl(path)
 }
@@ -93,6 +95,53 @@ pub struct SongRaw<K = kind::Levels> {
     pub _phantom: PhantomData<fn() -> K>,
 }
 
+impl<K> SongRaw<K> {
+    /// Makes the entry of `scores` for `version`,
+    /// where `lv` are the levels from Basic to Master, followed by Re:Master if any.
+    pub fn from_scores(
+        scores: OrdinaryScoresRef,
+        version: MaimaiVersion,
+        mut lv: Vec<f64>,
+    ) -> anyhow::Result<Self> {
+        let song = scores.song();
+        let song_name = AsRef::<str>::as_ref(song.latest_song_name());
+        let dx = match scores.generation() {
+            ScoreGeneration::Standard => 0,
+            ScoreGeneration::Deluxe => 1,
+        };
+        // Some songs have special versions in the data
+        let v = i8::from(scores.scores().version.context("Missing version")?);
+        let v = if song_name == "前前前世" {
+            -v
+        } else if (song_name, scores.generation()) == ("ジングルベル", ScoreGeneration::Standard)
+        {
+            0
+        } else if version == MaimaiVersion::Maimai {
+            1
+        } else {
+            v
+        };
+        match lv.len() {
+            4 => lv.push(0.),
+            5 => {}
+            _ => bail!("Unexpected number of scores: {scores:?}"),
+        }
+        Ok(Self {
+            dx,
+            v,
+            lv,
+            n: song_name.to_owned(),
+            nn: (song.song().abbreviation.values().flatten().last()).map(|x| x.to_string()),
+            ico: (song.song().icon.as_ref())
+                .context("Song icon absent")?
+                .standard_part()
+                .context("Nonstandard icon URL")?
+                .to_owned(),
+            _phantom: PhantomData,
+        })
+    }
+}
+
 #[allow(unused)]
 #[derive(Debug, PartialEq, Eq, Getters, CopyGetters)]
 pub struct Song<K: InLvKind = kind::Levels> {
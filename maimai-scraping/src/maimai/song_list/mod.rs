@@ -8,6 +8,7 @@ use serde::{Deserialize, Serialize};
 use typed_builder::TypedBuilder;
 
 use super::{
+    achievement::NoteProfile,
     rating::{InternalScoreLevel, ScoreLevel},
     schema::latest::{
         ArtistName, Category, ScoreDifficulty, ScoreGeneration, SongIcon, SongName, UtageKindRaw,
@@ -15,10 +16,15 @@ use super::{
     version::MaimaiVersion,
 };
 
+pub mod builder;
 pub mod database;
+pub mod diff;
 pub mod in_lv;
 pub mod official;
+pub mod official_snapshot;
+pub mod search;
 pub mod song_score;
+pub mod validation;
 
 pub mod optional_enum_map {
     use std::fmt::Debug;
@@ -96,6 +102,18 @@ impl Song {
     pub fn latest_pronunciation(&self) -> Option<&SongKana> {
         self.pronunciation.values().flatten().last()
     }
+
+    /// Whether the song was locked at `time`, according to the last official song list
+    /// taken at or before `time`.
+    /// Returns `None` if no such list contains the song.
+    pub fn locked_at(&self, time: NaiveDateTime) -> Option<bool> {
+        let (_, &locked) = self.locked_history.range(..=time).next_back()?;
+        Some(locked)
+    }
+
+    pub fn latest_locked(&self) -> Option<bool> {
+        self.locked_history.values().last().copied()
+    }
 }
 
 #[derive(
@@ -130,6 +148,8 @@ impl OrdinaryScores {
 #[derive(Default, Debug, Serialize, Deserialize)]
 pub struct OrdinaryScore {
     pub levels: OptionalEnumMap<MaimaiVersion, InternalScoreLevel>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<NoteProfile>,
 }
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
@@ -0,0 +1,364 @@
+//! Timestamped snapshots of the official song list, and differences between them.
+//!
+//! A snapshot is the official JSON saved as is, named like `maimai_songs_20250101123456.json`
+//! after the time (in JST) it was downloaded.
+//! `make_song_database_new` takes these files as its `official_song_list_paths`.
+
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use hashbrown::HashMap;
+use maimai_scraping_utils::regex;
+use serde::Serialize;
+
+use super::{
+    official::{self, Levels, ScoreDetails},
+    UtageIdentifier,
+};
+use crate::maimai::{
+    rating::ScoreLevel,
+    schema::latest::{ScoreDifficulty, ScoreGeneration, SongIcon},
+};
+
+pub const DEFAULT_URL: &str = "https://maimai.sega.jp/data/maimai_songs.json";
+
+/// Downloads the official song list, returning the response body as is.
+pub async fn fetch(url: &str) -> anyhow::Result<String> {
+    let response = reqwest::get(url)
+        .await
+        .with_context(|| format!("Failed to fetch {url}"))?
+        .error_for_status()?;
+    Ok(response.text().await?)
+}
+
+/// Parses the downloaded official song list.
+pub fn parse(text: &str) -> anyhow::Result<Vec<official::Song>> {
+    let songs: Vec<official::SongRaw> = serde_json::from_str(text)?;
+    songs.into_iter().map(TryInto::try_into).collect()
+}
+
+pub fn file_name(timestamp: NaiveDateTime) -> String {
+    format!("maimai_songs_{}.json", timestamp.format("%Y%m%d%H%M%S"))
+}
+
+/// Extracts the timestamp from the file name of a snapshot.
+/// The time part is optional (as in older snapshots), and defaults to noon.
+pub fn timestamp_from_path(path: &Path) -> anyhow::Result<NaiveDateTime> {
+    let captures = regex!(r"(?x)  ^ [^0-9]*  ( [0-9]{8} ) ( [0-9]{6} )? [^0-9]* $  ")
+        .captures(
+            path.file_name()
+                .with_context(|| format!("Invalid path: {path:?}"))?
+                .to_str()
+                .with_context(|| format!("Not a UTF-8 name: {path:?}"))?,
+        )
+        .with_context(|| format!("Cannot extract timestamp from: {path:?}"))?;
+    let date = NaiveDate::parse_from_str(captures.get(1).unwrap().as_str(), "%Y%m%d")
+        .with_context(|| format!("Invalid date: {path:?}"))?;
+    let time = captures
+        .get(2)
+        .map(|c| {
+            NaiveTime::parse_from_str(c.as_str(), "%H%M%S")
+                .with_context(|| format!("Invalid time: {path:?}"))
+        })
+        .transpose()?
+        .unwrap_or_else(|| NaiveTime::from_hms_opt(12, 0, 0).unwrap());
+    Ok(date.and_time(time))
+}
+
+/// Lists the snapshots in `dir`, oldest first.
+/// Files whose names do not contain a timestamp are ignored.
+pub fn list(dir: &Path) -> anyhow::Result<Vec<(NaiveDateTime, PathBuf)>> {
+    let mut ret = vec![];
+    for entry in fs_err::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|x| x == "json") {
+            if let Ok(timestamp) = timestamp_from_path(&path) {
+                ret.push((timestamp, path));
+            }
+        }
+    }
+    ret.sort();
+    Ok(ret)
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct OfficialListDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub level_changes: Vec<LevelChange>,
+    pub lock_changes: Vec<LockChange>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct LevelChange {
+    pub song: String,
+    pub generation: ScoreGeneration,
+    pub difficulty: ScoreDifficulty,
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct LockChange {
+    pub song: String,
+    pub from: bool,
+    pub to: bool,
+}
+
+/// An entry is either the ordinary scores of a song, or an utage score.
+#[derive(PartialEq, Eq, Hash)]
+enum EntryKey<'a> {
+    Ordinary(&'a SongIcon),
+    Utage(&'a SongIcon, UtageIdentifier<'a>),
+}
+
+impl OfficialListDiff {
+    pub fn new(old: &[official::Song], new: &[official::Song]) -> Self {
+        let old_index: HashMap<_, _> = old.iter().map(|song| (entry_key(song), song)).collect();
+        let new_index: HashMap<_, _> = new.iter().map(|song| (entry_key(song), song)).collect();
+
+        let mut ret = Self::default();
+        for song in new {
+            let Some(old_song) = old_index.get(&entry_key(song)) else {
+                ret.added.push(label(song));
+                continue;
+            };
+            if old_song.locked() != song.locked() {
+                ret.lock_changes.push(LockChange {
+                    song: label(song),
+                    from: old_song.locked(),
+                    to: song.locked(),
+                });
+            }
+            if let (ScoreDetails::Ordinary(x), ScoreDetails::Ordinary(y)) =
+                (old_song.details(), song.details())
+            {
+                for (generation, x, y) in [
+                    (ScoreGeneration::Standard, x.standard(), y.standard()),
+                    (ScoreGeneration::Deluxe, x.deluxe(), y.deluxe()),
+                ] {
+                    for (difficulty, from, to) in level_pairs(x, y) {
+                        if from != to {
+                            ret.level_changes.push(LevelChange {
+                                song: label(song),
+                                generation,
+                                difficulty,
+                                from: from.map(|x| x.to_string()),
+                                to: to.map(|x| x.to_string()),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        for (key, song) in &old_index {
+            if !new_index.contains_key(key) {
+                ret.removed.push(label(song));
+            }
+        }
+        ret.added.sort();
+        ret.removed.sort();
+        ret
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.level_changes.is_empty()
+            && self.lock_changes.is_empty()
+    }
+}
+
+fn entry_key(song: &official::Song) -> EntryKey<'_> {
+    match song.details() {
+        ScoreDetails::Ordinary(_) => EntryKey::Ordinary(song.image()),
+        ScoreDetails::Utage(u) => EntryKey::Utage(song.image(), u.identifier()),
+    }
+}
+
+fn label(song: &official::Song) -> String {
+    match song.details() {
+        ScoreDetails::Ordinary(_) => song.title().to_string(),
+        ScoreDetails::Utage(u) => format!("{} (utage {})", song.title(), u.level()),
+    }
+}
+
+fn level_pairs(
+    x: Option<Levels>,
+    y: Option<Levels>,
+) -> [(ScoreDifficulty, Option<ScoreLevel>, Option<ScoreLevel>); 5] {
+    use ScoreDifficulty::*;
+    [Basic, Advanced, Expert, Master, ReMaster].map(|difficulty| {
+        let get = |levels: Option<Levels>| {
+            let levels = levels?;
+            match difficulty {
+                Basic => Some(levels.basic()),
+                Advanced => Some(levels.advanced()),
+                Expert => Some(levels.expert()),
+                Master => Some(levels.master()),
+                _ => levels.re_master(),
+            }
+        };
+        (difficulty, get(x), get(y))
+    })
+}
+
+impl Display for OfficialListDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for song in &self.added {
+            writeln!(f, "+ {song}")?;
+        }
+        for song in &self.removed {
+            writeln!(f, "- {song}")?;
+        }
+        for x in &self.level_changes {
+            let show = |x: &Option<String>| x.clone().unwrap_or_else(|| "(none)".to_owned());
+            writeln!(
+                f,
+                "* {} {} {}: {} → {}",
+                x.song,
+                x.generation.abbrev(),
+                x.difficulty.abbrev(),
+                show(&x.from),
+                show(&x.to),
+            )?;
+        }
+        for x in &self.lock_changes {
+            let state = |locked| if locked { "locked" } else { "unlocked" };
+            writeln!(f, "* {}: {} → {}", x.song, state(x.from), state(x.to))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use chrono::NaiveDate;
+    use serde_json::json;
+
+    use crate::maimai::{schema::latest::ScoreDifficulty, song_list::official};
+
+    use super::{file_name, timestamp_from_path, OfficialListDiff};
+
+    #[test]
+    fn test_file_name_round_trip() {
+        let timestamp = NaiveDate::from_ymd_opt(2025, 1, 2)
+            .unwrap()
+            .and_hms_opt(3, 4, 5)
+            .unwrap();
+        let name = file_name(timestamp);
+        assert_eq!(name, "maimai_songs_20250102030405.json");
+        assert_eq!(timestamp_from_path(Path::new(&name)).unwrap(), timestamp);
+        assert_eq!(
+            timestamp_from_path(Path::new("dir/maimai_songs_20250102.json")).unwrap(),
+            NaiveDate::from_ymd_opt(2025, 1, 2)
+                .unwrap()
+                .and_hms_opt(12, 0, 0)
+                .unwrap(),
+        );
+    }
+
+    /// A song with the given fields in the official JSON, in addition to the common ones.
+    fn song(title: &str, image: &str, fields: serde_json::Value) -> official::Song {
+        let mut raw = json!({
+            "title": title,
+            "title_kana": title,
+            "artist": "Artist",
+            "catcode": "maimai",
+            "image_url": format!("{image}.png"),
+            "sort": "1",
+            "version": "25000",
+        });
+        raw.as_object_mut()
+            .unwrap()
+            .extend(fields.as_object().unwrap().clone());
+        serde_json::from_value::<official::SongRaw>(raw)
+            .unwrap()
+            .try_into()
+            .unwrap()
+    }
+
+    fn deluxe(master: &str, re_master: Option<&str>) -> serde_json::Value {
+        json!({
+            "dx_lev_bas": "3",
+            "dx_lev_adv": "6",
+            "dx_lev_exp": "9",
+            "dx_lev_mas": master,
+            "dx_lev_remas": re_master,
+        })
+    }
+
+    fn utage(kanji: &str, level: &str) -> official::Song {
+        let fields = json!({
+            "catcode": "宴会場",
+            "lev_utage": format!("{level}?"),
+            "comment": "comment",
+            "kanji": kanji,
+        });
+        song(&format!("[{kanji}]Utage"), "utage", fields)
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = [
+            song("Changed", "changed", deluxe("12", None)),
+            song("Removed", "removed", deluxe("12", None)),
+            song("Unlocked", "unlocked", {
+                let mut fields = deluxe("13", None);
+                fields["key"] = json!("○");
+                fields
+            }),
+            utage("協", "13"),
+        ];
+        let new = [
+            utage("光", "14"),
+            song("Added", "added", deluxe("12", None)),
+            song("Changed", "changed", deluxe("12+", Some("13+"))),
+            song("Unlocked", "unlocked", deluxe("13", None)),
+            utage("協", "13"),
+        ];
+        let diff = OfficialListDiff::new(&old, &new);
+
+        assert_eq!(diff.added, ["Added", "[光]Utage (utage 14)"]);
+        assert_eq!(diff.removed, ["Removed"]);
+        let level_changes = (diff.level_changes.iter())
+            .map(|x| {
+                (
+                    &x.song[..],
+                    x.difficulty,
+                    x.from.as_deref(),
+                    x.to.as_deref(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            level_changes,
+            [
+                ("Changed", ScoreDifficulty::Master, Some("12"), Some("12+")),
+                ("Changed", ScoreDifficulty::ReMaster, None, Some("13+")),
+            ]
+        );
+        let lock_changes = (diff.lock_changes.iter())
+            .map(|x| (&x.song[..], x.from, x.to))
+            .collect::<Vec<_>>();
+        assert_eq!(lock_changes, [("Unlocked", true, false)]);
+        assert_eq!(
+            diff.to_string(),
+            "\
++ Added
++ [光]Utage (utage 14)
+- Removed
+* Changed DX Mas: 12 → 12+
+* Changed DX ReMas: (none) → 13+
+* Unlocked: locked → unlocked
+"
+        );
+        assert!(OfficialListDiff::new(&new, &new).is_empty());
+    }
+}
@@ -0,0 +1,246 @@
+//! Fuzzy search of songs by name, kana, abbreviation and artist.
+//!
+//! Both the query and the song data are normalized before comparison:
+//! full-width alphanumerics become half-width, half-width katakana become full-width,
+//! katakana become hiragana, letters are lowercased, and symbols and spaces are dropped.
+
+use std::cmp::Reverse;
+
+use anyhow::bail;
+use itertools::Itertools;
+use ordered_float::OrderedFloat;
+use serde::Serialize;
+
+use super::database::{SongDatabase, SongRef};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, strum::Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum SearchField {
+    Name,
+    Kana,
+    Abbreviation,
+    Artist,
+}
+
+impl SearchField {
+    /// Artists are shared among songs, so their matches are ranked lower.
+    fn weight(self) -> f64 {
+        match self {
+            SearchField::Name => 1.0,
+            SearchField::Kana => 0.95,
+            SearchField::Abbreviation => 0.9,
+            SearchField::Artist => 0.6,
+        }
+    }
+}
+
+pub struct SearchIndex<'s> {
+    entries: Vec<IndexEntry<'s>>,
+}
+
+struct IndexEntry<'s> {
+    song: SongRef<'s>,
+    field: SearchField,
+    text: &'s str,
+    normalized: Vec<char>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SearchHit<'s> {
+    pub song: SongRef<'s>,
+    /// In `(0, 1]`; `1` for an exact match of the normalized name.
+    pub score: f64,
+    pub field: SearchField,
+    /// The text that matched the query (before normalization).
+    pub matched: &'s str,
+}
+
+impl<'s> SearchIndex<'s> {
+    pub fn new(database: &SongDatabase<'s>) -> Self {
+        let mut entries = vec![];
+        for &song in database.songs() {
+            let mut add = |field, text: &'s str| {
+                let normalized = normalize(text).chars().collect_vec();
+                if normalized.is_empty()
+                    || entries
+                        .iter()
+                        .rev()
+                        .take_while(|e: &&IndexEntry| e.song == song)
+                        .any(|e| e.field == field && e.normalized == normalized)
+                {
+                    return;
+                }
+                entries.push(IndexEntry {
+                    song,
+                    field,
+                    text,
+                    normalized,
+                });
+            };
+            let song_data = song.song();
+            for name in song_data.name.values().flatten() {
+                add(SearchField::Name, name.as_ref());
+            }
+            for kana in song_data.pronunciation.values().flatten() {
+                add(SearchField::Kana, AsRef::<String>::as_ref(kana));
+            }
+            for abbrev in song_data.abbreviation.values().flatten() {
+                add(SearchField::Abbreviation, abbrev.as_ref());
+            }
+            for artist in song_data.artist.values().flatten() {
+                add(SearchField::Artist, artist.as_ref());
+            }
+        }
+        Self { entries }
+    }
+
+    /// Returns the songs matching `query` with a score of at least `threshold`,
+    /// the best match first.  Each song appears at most once, with its best field.
+    pub fn search(&self, query: &str, threshold: f64) -> Vec<SearchHit<'s>> {
+        let query = normalize(query).chars().collect_vec();
+        if query.is_empty() {
+            return vec![];
+        }
+        let mut hits = self
+            .entries
+            .iter()
+            .filter_map(|entry| {
+                let score = similarity(&query, &entry.normalized) * entry.field.weight();
+                (score >= threshold).then_some(SearchHit {
+                    song: entry.song,
+                    score,
+                    field: entry.field,
+                    matched: entry.text,
+                })
+            })
+            .collect_vec();
+        hits.sort_by_key(|hit| (hit.song, Reverse(OrderedFloat(hit.score))));
+        hits.dedup_by_key(|hit| hit.song);
+        hits.sort_by_key(|hit| Reverse(OrderedFloat(hit.score)));
+        hits
+    }
+
+    /// Returns the hit of the song that `query` most likely refers to.
+    /// Fails if no song matches well enough, or if the best match is not unique.
+    pub fn find_unique(&self, query: &str) -> anyhow::Result<SearchHit<'s>> {
+        let hits = self.search(query, 0.5);
+        match &hits[..] {
+            [] => bail!("No song matches {query:?}"),
+            [best, second, ..] if best.score - second.score < 1e-9 => bail!(
+                "Song not unique for {query:?}: {:?}",
+                hits.iter()
+                    .take_while(|hit| best.score - hit.score < 1e-9)
+                    .map(|hit| hit.song.latest_song_name())
+                    .collect_vec()
+            ),
+            [best, ..] => Ok(*best),
+        }
+    }
+}
+
+/// Normalizes a string for comparison as described in the module document.
+pub fn normalize(s: &str) -> String {
+    let mut ret = String::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        let mut c = match c {
+            '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap(),
+            '\u{FF61}'..='\u{FF9F}' => {
+                let mut c = HALF_WIDTH_KATAKANA
+                    .chars()
+                    .nth(c as usize - 0xFF61)
+                    .unwrap();
+                // Combine with the following (semi-)voiced sound mark
+                let offset = match chars.peek() {
+                    Some('\u{FF9E}') if c == 'ウ' => Some(0x4E),
+                    Some('\u{FF9E}')
+                        if ('カ'..='ト').contains(&c) || ('ハ'..='ホ').contains(&c) =>
+                    {
+                        Some(1)
+                    }
+                    Some('\u{FF9F}') if ('ハ'..='ホ').contains(&c) => Some(2),
+                    _ => None,
+                };
+                if let Some(offset) = offset {
+                    chars.next();
+                    c = char::from_u32(c as u32 + offset).unwrap();
+                }
+                c
+            }
+            _ => c,
+        };
+        if ('\u{30A1}'..='\u{30F6}').contains(&c) {
+            c = char::from_u32(c as u32 - 0x60).unwrap();
+        }
+        if c.is_alphanumeric() || c == 'ー' {
+            ret.extend(c.to_lowercase());
+        }
+    }
+    ret
+}
+
+/// Half-width katakana from U+FF61 to U+FF9F, in full-width.
+const HALF_WIDTH_KATAKANA: &str =
+    "。「」、・ヲァィゥェォャュョッーアイウエオカキクケコサシスセソタチツテトナニヌネノハヒフヘホマミムメモヤユヨラリルレロワン゛゜";
+
+/// Similarity of two normalized strings in `[0, 1]`.
+/// A prefix or a substring match scores high, scaled by the covered fraction;
+/// otherwise the edit distance is used.
+fn similarity(query: &[char], text: &[char]) -> f64 {
+    let coverage = query.len() as f64 / text.len().max(query.len()) as f64;
+    if query == text {
+        1.0
+    } else if text.starts_with(query) {
+        0.7 + 0.25 * coverage
+    } else if text.windows(query.len()).any(|w| w == query) {
+        0.6 + 0.25 * coverage
+    } else {
+        let distance = edit_distance(query, text);
+        0.8 * (1.0 - distance as f64 / text.len().max(query.len()) as f64)
+    }
+}
+
+fn edit_distance(x: &[char], y: &[char]) -> usize {
+    let mut dp = (0..=y.len()).collect_vec();
+    for (i, &a) in x.iter().enumerate() {
+        let mut diagonal = dp[0];
+        dp[0] = i + 1;
+        for (j, &b) in y.iter().enumerate() {
+            let next = (dp[j + 1] + 1)
+                .min(dp[j] + 1)
+                .min(diagonal + usize::from(a != b));
+            diagonal = dp[j + 1];
+            dp[j + 1] = next;
+        }
+    }
+    dp[y.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+
+    use super::{edit_distance, normalize, similarity};
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("ＡＢＣ　ｄｅｆ１２３"), "abcdef123");
+        assert_eq!(normalize("ｶﾞｸﾎﾟﾝｳﾞ"), "がくぽんゔ");
+        assert_eq!(normalize("ガクポン"), normalize("がくぽん"));
+        assert_eq!(normalize("Sweets × Sweets!!"), "sweetssweets");
+        assert_eq!(normalize("セーラー"), "せーらー");
+    }
+
+    #[test]
+    fn test_similarity() {
+        let s = |x: &str| normalize(x).chars().collect_vec();
+        assert_eq!(edit_distance(&s("kitten"), &s("sitting")), 3);
+        assert_eq!(similarity(&s("ｂｕｄｄｉｅｓ"), &s("BUDDiES")), 1.0);
+        let prefix = similarity(&s("bud"), &s("buddies"));
+        let substring = similarity(&s("ddi"), &s("buddies"));
+        let typo = similarity(&s("budides"), &s("buddies"));
+        assert!(1.0 > prefix && prefix > substring && substring > 0.5);
+        assert!(typo > 0.5 && typo < substring);
+    }
+}
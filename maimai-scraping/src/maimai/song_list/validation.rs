@@ -0,0 +1,506 @@
+//! A configurable set of rules checked over the song list.
+//!
+//! Unlike `database::verify_songs`, which stops at the first problem,
+//! every violation of every enabled rule is reported with its severity.
+
+use std::fmt::Display;
+
+use anyhow::bail;
+use hashbrown::{HashMap, HashSet};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use super::{OrdinaryScore, OrdinaryScores, RemoveState, Song};
+use crate::maimai::{schema::latest::ScoreDifficulty, version::MaimaiVersion};
+
+#[derive(
+    Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize, strum::Display,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Violation {
+    pub rule: &'static str,
+    pub severity: Severity,
+    pub song: String,
+    pub message: String,
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{}] {}: {}: {}",
+            self.severity, self.rule, self.song, self.message
+        )
+    }
+}
+
+/// A violation found by a rule, before the severity is attached.
+pub struct Finding<'s> {
+    pub song: &'s Song,
+    pub message: String,
+}
+
+pub trait Rule {
+    /// The name used in the config.
+    fn name(&self) -> &'static str;
+    fn default_severity(&self) -> Severity;
+    fn check<'s>(&self, songs: &'s [Song], findings: &mut Vec<Finding<'s>>);
+}
+
+/// Overrides of the rules.  Written in TOML as follows:
+///
+/// ```toml
+/// disabled = ["version_ordering"]
+/// [severity]
+/// abbreviation_collision = "warning"
+/// ```
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleConfig {
+    #[serde(default)]
+    pub disabled: Vec<String>,
+    #[serde(default)]
+    pub severity: HashMap<String, Severity>,
+}
+
+pub struct RuleSet {
+    rules: Vec<(Box<dyn Rule>, Severity)>,
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        let rules: [Box<dyn Rule>; 9] = [
+            Box::new(NamePresent),
+            Box::new(IconPresent),
+            Box::new(UniqueIcon),
+            Box::new(VersionPresent),
+            Box::new(LevelConsistent),
+            Box::new(NoScoreAfterRemoval),
+            Box::new(VersionOrdering),
+            Box::new(UniqueUtageIdentifier),
+            Box::new(AbbreviationCollision),
+        ];
+        let rules = rules
+            .into_iter()
+            .map(|rule| {
+                let severity = rule.default_severity();
+                (rule, severity)
+            })
+            .collect();
+        Self { rules }
+    }
+}
+
+impl RuleSet {
+    /// The default rules, with `config` applied.
+    pub fn with_config(config: &RuleConfig) -> anyhow::Result<Self> {
+        let mut ret = Self::default();
+        let names: HashSet<_> = ret.rules.iter().map(|(rule, _)| rule.name()).collect();
+        for name in chain_names(config) {
+            if !names.contains(name) {
+                bail!("Unknown rule: {name:?}");
+            }
+        }
+        ret.rules
+            .retain(|(rule, _)| !config.disabled.iter().any(|x| x == rule.name()));
+        for (rule, severity) in &mut ret.rules {
+            if let Some(&s) = config.severity.get(rule.name()) {
+                *severity = s;
+            }
+        }
+        Ok(ret)
+    }
+
+    pub fn push(&mut self, rule: Box<dyn Rule>) {
+        let severity = rule.default_severity();
+        self.rules.push((rule, severity));
+    }
+
+    pub fn rule_names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.rules.iter().map(|(rule, _)| rule.name())
+    }
+
+    /// Runs all the rules, and returns the violations, errors first.
+    pub fn run(&self, songs: &[Song]) -> Vec<Violation> {
+        let mut ret = vec![];
+        for (rule, severity) in &self.rules {
+            let mut findings = vec![];
+            rule.check(songs, &mut findings);
+            ret.extend(findings.into_iter().map(|finding| Violation {
+                rule: rule.name(),
+                severity: *severity,
+                song: song_label(finding.song),
+                message: finding.message,
+            }));
+        }
+        ret.sort_by_key(|x| std::cmp::Reverse(x.severity));
+        ret
+    }
+}
+
+fn chain_names(config: &RuleConfig) -> impl Iterator<Item = &str> {
+    (config.disabled.iter().map(|x| &x[..])).chain(config.severity.keys().map(|x| &x[..]))
+}
+
+fn song_label(song: &Song) -> String {
+    match (song.latest_song_name(), &song.icon) {
+        (Some(name), _) => name.to_string(),
+        (None, icon) => format!("(unnamed song with icon {icon:?})"),
+    }
+}
+
+fn scores_of(scores: &OrdinaryScores) -> [(ScoreDifficulty, Option<&OrdinaryScore>); 6] {
+    use ScoreDifficulty::*;
+    [
+        // Easy is not distinguished from Basic here, as it is only used by old songs
+        (Basic, scores.easy.as_ref()),
+        (Basic, Some(&scores.basic)),
+        (Advanced, Some(&scores.advanced)),
+        (Expert, Some(&scores.expert)),
+        (Master, Some(&scores.master)),
+        (ReMaster, scores.re_master.as_ref()),
+    ]
+}
+
+/// Every song has a name.
+pub struct NamePresent;
+impl Rule for NamePresent {
+    fn name(&self) -> &'static str {
+        "name_present"
+    }
+    fn default_severity(&self) -> Severity {
+        Severity::Error
+    }
+    fn check<'s>(&self, songs: &'s [Song], findings: &mut Vec<Finding<'s>>) {
+        for song in songs {
+            if song.latest_song_name().is_none() {
+                findings.push(Finding {
+                    song,
+                    message: "Song name is missing".to_owned(),
+                });
+            }
+        }
+    }
+}
+
+/// Every song that is not removed has an icon.
+pub struct IconPresent;
+impl Rule for IconPresent {
+    fn name(&self) -> &'static str {
+        "icon_present"
+    }
+    fn default_severity(&self) -> Severity {
+        Severity::Error
+    }
+    fn check<'s>(&self, songs: &'s [Song], findings: &mut Vec<Finding<'s>>) {
+        for song in songs {
+            if !song.removed() && song.icon.is_none() {
+                findings.push(Finding {
+                    song,
+                    message: "Icon is missing".to_owned(),
+                });
+            }
+        }
+    }
+}
+
+/// No two songs have the same icon.
+pub struct UniqueIcon;
+impl Rule for UniqueIcon {
+    fn name(&self) -> &'static str {
+        "unique_icon"
+    }
+    fn default_severity(&self) -> Severity {
+        Severity::Error
+    }
+    fn check<'s>(&self, songs: &'s [Song], findings: &mut Vec<Finding<'s>>) {
+        let mut icons = HashMap::new();
+        for song in songs {
+            let Some(icon) = &song.icon else { continue };
+            if let Some(other) = icons.insert(icon, song) {
+                findings.push(Finding {
+                    song,
+                    message: format!(
+                        "Same icon {icon:?} as {}",
+                        other.latest_song_name().map_or("(unnamed)", |x| x.as_ref())
+                    ),
+                });
+            }
+        }
+    }
+}
+
+/// Every score in a song that is not removed has a version.
+pub struct VersionPresent;
+impl Rule for VersionPresent {
+    fn name(&self) -> &'static str {
+        "version_present"
+    }
+    fn default_severity(&self) -> Severity {
+        Severity::Error
+    }
+    fn check<'s>(&self, songs: &'s [Song], findings: &mut Vec<Finding<'s>>) {
+        for song in songs.iter().filter(|song| !song.removed()) {
+            for (generation, scores) in &song.scores {
+                if scores.as_ref().is_some_and(|x| x.version.is_none()) {
+                    findings.push(Finding {
+                        song,
+                        message: format!("Version is missing on generation {generation:?}"),
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Every internal level has at least one candidate,
+/// and all the candidates correspond to the same level in that version.
+pub struct LevelConsistent;
+impl Rule for LevelConsistent {
+    fn name(&self) -> &'static str {
+        "level_consistent"
+    }
+    fn default_severity(&self) -> Severity {
+        Severity::Error
+    }
+    fn check<'s>(&self, songs: &'s [Song], findings: &mut Vec<Finding<'s>>) {
+        for song in songs {
+            for (generation, scores) in &song.scores {
+                let Some(scores) = scores else { continue };
+                for (difficulty, score) in scores_of(scores) {
+                    let Some(score) = score else { continue };
+                    for (version, level) in score.levels {
+                        let Some(level) = level else { continue };
+                        let message = if level.is_empty() {
+                            "Empty score level".to_owned()
+                        } else {
+                            let levels = level
+                                .candidates()
+                                .map(|x| x.to_lv(version))
+                                .dedup()
+                                .collect_vec();
+                            if levels.len() == 1 {
+                                continue;
+                            }
+                            format!("Candidates {level} span multiple levels {levels:?}")
+                        };
+                        findings.push(Finding {
+                            song,
+                            message: format!(
+                                "{message} ({generation:?} {difficulty:?} {version:?})"
+                            ),
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// No level is recorded for a version in which the song does not exist
+/// (i.e. after it is removed and before it is revived).
+pub struct NoScoreAfterRemoval;
+impl Rule for NoScoreAfterRemoval {
+    fn name(&self) -> &'static str {
+        "no_score_after_removal"
+    }
+    fn default_severity(&self) -> Severity {
+        Severity::Error
+    }
+    fn check<'s>(&self, songs: &'s [Song], findings: &mut Vec<Finding<'s>>) {
+        for song in songs {
+            let dates = match song.remove_state {
+                RemoveState::Present => continue,
+                RemoveState::Removed(x) => [Some(x), None],
+                RemoveState::Revived(x, y) => [Some(x), Some(y)],
+            };
+            if dates
+                .into_iter()
+                .flatten()
+                .any(|x| MaimaiVersion::of_date(x).is_none())
+            {
+                findings.push(Finding {
+                    song,
+                    message: format!("No version for {:?}", song.remove_state),
+                });
+                continue;
+            }
+            for (generation, scores) in &song.scores {
+                let Some(scores) = scores else { continue };
+                for (difficulty, score) in scores_of(scores) {
+                    let Some(score) = score else { continue };
+                    for (version, level) in score.levels {
+                        if level.is_some() && !song.remove_state.exist_for_version(version) {
+                            findings.push(Finding {
+                                song,
+                                message: format!(
+                                    "Level found in {version:?} after removal ({generation:?} {difficulty:?})"
+                                ),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// No level is recorded for a version before the score was added.
+pub struct VersionOrdering;
+impl Rule for VersionOrdering {
+    fn name(&self) -> &'static str {
+        "version_ordering"
+    }
+    fn default_severity(&self) -> Severity {
+        Severity::Error
+    }
+    fn check<'s>(&self, songs: &'s [Song], findings: &mut Vec<Finding<'s>>) {
+        for song in songs {
+            for (generation, scores) in &song.scores {
+                let Some(scores) = scores else { continue };
+                let Some(added) = scores.version else {
+                    continue;
+                };
+                for (difficulty, score) in scores_of(scores) {
+                    let Some(score) = score else { continue };
+                    let first = score
+                        .levels
+                        .iter()
+                        .find_map(|(version, level)| level.is_some().then_some(version));
+                    if let Some(first) = first.filter(|&first| first < added) {
+                        findings.push(Finding {
+                            song,
+                            message: format!(
+                                "Level found in {first:?}, before the score was added in {added:?} ({generation:?} {difficulty:?})"
+                            ),
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Utage scores of a song are distinguished by their identifiers.
+pub struct UniqueUtageIdentifier;
+impl Rule for UniqueUtageIdentifier {
+    fn name(&self) -> &'static str {
+        "unique_utage_identifier"
+    }
+    fn default_severity(&self) -> Severity {
+        Severity::Error
+    }
+    fn check<'s>(&self, songs: &'s [Song], findings: &mut Vec<Finding<'s>>) {
+        for song in songs {
+            let mut identifiers = HashSet::new();
+            for score in &song.utage_scores {
+                if !identifiers.insert(score.identifier()) {
+                    findings.push(Finding {
+                        song,
+                        message: format!(
+                            "Duplicate utage score {} {}",
+                            score.kanji(),
+                            score.level()
+                        ),
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// No two songs share an abbreviation in the same version.
+pub struct AbbreviationCollision;
+impl Rule for AbbreviationCollision {
+    fn name(&self) -> &'static str {
+        "abbreviation_collision"
+    }
+    fn default_severity(&self) -> Severity {
+        Severity::Error
+    }
+    fn check<'s>(&self, songs: &'s [Song], findings: &mut Vec<Finding<'s>>) {
+        let mut abbrevs = HashMap::new();
+        for song in songs {
+            for (version, abbrev) in &song.abbreviation {
+                let Some(abbrev) = abbrev else { continue };
+                if let Some(other) = abbrevs.insert((version, abbrev), song) {
+                    findings.push(Finding {
+                        song,
+                        message: format!(
+                            "Abbreviation {abbrev:?} in {version:?} is also used by {}",
+                            song_label(other)
+                        ),
+                    });
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::maimai::{
+        rating::{InternalScoreLevel, ScoreLevel},
+        schema::latest::ScoreGeneration,
+        song_list::{OrdinaryScores, Song},
+        version::MaimaiVersion,
+    };
+
+    use super::{RuleConfig, RuleSet, Severity};
+
+    fn song(name: &str, abbrev: &str) -> Song {
+        let mut ret = Song {
+            icon: Some(
+                format!("https://maimaidx.jp/maimai-mobile/img/Music/{name}.png")
+                    .parse()
+                    .unwrap(),
+            ),
+            ..Default::default()
+        };
+        ret.name[MaimaiVersion::Prism] = Some(name.to_owned().into());
+        ret.abbreviation[MaimaiVersion::Prism] = Some(abbrev.to_owned().into());
+        ret
+    }
+
+    #[test]
+    fn test_rule_set() {
+        let mut songs = vec![song("a", "x"), song("b", "x")];
+        let mut scores = OrdinaryScores {
+            version: Some(MaimaiVersion::Prism),
+            ..Default::default()
+        };
+        let level = ScoreLevel::new(13, false).unwrap();
+        scores.master.levels[MaimaiVersion::Buddies] =
+            Some(InternalScoreLevel::unknown(MaimaiVersion::Buddies, level));
+        songs[0].scores[ScoreGeneration::Deluxe] = Some(scores);
+
+        let violations = RuleSet::default().run(&songs);
+        let rules: Vec<_> = violations.iter().map(|x| (x.rule, &x.song[..])).collect();
+        assert_eq!(
+            rules,
+            [("version_ordering", "a"), ("abbreviation_collision", "b")]
+        );
+
+        let config: RuleConfig = toml::from_str(
+            r#"
+            disabled = ["version_ordering"]
+            [severity]
+            abbreviation_collision = "warning"
+            "#,
+        )
+        .unwrap();
+        let violations = RuleSet::with_config(&config).unwrap().run(&songs);
+        let rules: Vec<_> = violations.iter().map(|x| (x.rule, x.severity)).collect();
+        assert_eq!(rules, [("abbreviation_collision", Severity::Warning)]);
+
+        let config: RuleConfig = toml::from_str(r#"disabled = ["no_such_rule"]"#).unwrap();
+        assert!(RuleSet::with_config(&config).is_err());
+    }
+}
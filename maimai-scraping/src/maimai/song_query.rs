@@ -0,0 +1,402 @@
+//! A small filter language over the scores of a version, used by `song_query`.
+//!
+//! A condition is written as `key=value`, or as `key` / `!key` for boolean keys.
+//! Ranges are written as `min..max` (inclusive), and either end can be omitted.
+//!
+//! | Key | Value |
+//! |---|---|
+//! | `lv` | Score level, e.g. `13+`, `13..14` |
+//! | `const` | Internal level, e.g. `13.7`, `13.5..` (matches if any candidate is in the range) |
+//! | `added` | Version in which the score was added, e.g. `Prism..` |
+//! | `gen` | `std` or `dx` |
+//! | `diff` | Comma-separated difficulties, e.g. `exp,mas` |
+//! | `category` | Comma-separated categories, e.g. `PopsAnime,MaimaiOriginal` |
+//! | `locked`, `removed`, `new`, `determined` | `true` or `false`, or the boolean form |
+//!
+//! `old` is the same as `!new`.
+
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::{bail, Context};
+use hashbrown::HashMap;
+use serde_json::Value;
+
+use super::{
+    internal_lv_estimator::snapshot::{ScoreKey, Snapshot},
+    rating::{InternalScoreLevel, ScoreConstant, ScoreLevel},
+    schema::latest::{Category, ScoreDifficulty, ScoreGeneration},
+    song_list::database::{OrdinaryScoreForVersionRef, SongDatabase},
+    version::MaimaiVersion,
+};
+
+/// An inclusive range whose ends may be open.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Bounds<T> {
+    pub min: Option<T>,
+    pub max: Option<T>,
+}
+
+impl<T: PartialOrd> Bounds<T> {
+    pub fn contains(&self, x: &T) -> bool {
+        self.min.as_ref().is_none_or(|min| min <= x) && self.max.as_ref().is_none_or(|max| x <= max)
+    }
+}
+
+impl<T: Clone> Bounds<T> {
+    fn parse(s: &str, parse: impl Fn(&str) -> anyhow::Result<T>) -> anyhow::Result<Self> {
+        let parse_opt = |s: &str| (!s.is_empty()).then(|| parse(s)).transpose();
+        match s.split_once("..") {
+            Some((min, max)) => Ok(Self {
+                min: parse_opt(min)?,
+                max: parse_opt(max)?,
+            }),
+            None => {
+                let x = parse(s)?;
+                Ok(Self {
+                    min: Some(x.clone()),
+                    max: Some(x),
+                })
+            }
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Condition {
+    Level(Bounds<ScoreLevel>),
+    InternalLv(Bounds<ScoreConstant>),
+    Added(Bounds<MaimaiVersion>),
+    Generation(ScoreGeneration),
+    Difficulty(Vec<ScoreDifficulty>),
+    Category(Vec<Category>),
+    Locked(bool),
+    Removed(bool),
+    New(bool),
+    Determined(bool),
+}
+
+impl FromStr for Condition {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let (key, value) = match s.split_once('=') {
+            Some((key, value)) => (key.trim(), Some(value.trim())),
+            None => match s.trim().strip_prefix('!') {
+                Some(key) => (key, Some("false")),
+                None => (s.trim(), None),
+            },
+        };
+        let flag = || -> anyhow::Result<bool> {
+            match value {
+                None | Some("true") => Ok(true),
+                Some("false") => Ok(false),
+                Some(value) => bail!("Expected true or false for {key}, found {value:?}"),
+            }
+        };
+        let value = || value.with_context(|| format!("Value is missing for {key}"));
+        Ok(match key {
+            "lv" | "level" => Self::Level(Bounds::parse(value()?, str::parse)?),
+            "const" | "internal_lv" => Self::InternalLv(Bounds::parse(value()?, parse_constant)?),
+            "added" => Self::Added(Bounds::parse(value()?, |s| {
+                s.parse().with_context(|| format!("Invalid version: {s:?}"))
+            })?),
+            "gen" | "generation" => Self::Generation(parse_generation(value()?)?),
+            "diff" | "difficulty" => Self::Difficulty(
+                value()?
+                    .split(',')
+                    .map(str::parse)
+                    .collect::<Result<_, _>>()?,
+            ),
+            "category" => Self::Category(
+                value()?
+                    .split(',')
+                    .map(parse_category)
+                    .collect::<Result<_, _>>()?,
+            ),
+            "locked" => Self::Locked(flag()?),
+            "removed" => Self::Removed(flag()?),
+            "new" => Self::New(flag()?),
+            "old" => Self::New(!flag()?),
+            "determined" => Self::Determined(flag()?),
+            _ => bail!("Unknown key: {key:?}"),
+        })
+    }
+}
+
+fn parse_constant(s: &str) -> anyhow::Result<ScoreConstant> {
+    let (x, y) = s.split_once('.').unwrap_or((s, "0"));
+    let (x, y): (u8, u8) = (x.parse()?, y.parse()?);
+    if y >= 10 {
+        bail!("Invalid internal level: {s:?}");
+    }
+    x.checked_mul(10)
+        .and_then(|x| x.checked_add(y))
+        .and_then(|x| ScoreConstant::try_from(x).ok())
+        .with_context(|| format!("Invalid internal level: {s:?}"))
+}
+
+fn parse_generation(s: &str) -> anyhow::Result<ScoreGeneration> {
+    Ok(match &s.to_lowercase()[..] {
+        "std" | "standard" => ScoreGeneration::Standard,
+        "dx" | "deluxe" => ScoreGeneration::Deluxe,
+        _ => bail!("Invalid score generation: {s:?}"),
+    })
+}
+
+fn parse_category(s: &str) -> anyhow::Result<Category> {
+    use Category::*;
+    let normalized = s.replace('_', "").to_lowercase();
+    [
+        GamesVariety,
+        PopsAnime,
+        MaimaiOriginal,
+        NiconicoVocaloid,
+        OngekiChunithm,
+        TouhouProject,
+    ]
+    .into_iter()
+    .find(|x| format!("{x:?}").to_lowercase() == normalized)
+    .with_context(|| format!("Invalid category: {s:?}"))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, strum::EnumString, strum::Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum Column {
+    Song,
+    Generation,
+    Difficulty,
+    Level,
+    InternalLv,
+    Category,
+    Artist,
+    Added,
+    Locked,
+    Removed,
+    New,
+    Determined,
+    Icon,
+}
+
+impl Column {
+    pub fn defaults() -> Vec<Self> {
+        use Column::*;
+        vec![Song, Generation, Difficulty, Level, InternalLv]
+    }
+}
+
+/// Whether a score added in `added` is counted as a new one in the rating of `version`.
+pub fn is_new(added: MaimaiVersion, version: MaimaiVersion) -> bool {
+    added >= version.new_song_threshold()
+}
+
+pub struct SongQuery {
+    version: MaimaiVersion,
+    /// Candidates taken from an estimator snapshot, overriding the levels in the database.
+    estimated: HashMap<ScoreKey, InternalScoreLevel>,
+}
+
+impl SongQuery {
+    pub fn new(version: MaimaiVersion) -> Self {
+        Self {
+            version,
+            estimated: HashMap::new(),
+        }
+    }
+
+    /// Uses the candidates in `snapshot`, which must be of the version of the query.
+    pub fn with_snapshot<LD, LL>(mut self, snapshot: &Snapshot<LD, LL>) -> anyhow::Result<Self> {
+        if snapshot.version != self.version {
+            bail!(
+                "The snapshot is for {:?}, but the query is for {:?}",
+                snapshot.version,
+                self.version
+            );
+        }
+        self.estimated = (snapshot.scores.iter())
+            .map(|score| (score.key.clone(), score.candidates))
+            .collect();
+        Ok(self)
+    }
+
+    pub fn version(&self) -> MaimaiVersion {
+        self.version
+    }
+
+    /// Scores of the version satisfying all of `conditions`, in the order of the database.
+    pub fn run<'s>(
+        &self,
+        database: &SongDatabase<'s>,
+        conditions: &[Condition],
+    ) -> Vec<OrdinaryScoreForVersionRef<'s>> {
+        database
+            .all_scores_for_version(self.version)
+            .filter(|&score| conditions.iter().all(|c| self.matches(score, c)))
+            .collect()
+    }
+
+    pub fn level(&self, score: OrdinaryScoreForVersionRef) -> Option<InternalScoreLevel> {
+        ScoreKey::new(score.score())
+            .ok()
+            .and_then(|key| self.estimated.get(&key).copied())
+            .or(score.level())
+    }
+
+    pub fn matches(&self, score: OrdinaryScoreForVersionRef, condition: &Condition) -> bool {
+        let song = score.score().scores().song().song();
+        let added = score.score().scores().scores().version;
+        let level = self.level(score);
+        match condition {
+            Condition::Level(bounds) => {
+                level.is_some_and(|x| bounds.contains(&x.into_level(self.version)))
+            }
+            Condition::InternalLv(bounds) => {
+                level.is_some_and(|x| x.candidates().any(|x| bounds.contains(&x)))
+            }
+            Condition::Added(bounds) => added.is_some_and(|x| bounds.contains(&x)),
+            Condition::Generation(generation) => score.score().scores().generation() == *generation,
+            Condition::Difficulty(difficulties) => {
+                difficulties.contains(&score.score().difficulty())
+            }
+            Condition::Category(categories) => song
+                .category
+                .values()
+                .flatten()
+                .last()
+                .is_some_and(|x| categories.contains(x)),
+            Condition::Locked(locked) => song.latest_locked().unwrap_or(false) == *locked,
+            Condition::Removed(removed) => song.removed() == *removed,
+            Condition::New(new) => added.is_some_and(|x| is_new(x, self.version) == *new),
+            Condition::Determined(determined) => {
+                level.is_some_and(|x| x.is_unique()) == *determined
+            }
+        }
+    }
+
+    /// The value of `column` for `score`; booleans are JSON booleans, and the others are strings.
+    pub fn value(&self, score: OrdinaryScoreForVersionRef, column: Column) -> Value {
+        let song = score.score().scores().song();
+        let added = score.score().scores().scores().version;
+        let level = self.level(score);
+        let string = |x: Option<String>| Value::String(x.unwrap_or_default());
+        match column {
+            Column::Song => string(Some(song.latest_song_name().to_string())),
+            Column::Generation => string(Some(
+                score.score().scores().generation().abbrev().to_owned(),
+            )),
+            Column::Difficulty => string(Some(score.score().difficulty().abbrev().to_owned())),
+            Column::Level => string(level.map(|x| x.into_level(self.version).to_string())),
+            Column::InternalLv => string(level.map(|x| x.to_string())),
+            Column::Category => {
+                string((song.song().category.values().flatten().last()).map(|x| format!("{x:?}")))
+            }
+            Column::Artist => {
+                string((song.song().artist.values().flatten().last()).map(|x| x.to_string()))
+            }
+            Column::Added => string(added.map(|x| format!("{x:?}"))),
+            Column::Locked => Value::Bool(song.song().latest_locked().unwrap_or(false)),
+            Column::Removed => Value::Bool(song.song().removed()),
+            Column::New => Value::Bool(added.is_some_and(|x| is_new(x, self.version))),
+            Column::Determined => Value::Bool(level.is_some_and(|x| x.is_unique())),
+            Column::Icon => string(song.song().icon.as_ref().map(|x| x.to_string())),
+        }
+    }
+
+    pub fn rows(
+        &self,
+        scores: &[OrdinaryScoreForVersionRef],
+        columns: &[Column],
+    ) -> Vec<Vec<Value>> {
+        scores
+            .iter()
+            .map(|&score| columns.iter().map(|&c| self.value(score, c)).collect())
+            .collect()
+    }
+}
+
+/// Shows a value as a cell of a table or TSV.
+pub fn show_value(value: &Value) -> impl Display + '_ {
+    struct Show<'a>(&'a Value);
+    impl Display for Show<'_> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self.0 {
+                Value::String(s) => f.write_str(s),
+                value => write!(f, "{value}"),
+            }
+        }
+    }
+    Show(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::maimai::{
+        rating::{ScoreConstant, ScoreLevel},
+        schema::latest::{Category, ScoreDifficulty, ScoreGeneration},
+        version::MaimaiVersion,
+    };
+
+    use super::{is_new, Bounds, Condition};
+
+    #[test]
+    fn test_parse_condition() {
+        let parse = |s: &str| s.parse::<Condition>().unwrap();
+        let lv = |s: &str| s.parse::<ScoreLevel>().unwrap();
+        let constant = |x: u8| ScoreConstant::try_from(x).unwrap();
+        assert_eq!(
+            parse("lv=13+"),
+            Condition::Level(Bounds {
+                min: Some(lv("13+")),
+                max: Some(lv("13+")),
+            })
+        );
+        assert_eq!(
+            parse("lv=..14"),
+            Condition::Level(Bounds {
+                min: None,
+                max: Some(lv("14")),
+            })
+        );
+        assert_eq!(
+            parse("const=13.5..14"),
+            Condition::InternalLv(Bounds {
+                min: Some(constant(135)),
+                max: Some(constant(140)),
+            })
+        );
+        assert_eq!(
+            parse("added=Prism.."),
+            Condition::Added(Bounds {
+                min: Some(MaimaiVersion::Prism),
+                max: None,
+            })
+        );
+        assert_eq!(
+            parse("gen=DX"),
+            Condition::Generation(ScoreGeneration::Deluxe)
+        );
+        assert_eq!(
+            parse("diff=exp,mas"),
+            Condition::Difficulty(vec![ScoreDifficulty::Expert, ScoreDifficulty::Master])
+        );
+        assert_eq!(
+            parse("category=pops_anime"),
+            Condition::Category(vec![Category::PopsAnime])
+        );
+        assert_eq!(parse("locked"), Condition::Locked(true));
+        assert_eq!(parse("!determined"), Condition::Determined(false));
+        assert_eq!(parse("old"), Condition::New(false));
+        assert!("const=13.55".parse::<Condition>().is_err());
+        assert!("lv".parse::<Condition>().is_err());
+        assert!("unknown=1".parse::<Condition>().is_err());
+    }
+
+    #[test]
+    fn test_is_new() {
+        use MaimaiVersion::*;
+        assert!(is_new(Circle, CirclePlus));
+        assert!(!is_new(PrismPlus, CirclePlus));
+        assert!(is_new(PrismPlus, Circle));
+        assert!(is_new(PrismPlus, PrismPlus));
+        assert!(!is_new(Prism, PrismPlus));
+    }
+}
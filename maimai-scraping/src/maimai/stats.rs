@@ -0,0 +1,347 @@
+use std::{collections::BTreeMap, io::Write};
+
+use chrono::{Datelike, NaiveDate};
+use hashbrown::HashSet;
+use serde::Serialize;
+
+use super::{
+    associated_user_data,
+    rating::ScoreLevel,
+    schema::latest::{FullComboKind, PlayRecord},
+    sessions::{credits, SessionConfig},
+    song_list::database::ScoreForVersionRef,
+    MaimaiUserData,
+};
+
+/// Aggregate tables of play records.
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub by_day: Vec<PeriodRow>,
+    pub by_week: Vec<PeriodRow>,
+    pub by_month: Vec<PeriodRow>,
+    pub by_place: Vec<PlaceRow>,
+    pub by_song: Vec<SongRow>,
+    /// Empty unless records are associated with the song database.
+    pub by_level: Vec<LevelRow>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PeriodRow {
+    pub period: String,
+    pub plays: usize,
+    pub credits: usize,
+    pub distinct_scores: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlaceRow {
+    pub place: String,
+    pub plays: usize,
+    pub days: usize,
+    pub first_played: NaiveDate,
+    pub last_played: NaiveDate,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SongRow {
+    pub song: String,
+    pub generation: &'static str,
+    pub difficulty: &'static str,
+    pub plays: usize,
+    pub best_achievement: String,
+    pub first_played: NaiveDate,
+    pub last_played: NaiveDate,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LevelRow {
+    pub level: String,
+    pub plays: usize,
+    pub fc_rate: f64,
+    pub ap_rate: f64,
+    /// In percent
+    pub average_achievement: f64,
+}
+
+/// Identifies a table in a `Report`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, strum::EnumString, strum::Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum TableKind {
+    ByDay,
+    ByWeek,
+    ByMonth,
+    ByPlace,
+    BySong,
+    ByLevel,
+}
+impl TableKind {
+    pub fn all() -> [Self; 6] {
+        use TableKind::*;
+        [ByDay, ByWeek, ByMonth, ByPlace, BySong, ByLevel]
+    }
+}
+
+impl Report {
+    pub fn new(data: &MaimaiUserData, associated: Option<&associated_user_data::UserData>) -> Self {
+        let records = || data.records.values();
+        let date_of = |r: &PlayRecord| r.played_at().time().to_natural_date();
+        Self {
+            by_day: by_period(records(), |r| date_of(r).to_string()),
+            by_week: by_period(records(), |r| {
+                let week = date_of(r).iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            }),
+            by_month: by_period(records(), |r| date_of(r).format("%Y-%m").to_string()),
+            by_place: by_place(records()),
+            by_song: by_song(records()),
+            by_level: associated.map_or_else(Vec::new, by_level),
+        }
+    }
+
+    /// Writes the specified table as CSV (or TSV if `delimiter` is `b'\t'`).
+    pub fn write_table(
+        &self,
+        kind: TableKind,
+        writer: impl Write,
+        delimiter: u8,
+    ) -> anyhow::Result<()> {
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(delimiter)
+            .from_writer(writer);
+        macro_rules! write_rows {
+            ($rows: expr) => {
+                for row in $rows {
+                    writer.serialize(row)?;
+                }
+            };
+        }
+        match kind {
+            TableKind::ByDay => write_rows!(&self.by_day),
+            TableKind::ByWeek => write_rows!(&self.by_week),
+            TableKind::ByMonth => write_rows!(&self.by_month),
+            TableKind::ByPlace => write_rows!(&self.by_place),
+            TableKind::BySong => write_rows!(&self.by_song),
+            TableKind::ByLevel => write_rows!(&self.by_level),
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+fn by_period<'d>(
+    records: impl Iterator<Item = &'d PlayRecord>,
+    key: impl Fn(&PlayRecord) -> String,
+) -> Vec<PeriodRow> {
+    let mut groups = BTreeMap::<_, Vec<_>>::new();
+    for record in records {
+        groups.entry(key(record)).or_default().push(record);
+    }
+    groups
+        .into_iter()
+        .map(|(period, records)| {
+            let distinct_scores = records
+                .iter()
+                .map(|r| {
+                    let metadata = r.score_metadata();
+                    (
+                        r.song_metadata().cover_art(),
+                        metadata.generation(),
+                        metadata.difficulty(),
+                    )
+                })
+                .collect::<HashSet<_>>()
+                .len();
+            PeriodRow {
+                period,
+                plays: records.len(),
+                credits: credits(records.iter().copied(), SessionConfig::default()).len(),
+                distinct_scores,
+            }
+        })
+        .collect()
+}
+
+fn by_place<'d>(records: impl Iterator<Item = &'d PlayRecord>) -> Vec<PlaceRow> {
+    let mut groups = BTreeMap::<_, Vec<_>>::new();
+    for record in records {
+        let place = record
+            .played_at()
+            .place()
+            .as_ref()
+            .map_or_else(String::new, |p| p.to_string());
+        groups
+            .entry(place)
+            .or_default()
+            .push(record.played_at().time().to_natural_date());
+    }
+    let mut ret: Vec<_> = groups
+        .into_iter()
+        .map(|(place, dates)| PlaceRow {
+            place,
+            plays: dates.len(),
+            days: dates.iter().collect::<HashSet<_>>().len(),
+            // `dates` is sorted since records are
+            first_played: dates[0],
+            last_played: dates[dates.len() - 1],
+        })
+        .collect();
+    ret.sort_by_key(|row| std::cmp::Reverse(row.plays));
+    ret
+}
+
+/// Scores are identified by the cover art rather than the name,
+/// as different songs may share the same name.
+fn by_song<'d>(records: impl Iterator<Item = &'d PlayRecord>) -> Vec<SongRow> {
+    let mut groups = BTreeMap::<_, Vec<_>>::new();
+    for record in records {
+        let metadata = record.score_metadata();
+        let key = (
+            record.song_metadata().cover_art().clone(),
+            metadata.generation().abbrev(),
+            metadata.difficulty().abbrev(),
+        );
+        groups.entry(key).or_default().push(record);
+    }
+    let mut ret: Vec<_> = groups
+        .into_iter()
+        .map(|((_, generation, difficulty), records)| SongRow {
+            // The name at the last play, in case it has changed
+            song: records[records.len() - 1]
+                .song_metadata()
+                .name()
+                .to_string(),
+            generation,
+            difficulty,
+            plays: records.len(),
+            best_achievement: records
+                .iter()
+                .map(|r| r.achievement_result().value())
+                .max()
+                .unwrap()
+                .to_string(),
+            first_played: records[0].played_at().time().to_natural_date(),
+            last_played: records[records.len() - 1]
+                .played_at()
+                .time()
+                .to_natural_date(),
+        })
+        .collect();
+    ret.sort_by(|x, y| (y.plays, &x.song).cmp(&(x.plays, &y.song)));
+    ret
+}
+
+fn by_level(data: &associated_user_data::UserData) -> Vec<LevelRow> {
+    let mut groups = BTreeMap::<ScoreLevel, Vec<_>>::new();
+    for record in data.records().values() {
+        let Ok(ScoreForVersionRef::Ordinary(score)) = record.score() else {
+            continue;
+        };
+        let Some(level) = score.level() else {
+            continue;
+        };
+        groups
+            .entry(level.into_level(score.version()))
+            .or_default()
+            .push(record.record());
+    }
+    groups
+        .into_iter()
+        .map(|(level, records)| {
+            let plays = records.len();
+            let rate = |f: fn(FullComboKind) -> bool| {
+                let count = records
+                    .iter()
+                    .filter(|r| f(r.combo_result().full_combo_kind()))
+                    .count();
+                round(count as f64 / plays as f64)
+            };
+            let sum: u64 = records
+                .iter()
+                .map(|r| r.achievement_result().value().get() as u64)
+                .sum();
+            LevelRow {
+                level: level.to_string(),
+                plays,
+                fc_rate: rate(|k| k != FullComboKind::Nothing),
+                ap_rate: rate(|k| {
+                    matches!(k, FullComboKind::AllPerfect | FullComboKind::AllPerfectPlus)
+                }),
+                average_achievement: round(sum as f64 / plays as f64 / 10000.),
+            }
+        })
+        .collect()
+}
+
+fn round(x: f64) -> f64 {
+    (x * 10000.).round() / 10000.
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::maimai::schema::latest::{PlayRecord, SampleRecord, ScoreDifficulty};
+
+    use super::{by_period, by_place, by_song};
+
+    #[test]
+    fn test_tables() {
+        let records: Vec<PlayRecord> = vec![
+            SampleRecord::builder()
+                .time("2024-01-01T10:00:00")
+                .name("x")
+                .icon("x1")
+                .build(),
+            SampleRecord::builder()
+                .time("2024-01-01T10:05:00")
+                .track(2)
+                .name("x")
+                .icon("x2")
+                .achievement(99_0000)
+                .build(),
+            SampleRecord::builder()
+                .time("2024-01-01T10:10:00")
+                .track(1)
+                .name("x")
+                .icon("x1")
+                .achievement(100_5000)
+                .build(),
+            SampleRecord::builder()
+                .time("2024-01-02T20:00:00")
+                .place("other")
+                .name("x")
+                .icon("x1")
+                .difficulty(ScoreDifficulty::Expert)
+                .build(),
+        ];
+
+        let by_day = by_period(records.iter(), |r| {
+            r.played_at().time().to_natural_date().to_string()
+        });
+        let by_day: Vec<_> = by_day
+            .iter()
+            .map(|x| (&x.period[..], x.plays, x.credits, x.distinct_scores))
+            .collect();
+        assert_eq!(by_day, [("2024-01-01", 3, 2, 2), ("2024-01-02", 1, 1, 1)]);
+
+        let by_place = by_place(records.iter());
+        let by_place: Vec<_> = by_place
+            .iter()
+            .map(|x| (&x.place[..], x.plays, x.days))
+            .collect();
+        assert_eq!(by_place, [("place", 3, 1), ("other", 1, 1)]);
+
+        // The songs of the same name are distinguished by the cover art
+        let by_song = by_song(records.iter());
+        let by_song: Vec<_> = by_song
+            .iter()
+            .map(|x| (&x.song[..], x.difficulty, x.plays, &x.best_achievement[..]))
+            .collect();
+        assert_eq!(
+            by_song,
+            [
+                ("x", "Mas", 2, "100.5000%"),
+                ("x", "Exp", 1, "100.0000%"),
+                ("x", "Mas", 1, "99.0000%"),
+            ]
+        );
+    }
+}
@@ -177,4 +177,14 @@ impl MaimaiVersion {
     pub fn latest() -> Self {
         Self::CirclePlus
     }
+    /// The oldest version whose scores are counted as new ones in the rating of this version.
+    /// From Circle and later, the scores from the last two versions are new.
+    pub fn new_song_threshold(self) -> Self {
+        if self >= MaimaiVersion::Circle {
+            self.previous()
+                .expect("If self is Circle or later, there is always a previous version")
+        } else {
+            self
+        }
+    }
 }
@@ -0,0 +1,68 @@
+use std::collections::BTreeMap;
+
+use anyhow::bail;
+use getset::{CopyGetters, Getters};
+use itertools::Itertools;
+
+use super::{
+    schema::latest::{self as schema, PlayTime},
+    song_list::database::{ScoreRef, SongDatabase},
+    OngekiUserData,
+};
+
+#[derive(Getters)]
+#[getset(get = "pub")]
+pub struct UserData<'d, 's> {
+    records: BTreeMap<PlayTime, PlayRecord<'d, 's>>,
+}
+
+#[derive(CopyGetters)]
+pub struct PlayRecord<'d, 's> {
+    #[getset(get_copy = "pub")]
+    record: &'d schema::PlayRecord,
+    score: anyhow::Result<ScoreRef<'s>>,
+}
+impl<'s> PlayRecord<'_, 's> {
+    pub fn score(&self) -> Result<ScoreRef<'s>, &anyhow::Error> {
+        self.score.as_ref().copied()
+    }
+}
+
+impl<'d, 's> UserData<'d, 's> {
+    pub fn annotate(database: &SongDatabase<'s>, user_data: &'d OngekiUserData) -> Self {
+        let records = user_data
+            .records
+            .iter()
+            .map(|(&time, record)| (time, PlayRecord::annotate(database, record)))
+            .collect();
+        Self { records }
+    }
+}
+
+impl<'d, 's> PlayRecord<'d, 's> {
+    pub fn annotate(database: &SongDatabase<'s>, record: &'d schema::PlayRecord) -> Self {
+        let score = (|| {
+            let difficulty = record.score_metadata().difficulty();
+            let cover_art = record.song_metadata().cover_art();
+            let name = record.song_metadata().name();
+            match database.score_from_cover_art(cover_art, difficulty) {
+                Ok(score) => Ok(score),
+                // Cover arts may change over time, so fall back to the song name.
+                Err(e) => {
+                    let candidates = database
+                        .song_from_name(name)
+                        .filter_map(|song| song.score(difficulty))
+                        .collect_vec();
+                    match candidates[..] {
+                        [score] => Ok(score),
+                        _ => bail!(
+                            "Score could not be determined uniquely from {name:?} {difficulty:?}: \
+                            {candidates:?} ({e:#})"
+                        ),
+                    }
+                }
+            }
+        })();
+        Self { record, score }
+    }
+}
@@ -27,10 +27,13 @@ use self::{
 };
 
 pub mod aime_selection_parser;
+pub mod associated_user_data;
 pub mod friend_code_parser;
 pub mod play_record_parser;
 pub mod play_record_reconstructor;
 pub mod schema;
+pub mod song_list;
+pub mod version;
 
 pub fn check_no_loss(html: &scraper::Html, record: &PlayRecord) -> anyhow::Result<()> {
     let html_reconstructed = play_record_reconstructor::reconstruct(record);
@@ -1,6 +1,7 @@
 use chrono::{NaiveDate, NaiveDateTime};
 use deranged::RangedU8;
 use derive_more::{AsRef, Display, From, FromStr, Into};
+use enum_map::Enum;
 use getset::{CopyGetters, Getters};
 use num_format::{Locale, WriteFormatted};
 use serde::{Deserialize, Serialize};
@@ -70,11 +71,15 @@ pub struct SongMetadata {
     cover_art: SongCoverArtUrl,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug, From, AsRef, Display, Serialize, Deserialize)]
+#[derive(
+    Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, From, AsRef, Display, Serialize, Deserialize,
+)]
 #[as_ref(forward)]
 pub struct SongName(String);
 
-#[derive(Clone, PartialEq, Eq, Debug, From, FromStr, AsRef, Display, Serialize, Deserialize)]
+#[derive(
+    Clone, PartialEq, Eq, Hash, Debug, From, FromStr, AsRef, Display, Serialize, Deserialize,
+)]
 pub struct SongCoverArtUrl(Url);
 
 #[derive(PartialEq, Eq, Debug, TypedBuilder, Getters, CopyGetters, Serialize, Deserialize)]
@@ -89,7 +94,9 @@ pub struct ScoreMetadata {
 #[as_ref(forward)]
 pub struct ScoreId(String);
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[derive(
+    Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize, Enum,
+)]
 pub enum ScoreDifficulty {
     Basic,
     Advanced,
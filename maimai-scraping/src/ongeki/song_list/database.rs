@@ -0,0 +1,173 @@
+use std::fmt::Display;
+
+use anyhow::{bail, Context};
+use derive_by_key::DeriveByKey;
+use getset::CopyGetters;
+use hashbrown::{HashMap, HashSet};
+
+use crate::ongeki::schema::latest::{ScoreDifficulty, SongCoverArtUrl, SongName};
+
+use super::{Score, Song};
+
+pub struct SongDatabase<'s> {
+    songs: Vec<SongRef<'s>>,
+    cover_art_map: HashMap<&'s SongCoverArtUrl, Vec<SongRef<'s>>>,
+    name_map: HashMap<&'s SongName, Vec<SongRef<'s>>>,
+}
+impl<'s> SongDatabase<'s> {
+    pub fn new(songs: &'s [Song]) -> anyhow::Result<Self> {
+        verify_songs(songs)?;
+
+        let songs: Vec<_> = songs
+            .iter()
+            .enumerate()
+            .map(|(id, song)| SongRef { song, id })
+            .collect();
+
+        let mut cover_art_map = HashMap::<_, Vec<_>>::new();
+        let mut name_map = HashMap::<_, Vec<_>>::new();
+        for &song in &songs {
+            cover_art_map
+                .entry(&song.song.cover_art)
+                .or_default()
+                .push(song);
+            name_map.entry(&song.song.name).or_default().push(song);
+        }
+
+        Ok(Self {
+            songs,
+            cover_art_map,
+            name_map,
+        })
+    }
+
+    pub fn songs(&self) -> &[SongRef<'s>] {
+        &self.songs
+    }
+
+    /// A lunatic-only entry may share the cover art with its original song,
+    /// so the difficulty is needed to identify the score.
+    pub fn score_from_cover_art(
+        &self,
+        cover_art: &SongCoverArtUrl,
+        difficulty: ScoreDifficulty,
+    ) -> anyhow::Result<ScoreRef<'s>> {
+        let songs = self
+            .cover_art_map
+            .get(cover_art)
+            .with_context(|| format!("No song matches {cover_art:?}"))?;
+        // `verify_songs` guarantees that there is at most one candidate.
+        songs
+            .iter()
+            .find_map(|song| song.score(difficulty))
+            .with_context(|| format!("{songs:?} does not have a score for {difficulty:?}"))
+    }
+
+    pub fn song_from_cover_art<'me>(
+        &'me self,
+        cover_art: &SongCoverArtUrl,
+    ) -> impl Iterator<Item = SongRef<'s>> + 'me {
+        self.cover_art_map
+            .get(cover_art)
+            .into_iter()
+            .flatten()
+            .copied()
+    }
+
+    pub fn song_from_name<'me>(
+        &'me self,
+        song_name: &SongName,
+    ) -> impl Iterator<Item = SongRef<'s>> + 'me {
+        self.name_map.get(song_name).into_iter().flatten().copied()
+    }
+
+    pub fn all_scores<'me>(&'me self) -> impl Iterator<Item = ScoreRef<'s>> + 'me {
+        self.songs.iter().flat_map(|song| song.scores())
+    }
+}
+
+#[derive(Clone, Copy, Debug, CopyGetters, DeriveByKey)]
+#[derive_by_key(key = "key", PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[getset(get_copy = "pub")]
+pub struct SongRef<'s> {
+    song: &'s Song,
+    #[getset(get_copy = "")]
+    id: usize,
+}
+impl<'s> SongRef<'s> {
+    fn key(self) -> usize {
+        self.id
+    }
+
+    pub fn score(self, difficulty: ScoreDifficulty) -> Option<ScoreRef<'s>> {
+        Some(ScoreRef {
+            song: self,
+            difficulty,
+            score: self.song.scores[difficulty].as_ref()?,
+        })
+    }
+
+    pub fn scores(self) -> impl Iterator<Item = ScoreRef<'s>> {
+        use ScoreDifficulty::*;
+        [Basic, Advanced, Expert, Master, Lunatic]
+            .into_iter()
+            .filter_map(move |d| self.score(d))
+    }
+}
+
+/// A reference to a score of a song.
+#[derive(Clone, Copy, Debug, CopyGetters, DeriveByKey)]
+#[derive_by_key(key = "key", PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[getset(get_copy = "pub")]
+pub struct ScoreRef<'s> {
+    song: SongRef<'s>,
+    difficulty: ScoreDifficulty,
+    score: &'s Score,
+}
+impl ScoreRef<'_> {
+    fn key(self) -> (usize, ScoreDifficulty) {
+        (self.song.id, self.difficulty)
+    }
+}
+impl Display for ScoreRef<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({:?} Lv.{})",
+            self.song.song.name, self.difficulty, self.score.level,
+        )
+    }
+}
+
+pub fn verify_songs(songs: &[Song]) -> anyhow::Result<()> {
+    // A pair of cover art and difficulty identifies a score uniquely.
+    let mut keys = HashSet::new();
+    for song in songs {
+        for (difficulty, score) in &*song.scores {
+            if score.is_some() && !keys.insert((&song.cover_art, difficulty)) {
+                bail!(
+                    "Duplicate score for {:?} {difficulty:?} ({})",
+                    song.cover_art,
+                    song.name
+                );
+            }
+        }
+    }
+
+    // Known constants are consistent with displayed levels.
+    for song in songs {
+        for score in song.scores.values().flatten() {
+            if let Some(constant) = score.constant {
+                if constant.to_lv() != score.level {
+                    bail!(
+                        "{}: constant {constant} is inconsistent with level {}",
+                        song.name,
+                        score.level
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
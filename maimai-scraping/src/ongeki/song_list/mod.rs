@@ -0,0 +1,171 @@
+use anyhow::{bail, Context};
+use derive_more::{AsRef, Display, From, FromStr, Into};
+use serde::{Deserialize, Serialize};
+
+use crate::maimai::song_list::optional_enum_map::OptionalEnumMap;
+
+use super::{
+    schema::latest::{ScoreDifficulty, SongCoverArtUrl, SongName},
+    version::OngekiVersion,
+};
+
+pub mod database;
+pub mod official;
+
+/// An ongeki song has at most one score for each difficulty.
+/// Unlike maimai, there is no standard/deluxe distinction.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Song {
+    pub name: SongName,
+    pub artist: ArtistName,
+    pub category: Category,
+    pub cover_art: SongCoverArtUrl,
+    pub official_id: OfficialSongId,
+    pub version: Option<OngekiVersion>,
+    pub scores: OptionalEnumMap<ScoreDifficulty, Score>,
+}
+
+#[derive(
+    Clone, PartialEq, Eq, Hash, Debug, From, AsRef, FromStr, Display, Serialize, Deserialize,
+)]
+#[as_ref(forward)]
+pub struct ArtistName(String);
+
+#[derive(
+    Clone, PartialEq, Eq, Hash, Debug, From, AsRef, FromStr, Display, Serialize, Deserialize,
+)]
+#[as_ref(forward)]
+pub struct Category(String);
+
+#[derive(
+    Clone, PartialEq, Eq, Hash, Debug, From, AsRef, FromStr, Display, Serialize, Deserialize,
+)]
+#[as_ref(forward)]
+pub struct OfficialSongId(String);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Score {
+    pub level: ScoreLevel,
+    /// Internal level, if known.
+    pub constant: Option<ScoreConstant>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+pub struct ScoreLevel {
+    pub level: u8,
+    pub plus: bool,
+}
+
+impl ScoreLevel {
+    pub fn new(level: u8, plus: bool) -> anyhow::Result<Self> {
+        match (level, plus) {
+            (16.., _) | (0..=6 | 15, true) => {
+                bail!("Level out of range: {level}{}", if plus { "+" } else { "" })
+            }
+            _ => Ok(ScoreLevel { level, plus }),
+        }
+    }
+
+    pub fn score_constant_candidates(self) -> impl Iterator<Item = ScoreConstant> + Clone {
+        let range = match (self.level, self.plus) {
+            (a @ 7..=14, true) => a * 10 + 7..(a + 1) * 10,
+            (a @ 7..=14, false) => a * 10..a * 10 + 7,
+            (a, _) => a * 10..(a + 1) * 10,
+        };
+        range.map(ScoreConstant)
+    }
+}
+
+impl FromStr for ScoreLevel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let stripped = s.strip_suffix('+');
+        let level = stripped.unwrap_or(s).parse()?;
+        let plus = stripped.is_some();
+        Self::new(level, plus)
+    }
+}
+
+impl Display for ScoreLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let plus = if self.plus { "+" } else { "" };
+        f.pad(&format!("{}{plus}", self.level))
+    }
+}
+
+/// Internal level multiplied by 10.
+#[derive(
+    Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Into, Serialize, Deserialize,
+)]
+pub struct ScoreConstant(u8);
+
+impl TryFrom<u8> for ScoreConstant {
+    type Error = u8;
+
+    fn try_from(v: u8) -> Result<Self, u8> {
+        #[allow(clippy::inconsistent_digit_grouping)]
+        match v {
+            0_0..=15_9 => Ok(Self(v)),
+            _ => Err(v),
+        }
+    }
+}
+
+impl ScoreConstant {
+    pub fn to_lv(self) -> ScoreLevel {
+        let level = self.0 / 10;
+        ScoreLevel {
+            level,
+            plus: (7..=14).contains(&level) && self.0 % 10 >= 7,
+        }
+    }
+}
+
+impl FromStr for ScoreConstant {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let (x, y) = s
+            .split_once('.')
+            .with_context(|| format!("Constant must have a fractional part: {s:?}"))?;
+        if y.len() != 1 {
+            bail!("Constant must have exactly one fractional digit: {s:?}");
+        }
+        let (x, y): (u8, u8) = (x.parse()?, y.parse()?);
+        x.checked_mul(10)
+            .and_then(|x| x.checked_add(y))
+            .and_then(|v| Self::try_from(v).ok())
+            .with_context(|| format!("Constant out of range: {s:?}"))
+    }
+}
+
+impl Display for ScoreConstant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(&format!("{}.{}", self.0 / 10, self.0 % 10))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ScoreConstant, ScoreLevel};
+
+    #[test]
+    fn test_score_level_and_constant() {
+        let level: ScoreLevel = "13+".parse().unwrap();
+        assert_eq!(level, ScoreLevel::new(13, true).unwrap());
+        assert_eq!(level.to_string(), "13+");
+        assert!("15+".parse::<ScoreLevel>().is_err());
+        assert!("6+".parse::<ScoreLevel>().is_err());
+
+        let constant: ScoreConstant = "13.7".parse().unwrap();
+        assert_eq!(constant.to_string(), "13.7");
+        assert_eq!(constant.to_lv(), level);
+        assert!(!"13.6".parse::<ScoreConstant>().unwrap().to_lv().plus);
+        assert!(level
+            .score_constant_candidates()
+            .all(|c| c.to_lv() == level));
+        assert_eq!(level.score_constant_candidates().count(), 3);
+        assert!("13.75".parse::<ScoreConstant>().is_err());
+    }
+}
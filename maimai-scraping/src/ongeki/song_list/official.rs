@@ -0,0 +1,127 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Context};
+use chrono::NaiveDate;
+use hashbrown::HashMap;
+use log::warn;
+use maimai_scraping_utils::fs_json_util::read_json;
+use serde::Deserialize;
+
+use crate::{
+    maimai::song_list::optional_enum_map::OptionalEnumMap,
+    ongeki::{schema::latest::ScoreDifficulty, version::OngekiVersion},
+};
+
+use super::{Score, ScoreLevel, Song};
+
+/// An entry of the official music list (`music.json`).
+/// Unknown fields (new, lunatic, bonus, character, chapter, card etc.) are ignored.
+#[derive(Clone, Debug, Deserialize)]
+pub struct SongRaw {
+    pub id: String,
+    pub title: String,
+    pub title_sort: String,
+    pub artist: String,
+    pub category: String,
+    /// Release date in `yyyymmdd`
+    pub date: String,
+
+    #[serde(default)]
+    pub lev_bas: String,
+    #[serde(default)]
+    pub lev_adv: String,
+    #[serde(default)]
+    pub lev_exc: String,
+    #[serde(default)]
+    pub lev_mas: String,
+    #[serde(default)]
+    pub lev_lnt: String,
+
+    pub image_url: String,
+}
+
+impl SongRaw {
+    pub fn release_date(&self) -> anyhow::Result<NaiveDate> {
+        NaiveDate::parse_from_str(&self.date, "%Y%m%d")
+            .with_context(|| format!("While trying to parse {:?}", self.date))
+    }
+}
+
+impl TryFrom<SongRaw> for Song {
+    type Error = anyhow::Error;
+
+    fn try_from(song: SongRaw) -> anyhow::Result<Self> {
+        use ScoreDifficulty::*;
+        let version = OngekiVersion::of_date(song.release_date()?);
+        let mut scores = OptionalEnumMap::<ScoreDifficulty, Score>::default();
+        for (difficulty, level) in [
+            (Basic, &song.lev_bas),
+            (Advanced, &song.lev_adv),
+            (Expert, &song.lev_exc),
+            (Master, &song.lev_mas),
+            (Lunatic, &song.lev_lnt),
+        ] {
+            if level.is_empty() {
+                continue;
+            }
+            let level: ScoreLevel = level
+                .parse()
+                .with_context(|| format!("Invalid level for {difficulty:?}: {level:?}"))?;
+            scores[difficulty] = Some(Score {
+                level,
+                constant: None,
+            });
+        }
+        if scores.values().all(Option::is_none) {
+            bail!("Song has no score: {:?}", song.title);
+        }
+        Ok(Song {
+            name: song.title.into(),
+            artist: song.artist.into(),
+            category: song.category.into(),
+            cover_art: format!(
+                "https://ongeki-net.com/ongeki-mobile/img/music/{}",
+                song.image_url
+            )
+            .parse()?,
+            official_id: song.id.into(),
+            version,
+            scores,
+        })
+    }
+}
+
+pub fn load(path: impl Into<PathBuf>) -> anyhow::Result<Vec<Song>> {
+    read_json::<_, Vec<SongRaw>>(path.into())?
+        .into_iter()
+        .map(|song| {
+            let title = song.title.clone();
+            Song::try_from(song).with_context(|| format!("While converting {title:?}"))
+        })
+        .collect()
+}
+
+/// Copies known constants from `previous` into `songs`.
+/// A constant is carried over only if the displayed level has not changed,
+/// since a level change always implies a constant change.
+pub fn carry_over_constants(songs: &mut [Song], previous: &[Song]) {
+    let previous: HashMap<_, _> = previous.iter().map(|s| (&s.official_id, s)).collect();
+    for song in songs {
+        let Some(prev) = previous.get(&song.official_id) else {
+            continue;
+        };
+        for (difficulty, score) in &mut *song.scores {
+            let (Some(score), Some(prev_score)) = (score, &prev.scores[difficulty]) else {
+                continue;
+            };
+            if score.level == prev_score.level {
+                score.constant = prev_score.constant;
+            } else if prev_score.constant.is_some() {
+                warn!(
+                    "Level of {} {difficulty:?} changed from {} to {}; discarding constant",
+                    song.name, prev_score.level, score.level
+                );
+            }
+        }
+    }
+}
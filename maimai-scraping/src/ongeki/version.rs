@@ -0,0 +1,97 @@
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use enum_iterator::Sequence;
+use enum_map::Enum;
+use serde::{Deserialize, Serialize};
+use strum::EnumString;
+
+#[non_exhaustive]
+#[derive(
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Debug,
+    EnumString,
+    Serialize,
+    Deserialize,
+    Sequence,
+    Enum,
+)]
+pub enum OngekiVersion {
+    Ongeki,
+    OngekiPlus,
+    Summer,
+    SummerPlus,
+    Red,
+    RedPlus,
+    Bright,
+    BrightMemory,
+}
+impl OngekiVersion {
+    pub fn start_date(self) -> NaiveDate {
+        use OngekiVersion::*;
+        match self {
+            Ongeki => NaiveDate::from_ymd_opt(2018, 7, 26).unwrap(),
+            OngekiPlus => NaiveDate::from_ymd_opt(2019, 2, 7).unwrap(),
+            Summer => NaiveDate::from_ymd_opt(2019, 8, 22).unwrap(),
+            SummerPlus => NaiveDate::from_ymd_opt(2020, 2, 20).unwrap(),
+            Red => NaiveDate::from_ymd_opt(2020, 9, 30).unwrap(),
+            RedPlus => NaiveDate::from_ymd_opt(2021, 3, 31).unwrap(),
+            Bright => NaiveDate::from_ymd_opt(2021, 10, 21).unwrap(),
+            BrightMemory => NaiveDate::from_ymd_opt(2022, 3, 3).unwrap(),
+        }
+    }
+    pub fn start_time(self) -> NaiveDateTime {
+        self.start_date()
+            .and_time(NaiveTime::from_hms_opt(7, 0, 0).unwrap())
+    }
+    pub fn end_time(self) -> NaiveDateTime {
+        match self.next() {
+            Some(next) => next.start_time(),
+            None => NaiveDateTime::MAX,
+        }
+    }
+    pub fn of_time(time: NaiveDateTime) -> Option<OngekiVersion> {
+        enum_iterator::all()
+            .find(|v: &OngekiVersion| (v.start_time()..v.end_time()).contains(&time))
+    }
+    pub fn of_date(x: NaiveDate) -> Option<OngekiVersion> {
+        enum_iterator::all().find(|v: &OngekiVersion| {
+            v.start_date() <= x && v.next().is_none_or(|v| x < v.start_date())
+        })
+    }
+    pub fn latest() -> Self {
+        Self::BrightMemory
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::OngekiVersion;
+
+    #[test]
+    fn test_of_date() {
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        assert_eq!(OngekiVersion::of_date(date(2018, 7, 25)), None);
+        assert_eq!(
+            OngekiVersion::of_date(date(2018, 7, 26)),
+            Some(OngekiVersion::Ongeki)
+        );
+        assert_eq!(
+            OngekiVersion::of_date(date(2022, 3, 2)),
+            Some(OngekiVersion::Bright)
+        );
+        // The latest version is open-ended
+        assert_eq!(
+            OngekiVersion::of_date(date(2024, 1, 1)),
+            Some(OngekiVersion::BrightMemory)
+        );
+        let time = date(2022, 3, 3).and_hms_opt(6, 59, 0).unwrap();
+        assert_eq!(OngekiVersion::of_time(time), Some(OngekiVersion::Bright));
+    }
+}
@@ -1,19 +1,42 @@
-use std::{path::PathBuf, sync::mpsc, time::Duration};
+use std::{
+    path::PathBuf,
+    sync::{mpsc, Arc},
+    time::Duration,
+};
 
 use clap::Parser;
-use maimai_scraping::{cookie_store::UserIdentifier, maimai::Maimai, sega_trait::SegaTrait};
-use maimai_watcher::watch::{self, ForcePaidConfig, TimeoutConfig};
+use maimai_scraping::{
+    cookie_store::UserIdentifier, maimai::Maimai, ongeki::Ongeki, sega_trait::SegaTrait,
+};
+use maimai_watcher::{
+    notifier::Silent,
+    watch::{self, ForcePaidConfig, Game, TimeoutConfig},
+};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let opts = Opts::parse();
+    let (game, credentials_path, cookie_store_path) = if opts.ongeki {
+        (
+            Game::Ongeki,
+            Ongeki::CREDENTIALS_PATH,
+            Ongeki::COOKIE_STORE_PATH,
+        )
+    } else {
+        (
+            Game::Maimai,
+            Maimai::CREDENTIALS_PATH,
+            Maimai::COOKIE_STORE_PATH,
+        )
+    };
     let handle = watch::watch(watch::Config {
         user_id: "[[[test]]]".into(),
+        game,
         interval: Duration::from_secs(30),
-        maimai_uesr_data_path: opts.maimai_uesr_data_path,
-        slack_post_webhook: None,
-        credentials_path: PathBuf::from(Maimai::CREDENTIALS_PATH),
-        cookie_store_path: PathBuf::from(Maimai::COOKIE_STORE_PATH),
+        user_data_path: opts.user_data_path,
+        notifier: Arc::new(Silent),
+        credentials_path: PathBuf::from(credentials_path),
+        cookie_store_path: PathBuf::from(cookie_store_path),
         estimate_internal_levels: true,
         timeout_config: TimeoutConfig::indefinite(),
         report_no_updates: false,
@@ -45,10 +68,12 @@ async fn main() -> anyhow::Result<()> {
 
 #[derive(Parser)]
 struct Opts {
-    maimai_uesr_data_path: PathBuf,
+    user_data_path: PathBuf,
     #[clap(flatten)]
     user_identifier: UserIdentifier,
     #[clap(long)]
+    ongeki: bool,
+    #[clap(long)]
     international: bool,
     #[clap(long)]
     force_paid: bool,
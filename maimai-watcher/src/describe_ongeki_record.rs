@@ -0,0 +1,168 @@
+use std::fmt::Display;
+
+use lazy_format::lazy_format;
+use maimai_scraping::ongeki::{
+    associated_user_data,
+    schema::latest::{
+        BattleRank, BellResult, FullBellKind, FullComboKind, JudgeResult, PlayRecord,
+        ScoreDifficulty, TechnicalRank,
+    },
+};
+
+pub fn make_message<'a>(
+    record: &'a PlayRecord,
+    associated: Option<&associated_user_data::PlayRecord>,
+) -> impl Display + Send + 'a {
+    let time = record.played_at().time();
+    let difficulty = abbrev(record.score_metadata().difficulty());
+    let lv = match associated.and_then(|x| x.score().ok()) {
+        Some(score) => match score.score().constant {
+            Some(constant) => constant.to_string(),
+            None => score.score().level.to_string(),
+        },
+        None => "?".to_owned(),
+    };
+
+    let technical = record.technical_result();
+    let technical_rank = describe_technical_rank(technical.rank());
+    let technical_new = lazy_format!(
+        if technical.score().new_record() => " :new:"
+        else => ""
+    );
+    let battle = record.battle_result();
+    let battle_rank = describe_battle_rank(battle.rank());
+    let battle_new = lazy_format!(
+        if battle.score().new_record() => " :new:"
+        else => ""
+    );
+
+    let fc = match record.combo_result().full_combo_kind() {
+        FullComboKind::Nothing => "",
+        FullComboKind::FullCombo => " FC",
+        FullComboKind::AllBreak => " AB",
+    };
+    let fb = match record.bell_result().full_bell_kind() {
+        FullBellKind::Nothing => "",
+        FullBellKind::FullBell => " FB",
+    };
+    let barely_ab = if let Some(x) = make_barely_ab(*record.judge_result()) {
+        format!(" ({x})")
+    } else {
+        String::new()
+    };
+
+    let main_line = lazy_format!(
+        "{time}　{title} ({difficulty} Lv.{lv})　{technical_rank}({tech}{technical_new}){fc}{barely_ab}{fb}\n",
+        title = record.song_metadata().name(),
+        tech = technical.score().value(),
+    );
+    let battle_line = lazy_format!(
+        "Battle: {battle_rank}({score}{battle_new}) {win}\n",
+        score = battle.score().value(),
+        win = describe_win_or_lose(record),
+    );
+    let bell_line = lazy_format!("Bell: {}\n", describe_bells(*record.bell_result()));
+    lazy_format!("{main_line}{battle_line}{bell_line}")
+}
+
+fn abbrev(difficulty: ScoreDifficulty) -> &'static str {
+    use ScoreDifficulty::*;
+    match difficulty {
+        Basic => "Bas",
+        Advanced => "Adv",
+        Expert => "Exp",
+        Master => "Mas",
+        Lunatic => "Lun",
+    }
+}
+
+fn describe_technical_rank(rank: TechnicalRank) -> &'static str {
+    use TechnicalRank::*;
+    match rank {
+        SSSPlus => "SSS+",
+        SSS => "SSS",
+        SS => "SS",
+        S => "S",
+        AAA => "AAA",
+        AA => "AA",
+        A => "A",
+        BBB => "BBB",
+        BB => "BB",
+        B => "B",
+        C => "C",
+        D => "D",
+    }
+}
+
+fn describe_battle_rank(rank: BattleRank) -> &'static str {
+    use BattleRank::*;
+    match rank {
+        FairLose | FairCleared => "可",
+        Good => "良",
+        Great => "優",
+        Excellent => "秀",
+    }
+}
+
+fn describe_win_or_lose(record: &PlayRecord) -> &'static str {
+    use maimai_scraping::ongeki::schema::latest::WinOrLose::*;
+    match record.battle_result().win_or_lose() {
+        Win => "WIN",
+        Draw => "DRAW",
+        Lose => "LOSE",
+    }
+}
+
+fn describe_bells(bell: BellResult) -> impl Display {
+    let lost = u32::from(bell.max()) - u32::from(bell.count());
+    let lost = lazy_format!(
+        if lost > 0 => " (-{lost})"
+        else => ""
+    );
+    lazy_format!("{}/{}{lost}", bell.count(), bell.max())
+}
+
+/// Describes how many notes were missed to achieve FC or AB, if only a few.
+pub fn make_barely_ab(judge: JudgeResult) -> Option<String> {
+    const BORDER: u32 = 10;
+    let hit = u32::from(judge.hit());
+    let miss = u32::from(judge.miss());
+    let describe = |count: u32, judge_str: &str| match count {
+        0 => String::new(),
+        1 => format!("{judge_str} "),
+        _ => format!("{judge_str}{count} "),
+    };
+    let (hit_str, miss_str) = (describe(hit, "H"), describe(miss, "M"));
+    if (1..=BORDER).contains(&(hit + miss)) {
+        Some(format!("{hit_str}{miss_str}for AB"))
+    } else if (1..=BORDER).contains(&miss) {
+        Some(format!("{miss_str}for FC"))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use maimai_scraping::ongeki::schema::latest::JudgeResult;
+
+    use super::make_barely_ab;
+
+    fn judge(hit: u32, miss: u32) -> JudgeResult {
+        JudgeResult::builder()
+            .critical_break(1000.into())
+            .break_(20.into())
+            .hit(hit.into())
+            .miss(miss.into())
+            .build()
+    }
+
+    #[test]
+    fn test_make_barely_ab() {
+        assert_eq!(make_barely_ab(judge(0, 0)), None);
+        assert_eq!(make_barely_ab(judge(1, 0)).as_deref(), Some("H for AB"));
+        assert_eq!(make_barely_ab(judge(3, 2)).as_deref(), Some("H3 M2 for AB"));
+        assert_eq!(make_barely_ab(judge(20, 2)).as_deref(), Some("M2 for FC"));
+        assert_eq!(make_barely_ab(judge(20, 20)), None);
+    }
+}
@@ -3,12 +3,12 @@ use std::fmt::Display;
 use either::Either;
 use lazy_format::lazy_format;
 use maimai_scraping::maimai::{
-    associated_user_data,
+    associated_user_data, deluxscore,
     rating::{rank_coef, single_song_rating_precise, InternalScoreLevel},
     schema::{
         latest::{
             JudgeCount, JudgeCountWithoutCP, JudgeResult, LifeResult, PlayRecord,
-            RatingBorderColor, ScoreDifficulty, ScoreMetadata,
+            RatingBorderColor, ScoreDifficulty, ScoreMetadata, ValueWithMax,
         },
         ver_20210316_2338::RatingValue,
     },
@@ -151,6 +151,27 @@ pub fn describe_score_kind<'a>(metadata: ScoreMetadata) -> impl Display + 'a {
     })
 }
 
+/// Describes the star upgrade of DX score, if any, compared to `previous_best`.
+pub fn make_star_upgrade(
+    record: &PlayRecord,
+    previous_best: Option<ValueWithMax<u32>>,
+) -> Option<String> {
+    let score = record.deluxscore_result().score();
+    let new = deluxscore::stars(score).get();
+    let old = previous_best.map_or(0, |x| deluxscore::stars(x).get());
+    (new > old).then(|| {
+        let next = match deluxscore::to_next_star(score) {
+            Some((next, required)) => format!("　+{required} to ☆{next}"),
+            None => String::new(),
+        };
+        format!(
+            "DX score: ☆{old} => ☆{new} ({}/{}){next}\n",
+            score.value(),
+            score.max()
+        )
+    })
+}
+
 pub fn make_barely_fc(judge: JudgeResult, max_combo: u32) -> Option<String> {
     use JudgeCount as JC;
     let border = (max_combo / 50).max(10);
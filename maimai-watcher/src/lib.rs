@@ -1,5 +1,7 @@
+pub mod describe_ongeki_record;
 pub mod describe_record;
 pub mod misc;
+pub mod notifier;
 pub mod slack;
 pub mod slack_main;
 pub mod watch;
@@ -18,16 +18,16 @@ use maimai_scraping::{
         version::MaimaiVersion,
         Maimai,
     },
+    ongeki::{self, Ongeki},
 };
 use maimai_scraping_utils::fs_json_util::{read_json, read_toml};
-use url::Url;
 
-use crate::{describe_record::make_message, slack::webhook_send, watch::UserId};
+use crate::{
+    describe_ongeki_record, describe_record::make_message, notifier::Notifier, watch::UserId,
+};
 
-#[allow(clippy::too_many_arguments)]
 pub async fn recent(
-    client: &reqwest::Client,
-    slack_post_webhook: &Option<Url>,
+    notifier: &dyn Notifier,
     user_id: &UserId,
     user_data_path: &PathBuf,
     database_path: Option<&PathBuf>,
@@ -75,13 +75,38 @@ pub async fn recent(
             .join_with("\n")
             .to_string(),
     };
-    webhook_send(
-        client,
-        slack_post_webhook,
-        Some(user_id),
-        message.to_string(),
-    )
-    .await;
+    notifier.notify(user_id, message).await;
+    Ok(())
+}
+
+pub async fn recent_ongeki(
+    notifier: &dyn Notifier,
+    user_id: &UserId,
+    user_data_path: &PathBuf,
+    database_path: Option<&PathBuf>,
+    count: usize,
+) -> Result<(), anyhow::Error> {
+    if count > 100 {
+        bail!("Too many songs are requested!  (This is a safety guard to avoid a flood of message.  Please contact the author if you want more.)");
+    }
+
+    let songs: Option<Vec<ongeki::song_list::Song>> = database_path.map(read_json).transpose()?;
+    let database = songs
+        .as_ref()
+        .map(|songs| ongeki::song_list::database::SongDatabase::new(songs))
+        .transpose()?;
+
+    let data = load_or_create_user_data::<Ongeki, _>(user_data_path)?;
+    let message = (data.records.values().rev().take(count).rev())
+        .map(|record| {
+            let associated = database.as_ref().map(|database| {
+                ongeki::associated_user_data::PlayRecord::annotate(database, record)
+            });
+            describe_ongeki_record::make_message(record, associated.as_ref()).to_string()
+        })
+        .join_with("\n")
+        .to_string();
+    notifier.notify(user_id, message).await;
     Ok(())
 }
 
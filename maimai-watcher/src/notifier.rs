@@ -0,0 +1,275 @@
+//! Destinations of the messages from the watcher.
+
+use std::{future::Future, io::Write, path::PathBuf, pin::Pin, sync::Arc, sync::Mutex};
+
+use anyhow::Context;
+use log::error;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::watch::UserId;
+
+pub type BoxFuture<'a> = Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>>;
+
+pub trait Notifier: Send + Sync {
+    /// Sends `message` on behalf of `user_id` (or the server itself if `None`).
+    fn send<'a>(&'a self, user_id: Option<&'a UserId>, message: &'a str) -> BoxFuture<'a>;
+}
+
+impl dyn Notifier + '_ {
+    /// Sends a message, only logging the error on failure.
+    pub async fn notify(&self, user_id: impl Into<Option<&UserId>>, message: impl AsRef<str>) {
+        if let Err(e) = self.send(user_id.into(), message.as_ref()).await {
+            error!("{e:#}")
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum NotifierConfig {
+    /// Slack incoming webhook.
+    Slack { url: Url },
+    /// Discord webhook.
+    Discord { url: Url },
+    /// POSTs `{"user_id": ..., "text": ...}` to the URL.
+    Json { url: Url },
+    /// Appends a JSON line to the file for each message.
+    Jsonl { path: PathBuf },
+}
+
+impl NotifierConfig {
+    pub fn build(&self) -> Arc<dyn Notifier> {
+        let client = reqwest::Client::new();
+        match self {
+            Self::Slack { url } => Arc::new(Slack {
+                client,
+                url: url.clone(),
+            }),
+            Self::Discord { url } => Arc::new(Discord {
+                client,
+                url: url.clone(),
+            }),
+            Self::Json { url } => Arc::new(JsonPost {
+                client,
+                url: url.clone(),
+            }),
+            Self::Jsonl { path } => Arc::new(JsonlFile { path: path.clone() }),
+        }
+    }
+}
+
+fn username(user_id: Option<&UserId>) -> String {
+    match user_id {
+        None => "maimai-watcher".to_owned(),
+        Some(user_id) => format!("maimai-watcher ({user_id})"),
+    }
+}
+
+async fn post_json(
+    client: &reqwest::Client,
+    url: &Url,
+    body: &impl Serialize,
+) -> anyhow::Result<()> {
+    client
+        .post(url.clone())
+        .json(body)
+        .send()
+        .await?
+        .error_for_status()
+        .with_context(|| format!("Failed to post to {url}"))?;
+    Ok(())
+}
+
+/// Discards every message.
+pub struct Silent;
+impl Notifier for Silent {
+    fn send<'a>(&'a self, _: Option<&'a UserId>, _: &'a str) -> BoxFuture<'a> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+pub struct Slack {
+    client: reqwest::Client,
+    url: Url,
+}
+impl Notifier for Slack {
+    fn send<'a>(&'a self, user_id: Option<&'a UserId>, message: &'a str) -> BoxFuture<'a> {
+        #[derive(Serialize)]
+        struct Post<'a> {
+            text: &'a str,
+            username: String,
+        }
+        Box::pin(async move {
+            let body = Post {
+                text: message,
+                username: username(user_id),
+            };
+            post_json(&self.client, &self.url, &body).await
+        })
+    }
+}
+
+pub struct Discord {
+    client: reqwest::Client,
+    url: Url,
+}
+impl Notifier for Discord {
+    fn send<'a>(&'a self, user_id: Option<&'a UserId>, message: &'a str) -> BoxFuture<'a> {
+        #[derive(Serialize)]
+        struct Post<'a> {
+            content: &'a str,
+            username: String,
+        }
+        Box::pin(async move {
+            for content in split_message(message, DISCORD_MAX_LEN) {
+                let body = Post {
+                    content,
+                    username: username(user_id),
+                };
+                post_json(&self.client, &self.url, &body).await?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Discord rejects a message longer than this (in characters).
+const DISCORD_MAX_LEN: usize = 2000;
+
+/// Splits `message` into chunks of at most `max_len` characters, preferably at line breaks.
+fn split_message(message: &str, max_len: usize) -> Vec<&str> {
+    let mut ret = vec![];
+    let mut rest = message;
+    while rest.chars().count() > max_len {
+        let limit = rest.char_indices().nth(max_len).map_or(rest.len(), |x| x.0);
+        let end = if rest[limit..].starts_with('\n') {
+            limit
+        } else {
+            (rest[..limit].rfind('\n'))
+                .filter(|&i| i > 0)
+                .unwrap_or(limit)
+        };
+        ret.push(&rest[..end]);
+        rest = rest[end..].strip_prefix('\n').unwrap_or(&rest[end..]);
+    }
+    ret.push(rest);
+    ret
+}
+
+#[derive(Serialize)]
+struct JsonMessage<'a> {
+    user_id: Option<&'a UserId>,
+    text: &'a str,
+}
+
+pub struct JsonPost {
+    client: reqwest::Client,
+    url: Url,
+}
+impl Notifier for JsonPost {
+    fn send<'a>(&'a self, user_id: Option<&'a UserId>, message: &'a str) -> BoxFuture<'a> {
+        Box::pin(async move {
+            let body = JsonMessage {
+                user_id,
+                text: message,
+            };
+            post_json(&self.client, &self.url, &body).await
+        })
+    }
+}
+
+pub struct JsonlFile {
+    path: PathBuf,
+}
+impl Notifier for JsonlFile {
+    fn send<'a>(&'a self, user_id: Option<&'a UserId>, message: &'a str) -> BoxFuture<'a> {
+        #[derive(Serialize)]
+        struct Line<'a> {
+            time: String,
+            #[serde(flatten)]
+            message: JsonMessage<'a>,
+        }
+        Box::pin(async move {
+            let line = Line {
+                time: chrono::Local::now().to_rfc3339(),
+                message: JsonMessage {
+                    user_id,
+                    text: message,
+                },
+            };
+            let mut line = serde_json::to_string(&line)?;
+            line.push('\n');
+            fs_err::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?
+                .write_all(line.as_bytes())?;
+            Ok(())
+        })
+    }
+}
+
+/// Keeps the messages in memory, e.g. for tests.
+#[derive(Default)]
+pub struct Recorder {
+    messages: Mutex<Vec<(Option<UserId>, String)>>,
+}
+impl Recorder {
+    pub fn messages(&self) -> Vec<(Option<UserId>, String)> {
+        self.messages.lock().unwrap().clone()
+    }
+}
+impl Notifier for Recorder {
+    fn send<'a>(&'a self, user_id: Option<&'a UserId>, message: &'a str) -> BoxFuture<'a> {
+        let entry = (user_id.cloned(), message.to_owned());
+        self.messages.lock().unwrap().push(entry);
+        Box::pin(async { Ok(()) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{split_message, Notifier, NotifierConfig, Recorder};
+
+    #[test]
+    fn test_split_message() {
+        assert_eq!(split_message("abc", 5), ["abc"]);
+        assert_eq!(split_message("ab\ncd\nef", 5), ["ab\ncd", "ef"]);
+        assert_eq!(
+            split_message("あいうえおかき", 3),
+            ["あいう", "えおか", "き"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_recorder() {
+        let recorder = Arc::new(Recorder::default());
+        let notifier: Arc<dyn Notifier> = recorder.clone();
+        let user_id = "user".to_owned();
+        notifier.notify(&user_id, "Started!").await;
+        notifier.notify(None, format!("{}", 42)).await;
+        assert_eq!(
+            recorder.messages(),
+            [
+                (Some(user_id), "Started!".to_owned()),
+                (None, "42".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_config() {
+        let config: NotifierConfig = toml::from_str(
+            r#"
+            kind = "discord"
+            url = "https://discord.com/api/webhooks/x/y"
+            "#,
+        )
+        .unwrap();
+        assert!(matches!(config, NotifierConfig::Discord { .. }));
+        assert!(toml::from_str::<NotifierConfig>(r#"kind = "jsonl""#).is_err());
+    }
+}
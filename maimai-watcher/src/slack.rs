@@ -1,8 +1,6 @@
-use log::error;
+use anyhow::{anyhow, bail, Context};
+use ring::hmac;
 use serde::Serialize;
-use url::Url;
-
-use crate::watch::UserId;
 
 #[allow(unused)]
 #[derive(Serialize)]
@@ -25,31 +23,60 @@ fn in_channel(message: impl AsRef<str>) -> SlashCommandResponse {
     }
 }
 
-#[derive(Serialize)]
-struct WebhookPost<'a> {
-    text: &'a str,
-    username: String,
+/// Requests older than this (in seconds) are rejected to prevent replay attacks.
+const MAX_REQUEST_AGE: i64 = 60 * 5;
+
+/// Verifies that a request comes from Slack, following
+/// <https://api.slack.com/authentication/verifying-requests-from-slack>.
+///
+/// `timestamp` and `signature` are the values of `X-Slack-Request-Timestamp` and
+/// `X-Slack-Signature` headers, respectively, and `now` is the current UNIX time.
+pub fn verify_signature(
+    signing_secret: &str,
+    timestamp: &str,
+    signature: &str,
+    body: &[u8],
+    now: i64,
+) -> anyhow::Result<()> {
+    let time: i64 = timestamp
+        .parse()
+        .with_context(|| format!("Invalid timestamp: {timestamp:?}"))?;
+    if (now - time).abs() > MAX_REQUEST_AGE {
+        bail!("Timestamp {time} is too far from the current time {now}");
+    }
+    let signature = signature
+        .strip_prefix("v0=")
+        .and_then(|x| hex::decode(x).ok())
+        .with_context(|| format!("Malformed signature: {signature:?}"))?;
+    let key = hmac::Key::new(hmac::HMAC_SHA256, signing_secret.as_bytes());
+    let message = [format!("v0:{timestamp}:").as_bytes(), body].concat();
+    hmac::verify(&key, &message, &signature).map_err(|_| anyhow!("Signature mismatch"))
 }
 
-pub async fn webhook_send(
-    client: &reqwest::Client,
-    url: &Option<Url>,
-    user_id: impl Into<Option<&UserId>>,
-    message: impl AsRef<str>,
-) {
-    let Some(url) = url else { return };
-    if let Err(e) = client
-        .post(url.clone())
-        .json(&WebhookPost {
-            text: message.as_ref(),
-            username: match user_id.into() {
-                None => "maimai-watcher".to_owned(),
-                Some(user_id) => format!("maimai-watcher ({user_id})"),
-            },
-        })
-        .send()
-        .await
-    {
-        error!("{e:#}")
+#[cfg(test)]
+mod tests {
+    use super::verify_signature;
+
+    #[test]
+    fn test_verify_signature() {
+        // The example in the Slack documentation
+        let secret = "8f742231b10e8888abcd99yyyzzz85a5";
+        let timestamp = "1531420618";
+        let body = b"token=xyzz0WbapA4vBCDEFasx0q6G&team_id=T1DC2JH3J&team_domain=testteamnow&channel_id=G8PSS9T3V&channel_name=foobar&user_id=U2CERLKJA&user_name=roadrunner&command=%2Fwebhook-collect&text=&response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2FT1DC2JH3J%2F397700885554%2F96rGlfmibIGlgcZRskXaIFfN&trigger_id=398738663015.47445629121.803a0bc887a14d10d2c447fce8b6703c";
+        let signature = "v0=a2114d57b48eac39b9ad189dd8316235a7b4a8d21a10bd27519666489c69b503";
+        let now = 1531420618 + 10;
+
+        assert!(verify_signature(secret, timestamp, signature, body, now).is_ok());
+        // Tampered body
+        let mut tampered = body.to_vec();
+        tampered[0] = b'T';
+        assert!(verify_signature(secret, timestamp, signature, &tampered, now).is_err());
+        // Wrong secret
+        assert!(verify_signature("secret", timestamp, signature, body, now).is_err());
+        // Replayed after the window
+        assert!(verify_signature(secret, timestamp, signature, body, now + 60 * 10).is_err());
+        // Malformed
+        assert!(verify_signature(secret, timestamp, "v1=00", body, now).is_err());
+        assert!(verify_signature(secret, "abc", signature, body, now).is_err());
     }
 }
@@ -9,10 +9,10 @@ use std::{
     time::Duration,
 };
 
-use actix_web::{middleware::Logger, web, App, HttpServer, Responder};
+use actix_web::{middleware::Logger, web, App, HttpRequest, HttpResponse, HttpServer};
 use anyhow::{bail, Context};
 use clap::Parser;
-use log::{error, info};
+use log::{error, info, warn};
 use maimai_scraping::cookie_store::UserIdentifier;
 use maimai_scraping_utils::fs_json_util::read_toml;
 use serde::Deserialize;
@@ -22,8 +22,9 @@ use url::Url;
 
 use crate::{
     misc,
-    slack::webhook_send,
-    watch::{self, AimeSwitchConfig, ForcePaidConfig, TimeoutConfig, UserId, WatchHandler},
+    notifier::{Notifier, NotifierConfig, Silent},
+    slack::verify_signature,
+    watch::{self, AimeSwitchConfig, ForcePaidConfig, Game, TimeoutConfig, UserId, WatchHandler},
 };
 
 #[derive(Parser)]
@@ -37,12 +38,19 @@ struct Opts {
 pub struct Config {
     port: u16,
     webhook_endpoint: String,
+    /// Signing secret of the Slack app, used to verify that the slash commands come from Slack.
+    slack_signing_secret: String,
     interval: Duration,
 
     database_path: Option<PathBuf>,
     estimator_config_path: Option<PathBuf>,
+    #[serde(default)]
+    ongeki_database_path: Option<PathBuf>,
 
+    /// Equivalent to `notifier` of kind `slack`; ignored if `notifier` is given.
     slack_post_webhook: Option<Url>,
+    #[serde(default)]
+    notifier: Option<NotifierConfig>,
     // TODO make it getter
     pub users: HashMap<UserId, UserConfig>,
     timeout_hours: f64,
@@ -53,6 +61,8 @@ pub struct Config {
 #[serde(deny_unknown_fields)]
 pub struct UserConfig {
     slack_user_ids: Vec<String>,
+    #[serde(default)]
+    game: Game,
     credentials_path: PathBuf,
     cookie_store_path: PathBuf,
     user_data_path: PathBuf,
@@ -64,6 +74,27 @@ pub struct UserConfig {
     force_paid_config: Option<ForcePaidConfig>,
     #[serde(default)]
     aime_switch_config: Option<AimeSwitchConfig>,
+    /// Overrides the notifier of the server for this user.
+    #[serde(default)]
+    notifier: Option<NotifierConfig>,
+}
+
+impl Config {
+    /// The notifier for the messages not specific to a user.
+    fn notifier(&self) -> Arc<dyn Notifier> {
+        match (&self.notifier, &self.slack_post_webhook) {
+            (Some(notifier), _) => notifier.build(),
+            (None, Some(url)) => NotifierConfig::Slack { url: url.clone() }.build(),
+            (None, None) => Arc::new(Silent),
+        }
+    }
+
+    pub fn user_notifier(&self, user_config: &UserConfig) -> Arc<dyn Notifier> {
+        match &user_config.notifier {
+            Some(notifier) => notifier.build(),
+            None => self.notifier(),
+        }
+    }
 }
 
 pub async fn main() -> anyhow::Result<()> {
@@ -74,11 +105,8 @@ pub async fn main() -> anyhow::Result<()> {
     let port = config.port;
     let route = config.webhook_endpoint.clone();
 
-    let reqwest_client = reqwest::Client::new();
-    let url = config.slack_post_webhook.clone();
-    let webhook_send = |message: &'static str| webhook_send(&reqwest_client, &url, None, message);
-
-    webhook_send("The server has started.").await;
+    let notifier = config.notifier();
+    notifier.notify(None, "The server has started.").await;
 
     HttpServer::new(move || {
         let mut slack_id_to_user_id = HashMap::<_, Vec<_>>::new();
@@ -115,7 +143,9 @@ pub async fn main() -> anyhow::Result<()> {
     .run()
     .await?;
 
-    webhook_send("The server is about to shut down.").await;
+    notifier
+        .notify(None, "The server is about to shut down.")
+        .await;
 
     Ok(())
 }
@@ -126,14 +156,45 @@ struct State {
     watch_handler: Mutex<HashMap<UserId, WatchHandler>>,
 }
 
-async fn webhook(state: web::Data<State>, info: web::Form<SlashCommand>) -> impl Responder {
-    let client = reqwest::Client::new();
-    let url = state.config.slack_post_webhook.clone();
-    if let Err(e) = webhook_impl(state, info, &client).await {
+async fn webhook(state: web::Data<State>, req: HttpRequest, body: web::Bytes) -> HttpResponse {
+    if let Err(e) = verify_request(&state.config.slack_signing_secret, &req, &body) {
+        warn!(
+            "Rejected a request from {:?}: {e:#}",
+            req.connection_info().realip_remote_addr()
+        );
+        return HttpResponse::Unauthorized().finish();
+    }
+    let info: SlashCommand = match serde_urlencoded::from_bytes(&body) {
+        Ok(info) => info,
+        Err(e) => {
+            warn!("Malformed slash command: {e}");
+            return HttpResponse::BadRequest().finish();
+        }
+    };
+
+    let notifier = state.config.notifier();
+    if let Err(e) = webhook_impl(state, info).await {
         error!("{e:#}");
-        webhook_send(&client, &url, None, format!("{e:#}")).await;
+        notifier.notify(None, format!("{e:#}")).await;
     };
-    "done"
+    HttpResponse::Ok().body("done")
+}
+
+fn verify_request(secret: &str, req: &HttpRequest, body: &[u8]) -> anyhow::Result<()> {
+    let header = |name| {
+        req.headers()
+            .get(name)
+            .with_context(|| format!("Header {name} is missing"))?
+            .to_str()
+            .with_context(|| format!("Header {name} is not a valid string"))
+    };
+    verify_signature(
+        secret,
+        header("X-Slack-Request-Timestamp")?,
+        header("X-Slack-Signature")?,
+        body,
+        chrono::Utc::now().timestamp(),
+    )
 }
 
 mod slash_command {
@@ -172,17 +233,13 @@ mod slash_command {
     }
 }
 
-async fn webhook_impl(
-    state: web::Data<State>,
-    info: web::Form<SlashCommand>,
-    client: &reqwest::Client,
-) -> anyhow::Result<()> {
+async fn webhook_impl(state: web::Data<State>, info: SlashCommand) -> anyhow::Result<()> {
     info!("Slash command: {info:?}");
 
     macro_rules! post {
-        ($user_id: expr, $message: literal) => {
-            let url = &state.config.slack_post_webhook;
-            webhook_send(client, url, $user_id, $message).await
+        ($user_id: expr, $user_config: expr, $message: literal) => {
+            let notifier = state.config.user_notifier($user_config);
+            notifier.notify($user_id, $message).await
         };
     }
 
@@ -192,16 +249,16 @@ async fn webhook_impl(
     use HMEntry::*;
     match args.sub {
         slash_command::Sub::Stop(sub_args) => {
-            let (user_id, _) = get_user_id(&state, &info, &sub_args.user_id)?;
+            let (user_id, user_config) = get_user_id(&state, &info, &sub_args.user_id)?;
             let mut map = state.watch_handler.lock().await;
             drop_if_closed(map.entry(user_id.clone()));
             match map.entry(user_id.clone()) {
                 Occupied(entry) => {
                     entry.remove().stop().await?;
-                    post!(user_id, "Stopped!");
+                    post!(user_id, user_config, "Stopped!");
                 }
                 Vacant(_) => {
-                    post!(user_id, "Watcher is not running!");
+                    post!(user_id, user_config, "Watcher is not running!");
                 }
             }
         }
@@ -211,7 +268,7 @@ async fn webhook_impl(
             drop_if_closed(map.entry(user_id.clone()));
             match map.entry(user_id.clone()) {
                 Occupied(_) => {
-                    post!(user_id, "Watcher is already running!");
+                    post!(user_id, user_config, "Watcher is already running!");
                 }
                 Vacant(entry) => {
                     let timeout = TimeoutConfig::hours(state.config.timeout_hours);
@@ -224,7 +281,7 @@ async fn webhook_impl(
                         None,
                     );
                     entry.insert(watch::watch(config).await?);
-                    post!(user_id, "Started!");
+                    post!(user_id, user_config, "Started!");
                 }
             }
         }
@@ -245,26 +302,33 @@ async fn webhook_impl(
             let (user_id, user_config) = get_user_id(&state, &info, &sub_args.user_id)?;
             let (user_id, user_config) = (user_id.to_owned(), user_config.to_owned());
             tokio::task::spawn(async move {
-                let client = reqwest::Client::new();
-                if let Err(e) = misc::recent(
-                    &client,
-                    &config.slack_post_webhook,
-                    &user_id,
-                    &user_config.user_data_path,
-                    config.database_path.as_ref(),
-                    config.estimator_config_path.as_ref(),
-                    sub_args.count,
-                )
-                .await
-                {
+                let notifier = config.user_notifier(&user_config);
+                let res = match user_config.game {
+                    Game::Maimai => {
+                        misc::recent(
+                            &*notifier,
+                            &user_id,
+                            &user_config.user_data_path,
+                            config.database_path.as_ref(),
+                            config.estimator_config_path.as_ref(),
+                            sub_args.count,
+                        )
+                        .await
+                    }
+                    Game::Ongeki => {
+                        misc::recent_ongeki(
+                            &*notifier,
+                            &user_id,
+                            &user_config.user_data_path,
+                            config.ongeki_database_path.as_ref(),
+                            sub_args.count,
+                        )
+                        .await
+                    }
+                };
+                if let Err(e) = res {
                     error!("{e:#}");
-                    webhook_send(
-                        &client,
-                        &config.slack_post_webhook,
-                        Some(&user_id),
-                        format!("{e:#}"),
-                    )
-                    .await;
+                    notifier.notify(&user_id, format!("{e:#}")).await;
                 }
             });
         }
@@ -274,7 +338,7 @@ async fn webhook_impl(
 
 fn get_user_id<'a>(
     state: &'a web::Data<State>,
-    info: &web::Form<SlashCommand>,
+    info: &SlashCommand,
     specified_user_id: &'a Option<UserId>,
 ) -> anyhow::Result<(&'a UserId, &'a UserConfig)> {
     let allowed_users = state
@@ -324,16 +388,20 @@ pub fn watch_config(
     // } else {
     //     &state_config.levels_path
     // };
-    let database = (!user_config.international)
-        .then_some(state_config.database_path.as_ref())
-        .flatten();
+    let database = match user_config.game {
+        Game::Maimai => (!user_config.international)
+            .then_some(state_config.database_path.as_ref())
+            .flatten(),
+        Game::Ongeki => state_config.ongeki_database_path.as_ref(),
+    };
     watch::Config {
         user_id,
+        game: user_config.game,
         interval: state_config.interval,
         credentials_path: user_config.credentials_path.clone(),
         cookie_store_path: user_config.cookie_store_path.clone(),
-        maimai_uesr_data_path: user_config.user_data_path.clone(),
-        slack_post_webhook: state_config.slack_post_webhook.clone(),
+        user_data_path: user_config.user_data_path.clone(),
+        notifier: state_config.user_notifier(user_config),
         estimate_internal_levels: user_config.estimate_internal_levels,
         timeout_config,
         report_no_updates,
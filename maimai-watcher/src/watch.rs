@@ -20,10 +20,12 @@ use maimai_scraping::{
     maimai::{
         associated_user_data,
         data_collector::update_targets,
+        deluxscore,
         internal_lv_estimator::{
             multi_user::{self, MultiUserEstimator},
-            Estimator,
+            AssociatedSongScoreList, Estimator,
         },
+        lv_changes::LvChangeReport,
         parser::{
             rating_target::{RatingTargetFile, RatingTargetList},
             song_score::ScoreIdx,
@@ -33,6 +35,7 @@ use maimai_scraping::{
         version::MaimaiVersion,
         Maimai, MaimaiIntl, MaimaiUserData,
     },
+    ongeki::{self, Ongeki, OngekiUserData},
     sega_trait::{self, Idx, PlayRecordTrait, PlayedAt, SegaTrait},
 };
 use maimai_scraping_utils::fs_json_util::{read_json, read_toml, write_json};
@@ -43,23 +46,35 @@ use tokio::{
     sync::mpsc::{self, error::TryRecvError},
     time::sleep,
 };
-use url::Url;
 
-use crate::{describe_record::make_message, misc::try_get_level, slack::webhook_send};
+use crate::{
+    describe_ongeki_record,
+    describe_record::{make_message, make_star_upgrade},
+    misc::try_get_level,
+    notifier::Notifier,
+};
 
 // TODO use netype instead of alias!
 // #[derive(Clone, PartialEq, Eq, Hash, Deserialize)]
 // struct UserId(String);
 pub type UserId = String;
 
-#[derive(Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Game {
+    #[default]
+    Maimai,
+    Ongeki,
+}
+
 pub struct Config {
     pub user_id: UserId,
+    pub game: Game,
     pub interval: Duration,
     pub credentials_path: PathBuf,
     pub cookie_store_path: PathBuf,
-    pub maimai_uesr_data_path: PathBuf,
-    pub slack_post_webhook: Option<Url>,
+    pub user_data_path: PathBuf,
+    pub notifier: Arc<dyn Notifier>,
     pub estimate_internal_levels: bool,
     pub timeout_config: TimeoutConfig,
     pub report_no_updates: bool,
@@ -68,6 +83,7 @@ pub struct Config {
     pub force_paid_config: Option<ForcePaidConfig>,
     pub aime_switch_config: Option<AimeSwitchConfig>,
 
+    /// Song database of the game specified by `game`.
     pub database_path: Option<PathBuf>,
     pub estimator_config_path: Option<PathBuf>,
 
@@ -115,185 +131,288 @@ impl TimeoutConfig {
 }
 
 pub async fn watch(config: Config) -> anyhow::Result<WatchHandler> {
-    let (tx, mut rx) = mpsc::channel(100);
-
-    let data = load_or_create_user_data::<Maimai, _>(&config.maimai_uesr_data_path)?;
+    let (tx, rx) = mpsc::channel(100);
+    match config.game {
+        Game::Maimai => {
+            let data = load_or_create_user_data::<Maimai, _>(&config.user_data_path)?;
+            spawn(watch_maimai(config, data, rx));
+        }
+        Game::Ongeki => {
+            let data = load_or_create_user_data::<Ongeki, _>(&config.user_data_path)?;
+            spawn(watch_ongeki(config, data, rx));
+        }
+    }
+    Ok(WatchHandler(tx))
+}
 
-    spawn(async move {
-        let songs: Option<Vec<Song>> = match &config.database_path {
-            None => None,
-            Some(database_path) => report_error(
-                &config.slack_post_webhook,
-                &config.user_id,
-                read_json(database_path).context("Failed to load song database"),
-            )
-            .await
-            .ok(),
-        };
-        let database = match &songs {
-            None => None,
-            Some(songs) => report_error(
-                &config.slack_post_webhook,
-                &config.user_id,
-                SongDatabase::new(songs).context("Failed to construct song database"),
-            )
-            .await
-            .ok(),
-        };
-        let mut estimator = match database.as_ref() {
-            None => None,
-            Some(database) => report_error(
-                &config.slack_post_webhook,
-                &config.user_id,
-                Estimator::new(database, MaimaiVersion::latest())
-                    .context("Failed to load estimator"),
-            )
-            .await
-            .ok(),
-        };
-        let estimator_config = match config.estimator_config_path.as_ref() {
-            None => None,
-            Some(path) => report_error(
-                &config.slack_post_webhook,
-                &config.user_id,
-                read_toml::<_, multi_user::Config>(path).context("Failed to read estmiator config"),
-            )
-            .await
-            .ok(),
-        };
-        run_estimator(
-            estimator.as_mut(),
-            estimator_config.as_ref(),
-            database.as_ref(),
+async fn watch_maimai(config: Config, data: MaimaiUserData, mut rx: mpsc::Receiver<()>) {
+    let songs: Option<Vec<Song>> = match &config.database_path {
+        None => None,
+        Some(database_path) => report_error(
             &config,
+            read_json(database_path).context("Failed to load song database"),
         )
-        .await;
-
-        let mut runner = Runner {
-            config: &config,
-            data,
-            database: database.as_ref(),
-            estimator_config: estimator_config.as_ref(),
-            estimator,
-        };
-
-        let mut last_update_time = Instant::now();
-        let mut count = 0;
-
-        'outer: while let Err(TryRecvError::Empty | TryRecvError::Disconnected) = rx.try_recv() {
-            let run = if config.international {
-                runner.run::<MaimaiIntl>().await
-            } else {
-                runner.run::<Maimai>().await
+        .await
+        .ok(),
+    };
+    let database = match &songs {
+        None => None,
+        Some(songs) => report_error(
+            &config,
+            SongDatabase::new(songs).context("Failed to construct song database"),
+        )
+        .await
+        .ok(),
+    };
+    let mut estimator = match database.as_ref() {
+        None => None,
+        Some(database) => report_error(
+            &config,
+            Estimator::new(database, MaimaiVersion::latest()).context("Failed to load estimator"),
+        )
+        .await
+        .ok(),
+    };
+    let estimator_config = match config.estimator_config_path.as_ref() {
+        None => None,
+        Some(path) => report_error(
+            &config,
+            read_toml::<_, multi_user::Config>(path).context("Failed to read estmiator config"),
+        )
+        .await
+        .ok(),
+    };
+    let initial_update = run_estimator(
+        estimator.as_mut(),
+        estimator_config.as_ref(),
+        database.as_ref(),
+        &config,
+    )
+    .await
+    .ok();
+
+    let mut runner = MaimaiRunner {
+        config: &config,
+        data,
+        database: database.as_ref(),
+        estimator_config: estimator_config.as_ref(),
+        estimator,
+        incremental_update: IncrementalUpdate {
+            last_full_update: initial_update.as_ref().map(|_| Instant::now()),
+            song_score_list: initial_update.flatten(),
+        },
+    };
+    watch_loop(&config, &mut rx, &mut runner).await;
+
+    if let Some(force_paid) = &config.force_paid_config {
+        if config.international {
+            error!("There is no paid course for maimai interantional!  Skipping the swithcing back process.");
+        } else if let Some(after_use) = &force_paid.after_use {
+            let init = SegaClientInitializer {
+                credentials_path: &config.credentials_path,
+                cookie_store_path: &config.cookie_store_path,
+                user_identifier: after_use,
+                force_paid: true,
             };
-            match run {
-                Err(e) => {
-                    error!("{e:#}");
-                    webhook_send(
-                        &reqwest::Client::new(),
-                        &config.slack_post_webhook,
-                        &config.user_id,
-                        format!("{e:#}"),
-                    )
-                    .await;
-                }
-                Ok(updates) => {
-                    if updates {
-                        last_update_time = Instant::now();
-                    } else if config.report_no_updates {
-                        webhook_send(
-                            &reqwest::Client::new(),
-                            &config.slack_post_webhook,
+            match Maimai::new_client(init).await {
+                Ok(_) => {
+                    config
+                        .notifier
+                        .notify(
                             &config.user_id,
-                            "Already up to date.",
+                            "Standard course has been given back to the original account.",
                         )
                         .await;
-                    }
+                }
+                Err(e) => {
+                    let e = e.context("Failed to switch back the paid account");
+                    error!("{e:#}");
+                    config
+                        .notifier
+                        .notify(&config.user_id, format!("{e:#}"))
+                        .await;
                 }
             }
-            if config.timeout_config.max_count == 1 {
-                break;
+        }
+    }
+
+    finish(&config);
+}
+
+async fn watch_ongeki(config: Config, data: OngekiUserData, mut rx: mpsc::Receiver<()>) {
+    let songs: Option<Vec<ongeki::song_list::Song>> = match &config.database_path {
+        None => None,
+        Some(database_path) => report_error(
+            &config,
+            read_json(database_path).context("Failed to load song database"),
+        )
+        .await
+        .ok(),
+    };
+    let database = match &songs {
+        None => None,
+        Some(songs) => report_error(
+            &config,
+            ongeki::song_list::database::SongDatabase::new(songs)
+                .context("Failed to construct song database"),
+        )
+        .await
+        .ok(),
+    };
+
+    let mut runner = OngekiRunner {
+        config: &config,
+        data,
+        database: database.as_ref(),
+    };
+    watch_loop(&config, &mut rx, &mut runner).await;
+
+    finish(&config);
+}
+
+/// Runs `runner` repeatedly until stopped or timed out.
+async fn watch_loop(config: &Config, rx: &mut mpsc::Receiver<()>, runner: &mut impl Runner) {
+    let mut last_update_time = Instant::now();
+    let mut count = 0;
+
+    'outer: while let Err(TryRecvError::Empty | TryRecvError::Disconnected) = rx.try_recv() {
+        match runner.run().await {
+            Err(e) => {
+                error!("{e:#}");
+                config
+                    .notifier
+                    .notify(&config.user_id, format!("{e:#}"))
+                    .await;
             }
-            let chunk = Duration::from_millis(250);
-            for remaining in successors(Some(config.interval), |x| x.checked_sub(chunk)) {
-                sleep(remaining.min(chunk)).await;
-                if !matches!(rx.try_recv(), Err(TryRecvError::Empty)) {
-                    break 'outer;
+            Ok(updates) => {
+                if updates {
+                    last_update_time = Instant::now();
+                } else if config.report_no_updates {
+                    config
+                        .notifier
+                        .notify(&config.user_id, "Already up to date.")
+                        .await;
                 }
             }
+        }
+        if config.timeout_config.max_count == 1 {
+            break;
+        }
+        let chunk = Duration::from_millis(250);
+        for remaining in successors(Some(config.interval), |x| x.checked_sub(chunk)) {
+            sleep(remaining.min(chunk)).await;
+            if !matches!(rx.try_recv(), Err(TryRecvError::Empty)) {
+                break 'outer;
+            }
+        }
 
-            count += 1;
-            if count >= config.timeout_config.max_count {
-                break;
-            } else if (Instant::now() - last_update_time) >= config.timeout_config.max_duration {
-                webhook_send(
-                    &reqwest::Client::new(),
-                    &config.slack_post_webhook,
+        count += 1;
+        if count >= config.timeout_config.max_count {
+            break;
+        } else if (Instant::now() - last_update_time) >= config.timeout_config.max_duration {
+            config
+                .notifier
+                .notify(
                     &config.user_id,
                     "There have been no updates for a while.  Stopping automatically.".to_string(),
                 )
                 .await;
-                break;
-            }
-        }
-
-        if let Some(force_paid) = config.force_paid_config {
-            if config.international {
-                error!("There is no paid course for maimai interantional!  Skipping the swithcing back process.");
-            } else if let Some(after_use) = force_paid.after_use {
-                let init = SegaClientInitializer {
-                    credentials_path: &config.credentials_path,
-                    cookie_store_path: &config.cookie_store_path,
-                    user_identifier: &after_use,
-                    force_paid: true,
-                };
-                match Maimai::new_client(init).await {
-                    Ok(_) => {
-                        webhook_send(
-                            &reqwest::Client::new(),
-                            &config.slack_post_webhook,
-                            &config.user_id,
-                            "Standard course has been given back to the original account.",
-                        )
-                        .await;
-                    }
-                    Err(e) => {
-                        let e = e.context("Failed to switch back the paid account");
-                        error!("{e:#}");
-                        webhook_send(
-                            &reqwest::Client::new(),
-                            &config.slack_post_webhook,
-                            &config.user_id,
-                            format!("{e:#}"),
-                        )
-                        .await;
-                    }
-                }
-            }
+            break;
         }
+    }
+}
 
-        if let Some(finish_flag) = config.finish_flag {
-            finish_flag.store(true, std::sync::atomic::Ordering::Release);
-        }
-    });
-    Ok(WatchHandler(tx))
+fn finish(config: &Config) {
+    if let Some(finish_flag) = &config.finish_flag {
+        finish_flag.store(true, std::sync::atomic::Ordering::Release);
+    }
 }
 
+/// Updates the estimator with the data of all users,
+/// and returns the song score list read there for the subsequent incremental updates.
 async fn run_estimator<'s, 'n>(
     estimator: Option<&mut MultiUserEstimator<'s, 'n>>,
     estimator_config: Option<&'n multi_user::Config>,
     database: Option<&SongDatabase<'s>>,
     config: &Config,
-) {
-    if let Some(((estimator, estimator_config), database)) =
+) -> anyhow::Result<Option<AssociatedSongScoreList<'s>>> {
+    let Some(((estimator, estimator_config), database)) =
         estimator.zip(estimator_config).zip(database)
-    {
+    else {
+        return Ok(None);
+    };
+    report_error(
+        config,
+        (|| {
+            let datas = estimator_config.read_all()?;
+            let datas = multi_user::associate_all(database, &datas)?;
+            multi_user::estimate_all(&datas, estimator)?;
+            anyhow::Ok(datas.song_score_list)
+        })(),
+    )
+    .await
+}
+
+/// The lists of the other users are taken into account only by the full update,
+/// so it is run at least this often even if the incremental update is possible.
+const FULL_ESTIMATOR_UPDATE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+struct IncrementalUpdate<'s> {
+    /// Read by the last full update.
+    song_score_list: Option<AssociatedSongScoreList<'s>>,
+    /// `None` if no full update has succeeded yet.
+    last_full_update: Option<Instant>,
+}
+impl<'s> IncrementalUpdate<'s> {
+    /// Updates the estimator with the rating target lists fetched along with `inserted_records`,
+    /// without reading the data of other users again.
+    /// Falls back to the full update if this user is not in the estimator config,
+    /// if no full update has succeeded yet,
+    /// or if `FULL_ESTIMATOR_UPDATE_INTERVAL` has passed since the last full update.
+    async fn run<'n>(
+        &mut self,
+        estimator: Option<&mut MultiUserEstimator<'s, 'n>>,
+        estimator_config: Option<&'n multi_user::Config>,
+        database: Option<&SongDatabase<'s>>,
+        associated: Option<&associated_user_data::UserData<'_, 's>>,
+        inserted_records: &[PlayTime],
+        config: &Config,
+    ) {
+        let Some(estimator) = estimator else {
+            return;
+        };
+        let user = estimator_config.and_then(|x| x.user_by_data_path(&config.user_data_path));
+        if estimator_config.is_some() && user.is_none() {
+            warn!(
+                "{} is not in the estimator config; falling back to the full update",
+                config.user_data_path.display()
+            );
+        }
+        let user = user.filter(|_| {
+            (self.last_full_update).is_some_and(|t| t.elapsed() < FULL_ESTIMATOR_UPDATE_INTERVAL)
+        });
+        let Some(user) = user else {
+            let result = run_estimator(Some(estimator), estimator_config, database, config).await;
+            if let Ok(song_score_list) = result {
+                self.song_score_list = song_score_list;
+                self.last_full_update = Some(Instant::now());
+            }
+            return;
+        };
+        let Some(associated) = associated else {
+            return;
+        };
         let _ = report_error(
-            &config.slack_post_webhook,
-            &config.user_id,
+            config,
             (|| {
-                let datas = estimator_config.read_all()?;
-                multi_user::update_all(database, &datas, estimator)?;
+                let data = associated.ordinary_data_associated()?;
+                multi_user::update_user_incrementally(
+                    user,
+                    &data,
+                    inserted_records,
+                    self.song_score_list.as_ref(),
+                    estimator,
+                )?;
                 anyhow::Ok(())
             })(),
         )
@@ -301,48 +420,49 @@ async fn run_estimator<'s, 'n>(
     }
 }
 
-struct Runner<'c, 's, 'd, 'ec> {
+/// A single iteration of the watcher for a specific game.
+trait Runner {
+    /// Returns `true` if there were new records.
+    async fn run(&mut self) -> anyhow::Result<bool>;
+}
+
+struct MaimaiRunner<'c, 's, 'd, 'ec> {
     config: &'c Config,
     data: MaimaiUserData,
     database: Option<&'d SongDatabase<'s>>,
     estimator_config: Option<&'ec multi_user::Config>,
     estimator: Option<MultiUserEstimator<'s, 'ec>>,
+    incremental_update: IncrementalUpdate<'s>,
 }
-impl<'c> Runner<'c, '_, '_, '_> {
-    async fn run<T>(&mut self) -> anyhow::Result<bool>
+impl Runner for MaimaiRunner<'_, '_, '_, '_> {
+    async fn run(&mut self) -> anyhow::Result<bool> {
+        if self.config.international {
+            self.run_as::<MaimaiIntl>().await
+        } else {
+            self.run_as::<Maimai>().await
+        }
+    }
+}
+impl<'c> MaimaiRunner<'c, '_, '_, '_> {
+    async fn run_as<T>(&mut self) -> anyhow::Result<bool>
     where
         T: MaimaiPossiblyIntl,
     {
         let config = self.config;
 
-        // Select aime if specified
-        if let Some(aime) = &config.aime_switch_config {
-            let credentials = read_json(&config.credentials_path)?;
-            let (api, aimes) = AimeApi::new(aime.cookie_store_path.to_owned())?
-                .login(&credentials)
-                .await?;
-            api.overwrite_if_absent(
-                &aimes,
-                aime.slot_index,
-                aime.access_code,
-                aime.card_name.clone(),
-            )
-            .await?;
-            sleep(Duration::from_secs(1)).await;
-            info!("Switched aime.")
-        }
+        switch_aime(config).await?;
 
         // Obtain the `force paid` flag (or `()` if international)
         let (force_paid, warn) = T::force_paid(config.force_paid_config.is_some());
         if warn {
             warn!("There is no Standard Course for Maimai International!");
-            webhook_send(
-                &reqwest::Client::new(),
-                &config.slack_post_webhook,
-                &config.user_id,
-                "There is no Standard Course for Maimai International!",
-            )
-            .await;
+            config
+                .notifier
+                .notify(
+                    &config.user_id,
+                    "There is no Standard Course for Maimai International!",
+                )
+                .await;
         }
 
         // Initialize sega client
@@ -355,15 +475,16 @@ impl<'c> Runner<'c, '_, '_, '_> {
         let (mut client, index) = T::new_client(init).await?;
 
         // For safety, we will be saving data over and over again
-        write_json(&config.maimai_uesr_data_path, &self.data)?;
+        write_json(&config.user_data_path, &self.data)?;
 
         // Retrieve records
         let last_played = index.first().context("There is no play yet.")?.0;
+        let last_version = latest_record_version(&self.data);
         let inserted_records = update_records(&mut client, &mut self.data.records, index).await?;
         if inserted_records.is_empty() {
             return Ok(false);
         }
-        write_json(&config.maimai_uesr_data_path, &self.data)?;
+        write_json(&config.user_data_path, &self.data)?;
 
         // Retrieve rating target list
         let update_targets_res = T::update_targets(
@@ -374,34 +495,23 @@ impl<'c> Runner<'c, '_, '_, '_> {
         )
         .await
         .context("Rating target not available");
-        let update_targets_res = report_error(
-            &config.slack_post_webhook,
-            &config.user_id,
-            update_targets_res,
-        )
-        .await;
+        let update_targets_res = report_error(config, update_targets_res).await;
         if update_targets_res.is_ok() {
-            webhook_send(
-                client.reqwest(),
-                &config.slack_post_webhook,
-                &config.user_id,
-                "Rating target updated",
-            )
-            .await;
+            config
+                .notifier
+                .notify(&config.user_id, "Rating target updated")
+                .await;
         }
 
         // Retrieve idx to icon map
         if let Ok(Some(rating_targets)) = update_targets_res {
             T::update_idx(&mut client, rating_targets, &mut self.data.idx_to_icon_map).await?;
-            webhook_send(
-                client.reqwest(),
-                &config.slack_post_webhook,
-                &config.user_id,
-                "Disambiguation list updated",
-            )
-            .await;
+            config
+                .notifier
+                .notify(&config.user_id, "Disambiguation list updated")
+                .await;
         }
-        write_json(&config.maimai_uesr_data_path, &self.data)?;
+        write_json(&config.user_data_path, &self.data)?;
 
         // Retrieval ends here.
 
@@ -409,8 +519,7 @@ impl<'c> Runner<'c, '_, '_, '_> {
         let associated = match &self.database {
             None => None,
             Some(database) => report_error(
-                &config.slack_post_webhook,
-                &config.user_id,
+                config,
                 associated_user_data::UserData::annotate(database, &self.data)
                     .context("Failed to associate record with database"),
             )
@@ -418,48 +527,144 @@ impl<'c> Runner<'c, '_, '_, '_> {
             .ok(),
         };
 
-        // Try to update internal level estimator.
-        // HACK: to take all data into account, we read user data again from scratch.
-        // Can we improve it?
+        // Try to update internal level estimator with the newly fetched rating target lists.
         let before_len = self.estimator.as_ref().map_or(0, |x| x.event_len());
-        run_estimator(
-            self.estimator.as_mut(),
-            self.estimator_config,
-            self.database,
-            config,
-        )
-        .await;
+        self.incremental_update
+            .run(
+                self.estimator.as_mut(),
+                self.estimator_config,
+                self.database,
+                associated.as_ref(),
+                &inserted_records,
+                config,
+            )
+            .await;
 
         // Now the results are reported to Slack.
-        for time in inserted_records {
+        for &time in &inserted_records {
             let record = &self.data.records[&time];
             let associated = associated.as_ref().and_then(|x| x.records().get(&time));
             let level = try_get_level(self.estimator.as_ref(), associated);
-            webhook_send(
-                client.reqwest(),
-                &config.slack_post_webhook,
-                &config.user_id,
-                make_message(record, associated, level).to_string(),
-            )
-            .await;
+            let mut message = make_message(record, associated, level).to_string();
+            let previous_best = deluxscore::best_before(&self.data, record);
+            if let Some(line) = make_star_upgrade(record, previous_best) {
+                message += &line;
+            }
+            config.notifier.notify(&config.user_id, message).await;
         }
 
         if let Some(estimator) = &self.estimator {
             for event in &estimator.events()[before_len..] {
-                webhook_send(
-                    client.reqwest(),
-                    &config.slack_post_webhook,
+                config
+                    .notifier
+                    .notify(&config.user_id, format!("★ {event}"))
+                    .await;
+            }
+        }
+
+        // Summarize the internal level changes on the first play in a new version.
+        let current_version = latest_record_version(&self.data);
+        if let (Some(database), Some(previous), Some(current)) =
+            (self.database, last_version, current_version)
+        {
+            if previous < current {
+                let report =
+                    LvChangeReport::new(database, self.estimator.as_ref(), previous, current);
+                config
+                    .notifier
+                    .notify(&config.user_id, report.summary())
+                    .await;
+            }
+        }
+
+        Ok(true)
+    }
+}
+struct OngekiRunner<'c, 's, 'd> {
+    config: &'c Config,
+    data: OngekiUserData,
+    database: Option<&'d ongeki::song_list::database::SongDatabase<'s>>,
+}
+impl Runner for OngekiRunner<'_, '_, '_> {
+    async fn run(&mut self) -> anyhow::Result<bool> {
+        let config = self.config;
+
+        switch_aime(config).await?;
+
+        if config.force_paid_config.is_some() {
+            warn!("Explicit paid course for ongeki is not implemented yet!");
+            config
+                .notifier
+                .notify(
                     &config.user_id,
-                    format!("★ {event}"),
+                    "Explicit paid course for ongeki is not implemented yet!",
+                )
+                .await;
+        }
+
+        // Initialize sega client
+        let init = SegaClientInitializer {
+            credentials_path: &config.credentials_path,
+            cookie_store_path: &config.cookie_store_path,
+            user_identifier: &config.user_identifier,
+            force_paid: false,
+        };
+        let (mut client, index) = SegaClient::<Ongeki>::new(init).await?;
+
+        // For safety, we will be saving data over and over again
+        write_json(&config.user_data_path, &self.data)?;
+
+        // Retrieve records
+        let inserted_records = update_records(&mut client, &mut self.data.records, index).await?;
+        if inserted_records.is_empty() {
+            return Ok(false);
+        }
+        write_json(&config.user_data_path, &self.data)?;
+
+        // Now the results are reported to Slack.
+        for time in inserted_records {
+            let record = &self.data.records[&time];
+            let associated = self.database.map(|database| {
+                ongeki::associated_user_data::PlayRecord::annotate(database, record)
+            });
+            config
+                .notifier
+                .notify(
+                    &config.user_id,
+                    describe_ongeki_record::make_message(record, associated.as_ref()).to_string(),
                 )
                 .await;
-            }
         }
 
         Ok(true)
     }
 }
 
+fn latest_record_version(data: &MaimaiUserData) -> Option<MaimaiVersion> {
+    let (time, _) = data.records.last_key_value()?;
+    MaimaiVersion::of_time(time.get())
+}
+
+/// Selects aime if specified.
+async fn switch_aime(config: &Config) -> anyhow::Result<()> {
+    if let Some(aime) = &config.aime_switch_config {
+        let credentials = read_json(&config.credentials_path)?;
+        let (api, aimes) = AimeApi::new(aime.cookie_store_path.to_owned())?
+            .login(&credentials)
+            .await?;
+        api.overwrite_if_absent(
+            &aimes,
+            aime.slot_index,
+            aime.access_code,
+            aime.card_name.clone(),
+        )
+        .await?;
+        sleep(Duration::from_secs(1)).await;
+        info!("Switched aime.")
+    }
+    Ok(())
+}
+
 trait MaimaiPossiblyIntl
 where
     Self: SegaTrait<PlayRecord = PlayRecord>,
@@ -548,14 +753,13 @@ impl MaimaiPossiblyIntl for MaimaiIntl {
     }
 }
 
-async fn report_error<T>(
-    url: &Option<Url>,
-    user_id: &UserId,
-    result: anyhow::Result<T>,
-) -> anyhow::Result<T> {
+async fn report_error<T>(config: &Config, result: anyhow::Result<T>) -> anyhow::Result<T> {
     if let Err(e) = &result {
         error!("{e:#}");
-        webhook_send(&reqwest::Client::new(), url, user_id, format!("{e:#}")).await;
+        config
+            .notifier
+            .notify(&config.user_id, format!("{e:#}"))
+            .await;
     }
     result
 }
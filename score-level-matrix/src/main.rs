@@ -1,7 +1,7 @@
-use std::path::PathBuf;
+use std::{io::BufWriter, path::PathBuf};
 
 use anyhow::Result;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use google_sheets4::{
     api::ValueRange,
     hyper_rustls, hyper_util,
@@ -9,111 +9,75 @@ use google_sheets4::{
     Sheets,
 };
 use itertools::Itertools;
-use lazy_format::lazy_format;
 use maimai_scraping::maimai::{
-    schema::latest::ScoreGeneration,
+    level_matrix::{LevelMatrix, SIZE},
     song_list::{database::SongDatabase, Song},
     version::MaimaiVersion,
 };
 use maimai_scraping_utils::fs_json_util::read_json;
+use rust_xlsxwriter::{Format, Workbook};
 
 #[derive(Parser)]
 struct Opts {
     database_path: PathBuf,
-    key_path: PathBuf,
-    spreadsheet_id: String,
+    /// Defaults to the latest version
+    #[arg(long)]
+    version: Option<MaimaiVersion>,
+    #[command(subcommand)]
+    output: Output,
+}
+
+#[derive(Subcommand)]
+enum Output {
+    /// Writes to a spreadsheet in Google Sheets, starting from B2
+    Sheets {
+        key_path: PathBuf,
+        spreadsheet_id: String,
+    },
+    Csv {
+        path: PathBuf,
+    },
+    Xlsx {
+        path: PathBuf,
+    },
+    /// A heatmap of the number of songs in each cell
+    Html {
+        path: PathBuf,
+    },
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    env_logger::init();
     let opts = Opts::parse();
 
     let songs: Vec<Song> = read_json(opts.database_path)?;
     let database = SongDatabase::new(&songs)?;
 
-    let mut matrix = {
-        let size = 150 - 10 + 1;
-        vec![vec![vec![]; size]; size]
-    };
+    let version = opts.version.unwrap_or(MaimaiVersion::latest());
+    let matrix = LevelMatrix::new(&database, version);
 
-    let version = MaimaiVersion::latest();
-    for song in database.songs() {
-        for scores in song.scoreses() {
-            let vec = scores
-                .all_scores()
-                .filter_map(|x| {
-                    let score = x.for_version(version)?;
-                    let Some(level) = score.level() else {
-                        println!("Warning: level not found for {x}");
-                        return None;
-                    };
-                    let level =
-                        level
-                            .get_if_unique()
-                            .or_else(|| match level.candidates().next() {
-                                Some(x) => {
-                                    println!("Warning: internal level unknown for {x}");
-                                    println!("         (falling back to lowest possible)");
-                                    Some(x)
-                                }
-                                None => {
-                                    println!("Warning: no internal level candidate for {x} (!!!)");
-                                    None
-                                }
-                            })?;
-                    let difficulty = score.score().difficulty();
-                    let level_usize = (150 - u8::from(level)) as usize;
-                    Some((difficulty, level_usize))
-                })
-                .collect_vec();
-            for &(a, x) in &vec {
-                for &(b, y) in &vec {
-                    if (x, a) < (y, b) {
-                        matrix[y][x].push(scores);
-                    }
-                }
-            }
-        }
+    match opts.output {
+        Output::Sheets {
+            key_path,
+            spreadsheet_id,
+        } => write_sheets(&matrix, key_path, &spreadsheet_id).await?,
+        Output::Csv { path } => matrix.write_csv(BufWriter::new(fs_err::File::create(path)?))?,
+        Output::Xlsx { path } => write_xlsx(&matrix, path)?,
+        Output::Html { path } => fs_err::write(path, matrix.to_html())?,
     }
 
-    // for (x, (i, j)) in matrix
-    //     .iter()
-    //     .enumerate()
-    //     .flat_map(|(i, a)| a.iter().enumerate().map(move |(j, b)| (b.len(), (i, j))))
-    //     .sorted_by_key(|&x| std::cmp::Reverse(x))
-    // {
-    //     let f = |x| (150 - x + 10) as f64 / 10.;
-    //     println!("{x} {:.1} {:.1}", f(i), f(j));
-    // }
+    Ok(())
+}
 
+async fn write_sheets(matrix: &LevelMatrix<'_>, key_path: PathBuf, id: &str) -> Result<()> {
     let table = matrix
-        .iter_mut()
-        .map(|x| {
-            x.iter_mut()
-                .map(|x| {
-                    x.sort_by_key(|x| x.song().song().pronunciation.values().flatten().last());
-                    x.iter()
-                        .map(|x| {
-                            let name = x.song().latest_song_name();
-                            let has_alternative = x.song().scoreses().count() >= 2;
-                            let generation = has_alternative.then_some(x.generation());
-                            let generation = lazy_format!(match (generation) {
-                                Some(ScoreGeneration::Deluxe) => " (DX)",
-                                Some(ScoreGeneration::Standard) => " (Std)",
-                                None => "",
-                            });
-                            format!("{name}{generation}")
-                        })
-                        .join("\n")
-                        .into()
-                })
-                .collect_vec()
-        })
+        .labels()
+        .into_iter()
+        .map(|row| row.into_iter().map(Into::into).collect_vec())
         .collect_vec();
 
-    // println!("{:?}", &table[..5]);
-
-    let key = read_service_account_key(opts.key_path).await?;
+    let key = read_service_account_key(key_path).await?;
     let auth = ServiceAccountAuthenticator::builder(key).build().await?;
     let client = hyper_util::client::legacy::Client::builder(hyper_util::rt::TokioExecutor::new())
         .build(
@@ -134,7 +98,7 @@ async fn main() -> Result<()> {
                 range: None,
                 values: Some(table),
             },
-            &opts.spreadsheet_id,
+            id,
             "B2",
         )
         .value_input_option("RAW")
@@ -144,3 +108,31 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+fn write_xlsx(matrix: &LevelMatrix<'_>, path: PathBuf) -> Result<()> {
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet();
+    let header = Format::new().set_bold();
+    let wrap = Format::new().set_text_wrap();
+    for i in 0..SIZE {
+        let level = LevelMatrix::level(i).to_string();
+        let pos = (i + 1) as u16;
+        worksheet.write_string_with_format(0, pos, &level, &header)?;
+        worksheet.write_string_with_format(pos.into(), 0, &level, &header)?;
+    }
+    for (i, row) in matrix.labels().into_iter().enumerate() {
+        for (j, label) in row.into_iter().enumerate() {
+            if !label.is_empty() {
+                worksheet.write_string_with_format(
+                    (i + 1) as u32,
+                    (j + 1) as u16,
+                    &label,
+                    &wrap,
+                )?;
+            }
+        }
+    }
+    worksheet.set_freeze_panes(1, 1)?;
+    workbook.save(path)?;
+    Ok(())
+}